@@ -1,6 +1,10 @@
-use log::error;
-use std::{net::Ipv4Addr, sync::Arc, time::Instant};
+use log::{error, info, warn};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use protocol::chat::ChatChannel;
 use winit::{
     application::ApplicationHandler,
     event::{DeviceEvent, KeyEvent, WindowEvent},
@@ -9,20 +13,86 @@ use winit::{
     window::WindowAttributes,
 };
 
-use crate::{network::Network, renderer::Renderer};
+use crate::{
+    config::ClientConfig,
+    network::{Network, ServerInfo},
+    renderer::Renderer,
+};
 
-#[derive(Default)]
 pub struct AppState {
+    config: ClientConfig,
     renderer: Option<Renderer>,
     prev_frame_time: Option<Instant>,
     network_handler: Option<Network>,
+    server_info: Option<ServerInfo>,
 }
 
 impl AppState {
-    fn cleanup(&self, event_loop: &ActiveEventLoop) {
-        if let Some(ref network_handler) = self.network_handler {
-            network_handler.send_player_leave().unwrap();
+    const INTERPOLATION_DELAY: Duration = Duration::from_millis(100);
+
+    pub fn new(config: ClientConfig) -> Self {
+        Self {
+            config,
+            renderer: None,
+            prev_frame_time: None,
+            network_handler: None,
+            server_info: None,
+        }
+    }
+
+    /// Connects and joins the configured server, or explains why not.
+    fn join_server(&mut self) {
+        let config = &self.config;
+        let mut network_handler = match Network::new(&config.host, config.port) {
+            Ok(network_handler) => network_handler,
+            Err(e) => {
+                error!(
+                    "could not connect to {}:{}: {e}, playing offline",
+                    config.host, config.port
+                );
+                return;
+            }
         };
+        network_handler.set_interpolation_delay(Self::INTERPOLATION_DELAY);
+        match network_handler.send_player_join(&config.player_name) {
+            Ok(server_info) => {
+                info!(
+                    "joined {}:{} as {} on {} ({} ms ticks)",
+                    config.host,
+                    config.port,
+                    server_info.player_id,
+                    server_info.map,
+                    server_info.tick_rate.as_millis()
+                );
+                if config
+                    .map_file
+                    .file_stem()
+                    .is_none_or(|stem| *stem != *server_info.map)
+                {
+                    warn!(
+                        "server is running {}, not {}",
+                        server_info.map,
+                        config.map_file.display()
+                    );
+                }
+                self.server_info = Some(server_info);
+                self.network_handler = Some(network_handler);
+            }
+            Err(e) => {
+                error!(
+                    "could not join {}:{}: {e}, playing offline",
+                    config.host, config.port
+                );
+            }
+        }
+    }
+
+    fn cleanup(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(ref mut network_handler) = self.network_handler
+            && let Err(e) = network_handler.send_player_leave()
+        {
+            error!("failed to leave the server: {e}");
+        }
         event_loop.exit();
     }
 }
@@ -35,25 +105,18 @@ impl ApplicationHandler for AppState {
                 .unwrap(),
         );
 
-        self.renderer = match pollster::block_on(Renderer::new(
-            window.clone(),
-            String::from("client/src/model/maps/map_1.json"),
-        )) {
+        let map_file = self.config.map_file.to_string_lossy().into_owned();
+        self.renderer = match pollster::block_on(Renderer::new(window.clone(), map_file)) {
             Ok(r) => Some(r),
             Err(e) => {
                 error!("{e}");
                 std::process::exit(1);
             }
         };
-        self.network_handler = match Network::new(Ipv4Addr::new(127, 0, 0, 1), 8003) {
-            Ok(nh) => Some(nh),
-            Err(_) => {
-                error!("A network setup error occurred!");
-                None
-            }
-        };
-        if let Some(ref network_handler) = self.network_handler {
-            network_handler.send_player_join().unwrap();
+        if self.config.offline {
+            info!("playing offline");
+        } else {
+            self.join_server();
         }
         self.prev_frame_time = Some(Instant::now());
         window.request_redraw();
@@ -69,32 +132,28 @@ impl ApplicationHandler for AppState {
             return;
         };
 
-        //send movement data
         if let Some(ref mut network_handler) = self.network_handler {
             network_handler.poll();
-            let player = renderer.get_player();
-            if network_handler
-                .send_player_move(
-                    player.position.into(),
-                    player.velocity.into(),
-                    player.pitch,
-                    player.yaw,
-                )
-                .is_err()
-            {
-                error!("Server-Client desync!");
-            };
         }
         match event {
             WindowEvent::CloseRequested => {
                 self.cleanup(event_loop);
             }
             WindowEvent::RedrawRequested => {
-                renderer.update(
+                let input = renderer.update(
                     self.prev_frame_time.unwrap_or_else(Instant::now).elapsed(),
-                    &self.network_handler,
+                    &mut self.network_handler,
                 );
                 self.prev_frame_time = Some(Instant::now());
+                let shot = renderer.take_shot();
+                if let Some(ref mut network_handler) = self.network_handler {
+                    if let Some((weapon, direction)) = shot {
+                        network_handler.send_fire(weapon, direction);
+                    }
+                    if network_handler.send_player_input(input).is_err() {
+                        error!("Server-Client desync!");
+                    }
+                }
                 match renderer.render() {
                     Ok(_) => {}
                     Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
@@ -106,28 +165,57 @@ impl ApplicationHandler for AppState {
                     }
                 }
             }
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(code),
-                        state,
-                        ..
-                    },
-                ..
-            } => {
+            WindowEvent::KeyboardInput { event, .. } => {
+                if renderer.get_mut_chat_input().is_typing() {
+                    if let Some((channel, text)) = renderer.get_mut_chat_input().handle_key(&event)
+                        && let Some(ref mut network_handler) = self.network_handler
+                    {
+                        network_handler.send_chat(channel, &text);
+                    }
+                    if !renderer.get_mut_chat_input().is_typing() {
+                        renderer.get_mut_player_controller().set_typing(false);
+                    }
+                    return;
+                }
+                let KeyEvent {
+                    physical_key: PhysicalKey::Code(code),
+                    state,
+                    repeat,
+                    ..
+                } = event
+                else {
+                    return;
+                };
                 // Special key for reloading the renderer
                 if code == KeyCode::KeyB && state.is_pressed() {
                     renderer.rerender();
                     renderer.get_window().as_ref().request_redraw();
                 } else if code == KeyCode::Escape && state.is_pressed() {
                     self.cleanup(event_loop);
+                } else if matches!(code, KeyCode::KeyT | KeyCode::KeyY)
+                    && state.is_pressed()
+                    && !repeat
+                {
+                    // T talks to everyone and Y to the team.
+                    let channel = if code == KeyCode::KeyT {
+                        ChatChannel::All
+                    } else {
+                        ChatChannel::Team
+                    };
+                    renderer.get_mut_chat_input().open(channel);
+                    renderer.get_mut_player_controller().set_typing(true);
                 } else if renderer
                     .get_mut_player_controller()
-                    .handle_key_held(code, state, event_loop)
+                    .handle_key_held(code, state)
                 {
                     renderer.get_window().as_ref().request_redraw();
                 }
             }
+            WindowEvent::MouseInput { state, button, .. } => {
+                renderer
+                    .get_mut_player_controller()
+                    .handle_mouse_button(button, state);
+            }
             _ => (),
         }
     }
@@ -141,11 +229,8 @@ impl ApplicationHandler for AppState {
         let Some(renderer) = &mut self.renderer else {
             return;
         };
-        match event {
-            DeviceEvent::MouseMotion { delta } => {
-                renderer.get_mut_player_controller().handle_mouse(delta);
-            }
-            _ => {}
+        if let DeviceEvent::MouseMotion { delta } = event {
+            renderer.get_mut_player_controller().handle_mouse(delta);
         }
     }
 }
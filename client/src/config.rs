@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
+use protocol::config::{ConfigError, LayeredConfig, set_flag};
+use protocol::player_state::{MAX_NAME_LENGTH, sanitize_name};
+use serde::Deserialize;
+
+/// How the client finds its server, who it plays as and on which map. See
+/// `client/client.example.toml` for a file holding every setting.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientConfig {
+    /// Host name or IP address (v4 or v6) of the server.
+    pub host: String,
+    pub port: u16,
+    pub player_name: String,
+    pub map_file: PathBuf,
+    /// Plays locally without contacting any server.
+    pub offline: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            host: String::from("127.0.0.1"),
+            port: 8003,
+            player_name: String::from("player"),
+            map_file: PathBuf::from("client/src/model/maps/map_1.json"),
+            offline: false,
+        }
+    }
+}
+
+impl LayeredConfig for ClientConfig {
+    fn cli() -> Command {
+        Command::new("client")
+            .about("Mood, a first person shooter")
+            .arg(
+                Arg::new("host")
+                    .long("host")
+                    .value_name("HOST")
+                    .help("Server host name or IP address [default: 127.0.0.1]"),
+            )
+            .arg(
+                Arg::new("port")
+                    .long("port")
+                    .short('p')
+                    .value_parser(value_parser!(u16))
+                    .help("Server UDP port [default: 8003]"),
+            )
+            .arg(
+                Arg::new("player_name")
+                    .long("name")
+                    .short('n')
+                    .help("Name shown to other players [default: player]"),
+            )
+            .arg(
+                Arg::new("map_file")
+                    .long("map")
+                    .value_name("FILE")
+                    .value_parser(value_parser!(PathBuf))
+                    .help("Map to load"),
+            )
+            .arg(
+                Arg::new("offline")
+                    .long("offline")
+                    .action(ArgAction::SetTrue)
+                    .help("Play without connecting to a server"),
+            )
+    }
+
+    fn apply_flags(&mut self, matches: &ArgMatches) {
+        set_flag(matches, "host", &mut self.host);
+        set_flag(matches, "port", &mut self.port);
+        set_flag(matches, "player_name", &mut self.player_name);
+        set_flag(matches, "map_file", &mut self.map_file);
+        if matches.get_flag("offline") {
+            self.offline = true;
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if !self.offline && self.host.trim().is_empty() {
+            return Err(ConfigError::invalid("host", "must not be empty"));
+        }
+        if !self.offline && self.port == 0 {
+            return Err(ConfigError::invalid("port", "must be between 1 and 65535"));
+        }
+        let name_length = self.player_name.chars().count();
+        if !(1..=MAX_NAME_LENGTH).contains(&name_length) {
+            return Err(ConfigError::invalid(
+                "player_name",
+                format!("must be between 1 and {MAX_NAME_LENGTH} characters, got {name_length}"),
+            ));
+        }
+        if sanitize_name(&self.player_name) != self.player_name {
+            return Err(ConfigError::invalid(
+                "player_name",
+                "must not start or end with spaces or hold control characters",
+            ));
+        }
+        if !self.map_file.is_file() {
+            return Err(ConfigError::invalid(
+                "map_file",
+                format!("{} is not a readable file", self.map_file.display()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/model/maps/map_1.json");
+
+    /// The config from `args`, on a map that exists unless they name another.
+    fn with_flags(args: &[&str]) -> Result<ClientConfig, ConfigError> {
+        let map = if args.contains(&"--map") {
+            vec![]
+        } else {
+            vec!["--map", MAP]
+        };
+        ClientConfig::from_args(["client"].iter().chain(&map).chain(args))
+    }
+
+    fn refused_field(args: &[&str]) -> &'static str {
+        match with_flags(args) {
+            Err(ConfigError::Invalid { field, .. }) => field,
+            other => panic!("{args:?} gave {other:?}"),
+        }
+    }
+
+    #[test]
+    fn hosts_may_be_names_or_either_ip_version() {
+        for host in ["example.com", "localhost", "10.0.0.2", "::1", "[::1]"] {
+            assert_eq!(with_flags(&["--host", host]).unwrap().host, host);
+        }
+        assert_eq!(refused_field(&["--host", " "]), "host");
+        assert_eq!(refused_field(&["--port", "0"]), "port");
+    }
+
+    #[test]
+    fn offline_play_needs_no_server() {
+        let config = with_flags(&["--offline", "--host", "", "--port", "0"]).unwrap();
+        assert!(config.offline);
+    }
+
+    #[test]
+    fn the_map_must_exist() {
+        assert_eq!(refused_field(&["--map", "missing.json"]), "map_file");
+        assert_eq!(
+            refused_field(&["--map", env!("CARGO_MANIFEST_DIR")]),
+            "map_file"
+        );
+    }
+
+    #[test]
+    fn names_must_be_what_the_server_would_show() {
+        let longest = "é".repeat(MAX_NAME_LENGTH);
+        assert_eq!(
+            with_flags(&["--name", &longest]).unwrap().player_name,
+            longest
+        );
+        let too_long = "x".repeat(MAX_NAME_LENGTH + 1);
+        for name in ["", " padded", "tab\tbed", too_long.as_str()] {
+            assert_eq!(refused_field(&["--name", name]), "player_name", "{name:?}");
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use protocol::chat::{self, ChatChannel, MAX_CHAT_LENGTH};
+use winit::{
+    event::KeyEvent,
+    keyboard::{Key, NamedKey},
+};
+
+/// The line of chat being typed, if any. While it is open every key goes to
+/// the line instead of the player.
+#[derive(Default)]
+pub struct ChatInput {
+    draft: Option<(ChatChannel, String)>,
+}
+
+impl ChatInput {
+    pub fn is_typing(&self) -> bool {
+        self.draft.is_some()
+    }
+
+    /// Starts a new line for `channel`.
+    pub fn open(&mut self, channel: ChatChannel) {
+        self.draft = Some((channel, String::new()));
+    }
+
+    /// The channel and text typed so far.
+    pub fn draft(&self) -> Option<(ChatChannel, &str)> {
+        self.draft
+            .as_ref()
+            .map(|(channel, text)| (*channel, text.as_str()))
+    }
+
+    /// Types `event` into the open line. Enter closes it and returns the
+    /// line to send, if there is anything to send; escape throws it away.
+    pub fn handle_key(&mut self, event: &KeyEvent) -> Option<(ChatChannel, String)> {
+        if !event.state.is_pressed() {
+            return None;
+        }
+        let (_, text) = self.draft.as_mut()?;
+        match &event.logical_key {
+            Key::Named(NamedKey::Enter) => {
+                let (channel, text) = self.draft.take()?;
+                let text = chat::sanitize(&text);
+                return (!text.is_empty()).then_some((channel, text));
+            }
+            Key::Named(NamedKey::Escape) => self.draft = None,
+            Key::Named(NamedKey::Backspace) => {
+                text.pop();
+            }
+            _ => {
+                let typed = event.text.as_deref().unwrap_or_default();
+                for c in typed.chars().filter(|c| !c.is_control()) {
+                    if text.len() + c.len_utf8() > MAX_CHAT_LENGTH {
+                        break;
+                    }
+                    text.push(c);
+                }
+            }
+        }
+        None
+    }
+}
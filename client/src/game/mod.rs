@@ -1,4 +1,3 @@
-pub mod bounding_box;
-pub mod collision_manager;
+pub mod chat;
 pub mod player;
 pub mod player_controller;
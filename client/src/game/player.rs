@@ -1,125 +1,173 @@
 use std::time::Duration;
 
-use nalgebra::{Point3, Vector3};
+use game::{
+    collision_manager::CollisionManager,
+    player_body::{PlayerBody, StepClock},
+    prediction::Prediction,
+    world::World,
+};
+use nalgebra::Vector3;
+use protocol::input::PlayerInput;
+use uuid::Uuid;
 
-use crate::camera::Camera;
+use crate::{camera::Camera, network::LocalPlayerUpdate};
 
-use super::{
-    bounding_box::BoundingBox, collision_manager::CollisionManager,
-    player_controller::PlayerController,
-};
+use super::player_controller::PlayerController;
 
+/// The local player. Its body lives in the client's `World` under `id` so it
+/// is simulated by the same code as on the server.
 pub struct Player {
-    pub position: Point3<f32>,
-    pub velocity: Vector3<f32>,
+    id: Uuid,
     sensitivity: f32,
-    speed: f32,
-    jump_strength: f32,
-    hitbox: BoundingBox,
-    is_on_ground: bool,
+    next_sequence: u32,
+    /// Frame time not yet simulated, short of a whole sub-step.
+    clock: StepClock,
+    prediction: Prediction,
+    /// Visual offset left over from server corrections, decayed over a few
+    /// frames so the camera glides to the corrected position instead of snapping.
+    correction: Vector3<f32>,
+    /// Whether the server last reported this player alive; `None` until it
+    /// has reported anything, e.g. when playing offline.
+    alive: Option<bool>,
     pub camera: Camera,
-    pub yaw: f32,
-    pub pitch: f32,
 }
 
 impl Player {
-    const GRAVITY: f32 = 0.1;
-    const SLOW_DOWN: f32 = 0.0;
+    /// Fraction of the remaining correction removed per second.
+    const CORRECTION_RATE: f32 = 10.0;
+    /// Corrections larger than this are applied at once (e.g. teleports).
+    const MAX_SMOOTHED_CORRECTION: f32 = 1.0;
+    /// How far the death camera looks down on the body.
+    const DEATH_CAMERA_PITCH: f32 = -0.6;
+    /// How far the death camera stays from the body.
+    const DEATH_CAMERA_DISTANCE: f32 = 2.0;
 
-    pub fn new(
-        sensitivity: f32,
-        speed: f32,
-        jump_strength: f32,
-        hitbox_width: f32,
-        hitbox_height: f32,
-        camera: Camera,
-    ) -> Self {
-        let position = camera.position;
+    pub fn new(sensitivity: f32, mut camera: Camera, yaw: f32, world: &mut World) -> Self {
+        let id = Uuid::new_v4();
+        let body = world.spawn_player(id, camera.position);
+        body.yaw = yaw;
+        camera.rotate_camera(body.pitch, body.yaw);
         Self {
-            position,
-            velocity: Vector3::zeros(),
+            id,
             sensitivity,
-            speed,
-            jump_strength,
-            hitbox: BoundingBox {
-                top_left: Point3::new(
-                    position.x - (hitbox_width / 2.0),
-                    position.y,
-                    position.z - (hitbox_width / 2.0),
-                ),
-                bottom_right: Point3::new(
-                    position.x + (hitbox_width / 2.0),
-                    position.y - hitbox_height,
-                    position.z + (hitbox_width / 2.0),
-                ),
-                collide_on_top: false,
-            },
-            is_on_ground: false,
+            next_sequence: 1,
+            clock: StepClock::default(),
+            prediction: Prediction::default(),
+            correction: Vector3::zeros(),
+            alive: None,
             camera,
-            pitch: 0.0,
-            yaw: 0.0,
         }
     }
 
+    /// Direction the player is aiming in, if its body is in `world`.
+    pub fn aim_direction(&self, world: &World) -> Option<Vector3<f32>> {
+        world
+            .player(&self.id)
+            .map(|body| PlayerBody::aim_direction(body.pitch, body.yaw))
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.alive == Some(false)
+    }
+
+    /// Rewinds to the server's state of this player and replays the inputs it
+    /// has not seen yet. On death and on (re)spawn the server's state is
+    /// taken as it is instead, since nothing predicted before still applies.
+    pub fn reconcile(&mut self, update: &LocalPlayerUpdate, world: &mut World) {
+        let alive = update.player_state.health > 0;
+        let was_alive = self.alive.replace(alive);
+        let (body, collision_manager) = self.body(world);
+        if alive && was_alive == Some(true) {
+            let predicted = body.position;
+            self.prediction.reconcile(
+                body,
+                &update.player_state,
+                update.last_input_sequence,
+                collision_manager,
+            );
+            self.correction += predicted - body.position;
+            if self.correction.norm() > Self::MAX_SMOOTHED_CORRECTION {
+                self.correction = Vector3::zeros();
+            }
+        } else if was_alive != Some(alive) {
+            self.prediction
+                .reset(body, &update.player_state, update.last_input_sequence);
+            self.correction = Vector3::zeros();
+        }
+    }
+
+    /// Turns the controller state into an input, predicts it locally and
+    /// returns it so it can be sent to the server.
     pub fn update(
         &mut self,
         dt: Duration,
-        collision_manager: &mut CollisionManager,
+        world: &mut World,
         player_controller: &mut PlayerController,
-    ) {
+    ) -> PlayerInput {
         let sens = self.sensitivity * dt.as_secs_f32();
-        self.velocity.x *= Self::SLOW_DOWN;
-        self.velocity.z *= Self::SLOW_DOWN;
+        let step_dt = self.clock.advance(dt.as_secs_f32());
+        let (body, collision_manager) = self.body(world);
+        let mut pitch = body.pitch;
+        let mut yaw = body.yaw;
         if let Some(delta_mouse_pos) = player_controller.delta_mouse_pos {
-            self.yaw -= delta_mouse_pos.0 * sens;
-            self.pitch -= delta_mouse_pos.1 * sens;
+            yaw -= delta_mouse_pos.0 * sens;
+            pitch -= delta_mouse_pos.1 * sens;
             player_controller.delta_mouse_pos = None;
-            let max_pitch = std::f32::consts::FRAC_PI_2 - 0.01;
-            self.pitch = self.pitch.clamp(-max_pitch, max_pitch);
-            self.camera.rotate_camera(self.pitch, self.yaw);
         }
 
-        let camera_position = self.camera.position;
-        let camera_target = self.camera.target;
-        let camera_up = self.camera.up;
-
-        let looking_at = (camera_target - camera_position).normalize();
-        let left = camera_up.cross(&looking_at).normalize();
-        let forward = left.cross(&camera_up).normalize();
-        let mut delta_velocity = Vector3::zeros();
-        if player_controller.is_w_pressed {
-            delta_velocity += forward;
+        if self.is_dead() {
+            // The body stays where it died and the mouse orbits the camera
+            // around it until the server respawns us.
+            body.yaw = yaw;
+            let input = PlayerInput {
+                sequence: self.next_sequence,
+                pitch: body.pitch,
+                yaw,
+                dt: step_dt,
+                ..PlayerInput::default()
+            };
+            self.next_sequence += 1;
+            let eye = body.position
+                - PlayerBody::aim_direction(Self::DEATH_CAMERA_PITCH, yaw)
+                    * Self::DEATH_CAMERA_DISTANCE;
+            self.camera.move_camera(eye - self.camera.position);
+            self.camera.rotate_camera(Self::DEATH_CAMERA_PITCH, yaw);
+            return input;
         }
-        if player_controller.is_s_pressed {
-            delta_velocity -= forward;
-        }
-        if player_controller.is_a_pressed {
-            delta_velocity += left;
-        }
-        if player_controller.is_d_pressed {
-            delta_velocity -= left;
-        }
-        if player_controller.is_space_pressed && self.is_on_ground {
-            self.velocity.y += self.jump_strength;
-        }
-        self.velocity.y -= Self::GRAVITY;
-        let mut movement_velocity = Vector3::zeros();
-        if let Some(normalized_delta_velocity) = delta_velocity.try_normalize(0.0) {
-            movement_velocity = normalized_delta_velocity * self.speed;
-        }
-        self.velocity.x += movement_velocity.x;
-        self.velocity.z += movement_velocity.z;
-        let intended_displacement = self.velocity * dt.as_secs_f32();
-        let actual_displacement =
-            collision_manager.move_player(&mut self.hitbox, intended_displacement);
-        if actual_displacement.y == 0.0 && intended_displacement.y < 0.0 {
-            self.velocity.y = 0.0;
-            self.is_on_ground = true;
-        } else {
-            self.is_on_ground = false;
-        }
-        self.velocity = actual_displacement / dt.as_secs_f32();
-        self.camera.move_camera(actual_displacement);
-        self.position += actual_displacement;
+
+        let input = PlayerInput {
+            sequence: self.next_sequence,
+            forward: player_controller.is_w_pressed,
+            backward: player_controller.is_s_pressed,
+            left: player_controller.is_a_pressed,
+            right: player_controller.is_d_pressed,
+            jump: player_controller.is_space_pressed,
+            pitch,
+            yaw,
+            dt: step_dt,
+        };
+        self.next_sequence += 1;
+        self.prediction.predict(body, input, collision_manager);
+
+        self.correction *= (-Self::CORRECTION_RATE * dt.as_secs_f32()).exp();
+        let eye = body.interpolated_position(self.clock.alpha()) + self.correction;
+        let (pitch, yaw) = (body.pitch, body.yaw);
+        self.camera.move_camera(eye - self.camera.position);
+        self.camera.rotate_camera(pitch, yaw);
+        input
+    }
+
+    /// This player's body along with the map it collides with, respawned at
+    /// the camera should something have removed it from the world.
+    fn body<'a>(&self, world: &'a mut World) -> (&'a mut PlayerBody, &'a CollisionManager) {
+        let World {
+            players,
+            collision_manager,
+            ..
+        } = world;
+        let body = players
+            .entry(self.id)
+            .or_insert_with(|| PlayerBody::new(self.camera.position));
+        (body, collision_manager)
     }
 }
@@ -1,4 +1,8 @@
-use winit::{event::ElementState, event_loop::ActiveEventLoop, keyboard::KeyCode};
+use protocol::command::Weapon;
+use winit::{
+    event::{ElementState, MouseButton},
+    keyboard::KeyCode,
+};
 
 #[derive(Default)]
 pub struct PlayerController {
@@ -8,16 +12,19 @@ pub struct PlayerController {
     pub is_d_pressed: bool,
     pub is_space_pressed: bool,
     pub debug_enabled: bool,
+    pub show_scoreboard: bool,
     pub delta_mouse_pos: Option<(f32, f32)>,
+    /// Weapon clicked to fire, kept until the shot has been sent.
+    pub fire_requested: Option<Weapon>,
+    /// Set while a chat line is typed, when keys belong to the chat box.
+    typing: bool,
 }
 
 impl PlayerController {
-    pub fn handle_key_held(
-        &mut self,
-        key: KeyCode,
-        state: ElementState,
-        event_loop: &ActiveEventLoop,
-    ) -> bool {
+    pub fn handle_key_held(&mut self, key: KeyCode, state: ElementState) -> bool {
+        if self.typing {
+            return false;
+        }
         match key {
             KeyCode::KeyW => {
                 self.is_w_pressed = state.is_pressed();
@@ -43,10 +50,40 @@ impl PlayerController {
                 self.is_space_pressed = state.is_pressed();
                 true
             }
+            KeyCode::Tab => {
+                self.show_scoreboard = state.is_pressed();
+                true
+            }
             _ => false,
         }
     }
 
+    /// Stops or resumes taking movement keys. Keys held when typing starts
+    /// are let go, since their release goes to the chat box.
+    pub fn set_typing(&mut self, typing: bool) {
+        self.typing = typing;
+        if typing {
+            self.is_w_pressed = false;
+            self.is_s_pressed = false;
+            self.is_a_pressed = false;
+            self.is_d_pressed = false;
+            self.is_space_pressed = false;
+            self.show_scoreboard = false;
+        }
+    }
+
+    /// Left click fires the rifle, right click the rocket launcher.
+    pub fn handle_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        if !state.is_pressed() {
+            return;
+        }
+        match button {
+            MouseButton::Left => self.fire_requested = Some(Weapon::Rifle),
+            MouseButton::Right => self.fire_requested = Some(Weapon::RocketLauncher),
+            _ => {}
+        }
+    }
+
     pub fn handle_mouse(&mut self, delta: (f64, f64)) {
         let dx = delta.0 as f32;
         let dy = delta.1 as f32;
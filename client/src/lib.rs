@@ -1,23 +1,25 @@
 mod application;
 mod camera;
+pub mod config;
 mod game;
 mod model;
 mod network;
 mod renderer;
 
 use application::AppState;
+use config::ClientConfig;
 use winit::error::EventLoopError;
 use winit::event_loop::{ControlFlow, EventLoop};
 
 pub struct Game;
 
 impl Game {
-    pub fn run() -> Result<(), EventLoopError> {
+    pub fn run(config: ClientConfig) -> Result<(), EventLoopError> {
         env_logger::init();
-        let event_loop = EventLoop::new().unwrap();
+        let event_loop = EventLoop::new()?;
         event_loop.set_control_flow(ControlFlow::Poll);
         event_loop.set_control_flow(ControlFlow::Wait);
-        let mut app = AppState::default();
+        let mut app = AppState::new(config);
 
         event_loop.run_app(&mut app)
     }
@@ -1,6 +1,21 @@
-use client::Game;
-use winit::error::EventLoopError;
+use std::{env, process::ExitCode};
 
-fn main() -> Result<(), EventLoopError> {
-    Game::run()
+use client::{Game, config::ClientConfig};
+use protocol::config::LayeredConfig;
+
+fn main() -> ExitCode {
+    let config = match ClientConfig::from_args(env::args_os()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match Game::run(config) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -6,9 +6,13 @@ use std::{collections::HashMap, error::Error, fs};
 use wgpu::util::DeviceExt;
 use wgpu::{BindGroupLayout, Device, Queue};
 
-use crate::{
-    camera::light::Light,
-    game::{bounding_box::BoundingBox, collision_manager::CollisionManager},
+use crate::camera::light::Light;
+use game::{
+    bounding_box::BoundingBox,
+    collision_manager::CollisionManager,
+    map::{BoundingBoxLoader, SpawnPoint},
+    navigation::{NavGraph, Reach},
+    pickup::PickupSpawn,
 };
 
 use super::model_instance::{Instance, RawInstance};
@@ -26,6 +30,10 @@ pub struct Map {
     pub debug_lines: Vec<LineVertex>,
     pub player_head_mesh: Mesh,
     pub player_body_mesh: Mesh,
+    pub projectile_mesh: Mesh,
+    pub pickup_mesh: Mesh,
+    pub spawn_points: Vec<SpawnPoint>,
+    pub pickups: Vec<PickupSpawn>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -37,6 +45,16 @@ pub struct MapLoader {
     bounding_boxes: Vec<BoundingBoxLoader>,
     player_head_mesh: MeshLoader,
     player_body_mesh: MeshLoader,
+    /// Drawn for every projectile; maps without one use a shrunken player head.
+    #[serde(default)]
+    projectile_mesh: Option<MeshLoader>,
+    /// Drawn for every pickup; maps without one use the player head.
+    #[serde(default)]
+    pickup_mesh: Option<MeshLoader>,
+    #[serde(default)]
+    spawn_points: Vec<SpawnPoint>,
+    #[serde(default)]
+    pickups: Vec<PickupSpawn>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -84,15 +102,10 @@ struct InstanceLoader {
     pub rotation: [[f32; 3]; 3],
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct BoundingBoxLoader {
-    pub top_left: [f32; 3],
-    pub bottom_right: [f32; 3],
-    pub collide_on_top: bool,
-}
-
 impl MapLoader {
     const LINE_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+    const NAV_LINE_COLOR: [f32; 3] = [0.0, 1.0, 0.0];
+    const NAV_JUMP_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
     const MATERIAL_INDEX: u32 = 0;
     pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
         let json_data = fs::read_to_string(filename)?;
@@ -115,31 +128,27 @@ impl MapLoader {
                 }
             })
             .collect();
-        let map_boxes: Vec<BoundingBox> = self
-            .bounding_boxes
-            .iter()
-            .map(|bounding_box| -> BoundingBox {
-                BoundingBox {
-                    top_left: Point3::new(
-                        bounding_box.top_left[0],
-                        bounding_box.top_left[1],
-                        bounding_box.top_left[2],
-                    ),
-                    bottom_right: Point3::new(
-                        bounding_box.bottom_right[0],
-                        bounding_box.bottom_right[1],
-                        bounding_box.bottom_right[2],
-                    ),
-                    collide_on_top: bounding_box.collide_on_top,
-                }
-            })
-            .collect();
+        let map_boxes: Vec<BoundingBox> =
+            self.bounding_boxes.iter().map(BoundingBox::from).collect();
 
-        let debug_lines: Vec<LineVertex> = map_boxes
+        let mut debug_lines: Vec<LineVertex> = map_boxes
             .iter()
             .flat_map(|map_box| Self::bounding_box_to_line_vertices(map_box, Self::LINE_COLOR))
             .collect();
-        let collision_manager = CollisionManager { map_boxes };
+        let collision_manager = CollisionManager::new(map_boxes);
+        // Where bots can go, drawn over the floor.
+        let nav_graph = NavGraph::build(&collision_manager, Reach::default());
+        debug_lines.extend(nav_graph.debug_lines().flat_map(|(from, to, jump)| {
+            let color = if jump {
+                Self::NAV_JUMP_COLOR
+            } else {
+                Self::NAV_LINE_COLOR
+            };
+            [from, to].map(|point| LineVertex {
+                position: point.into(),
+                color,
+            })
+        }));
 
         let materials: Arc<HashMap<String, Material>> = Arc::new(
             self.materials
@@ -232,43 +241,16 @@ impl MapLoader {
             })
             .collect();
 
-        let mut player_head_vertices: Vec<Vertex> = self
-            .player_head_mesh
-            .vertices
-            .iter()
-            .map(|vertex| Vertex {
-                position: vertex.position,
-                tex_coords: vertex.tex_coords,
-                normal: vertex.normal,
-                tangent: [0.0; 3],
-                bitangent: [0.0; 3],
-            })
-            .collect();
-
-        let mut player_body_vertices: Vec<Vertex> = self
-            .player_body_mesh
-            .vertices
-            .iter()
-            .map(|vertex| Vertex {
-                position: vertex.position,
-                tex_coords: vertex.tex_coords,
-                normal: vertex.normal,
-                tangent: [0.0; 3],
-                bitangent: [0.0; 3],
-            })
-            .collect();
-        let player_head_mesh = Self::gen_mesh(
-            &self.player_head_mesh.name,
-            &mut player_head_vertices,
-            &self.player_head_mesh.indices,
-            None,
+        let player_head_mesh = Self::gen_untextured_mesh(&self.player_head_mesh, device);
+        let player_body_mesh = Self::gen_untextured_mesh(&self.player_body_mesh, device);
+        let projectile_mesh = Self::gen_untextured_mesh(
+            self.projectile_mesh
+                .as_ref()
+                .unwrap_or(&self.player_head_mesh),
             device,
         );
-        let player_body_mesh = Self::gen_mesh(
-            &self.player_body_mesh.name,
-            &mut player_body_vertices,
-            &self.player_body_mesh.indices,
-            None,
+        let pickup_mesh = Self::gen_untextured_mesh(
+            self.pickup_mesh.as_ref().unwrap_or(&self.player_head_mesh),
             device,
         );
         Map {
@@ -279,8 +261,27 @@ impl MapLoader {
             models,
             player_head_mesh,
             player_body_mesh,
+            projectile_mesh,
+            pickup_mesh,
+            spawn_points: self.spawn_points.clone(),
+            pickups: self.pickups.clone(),
         }
     }
+
+    fn gen_untextured_mesh(mesh: &MeshLoader, device: &Device) -> Mesh {
+        let mut vertices: Vec<Vertex> = mesh
+            .vertices
+            .iter()
+            .map(|vertex| Vertex {
+                position: vertex.position,
+                tex_coords: vertex.tex_coords,
+                normal: vertex.normal,
+                tangent: [0.0; 3],
+                bitangent: [0.0; 3],
+            })
+            .collect();
+        Self::gen_mesh(&mesh.name, &mut vertices, &mesh.indices, None, device)
+    }
     fn bounding_box_to_line_vertices(bbox: &BoundingBox, color: [f32; 3]) -> Vec<LineVertex> {
         let top_left = bbox.top_left;
         let bottom_right = bbox.bottom_right;
@@ -8,7 +8,9 @@ pub mod cube_texture;
 pub mod depth_texture;
 pub mod map_loader;
 pub mod model_instance;
+pub mod pickup_model;
 pub mod player_model;
+pub mod projectile_model;
 pub mod texture;
 pub mod vertex;
 
@@ -0,0 +1,89 @@
+use game::pickup::PickupSpawn;
+use nalgebra::{Matrix3, Matrix4, Rotation3, Vector3};
+use wgpu::{Buffer, Device, Queue, RenderPass};
+
+use super::{Mesh, model_instance::RawInstance};
+use wgpu::util::DeviceExt;
+
+pub struct PickupModel {
+    pub mesh: Mesh,
+    pub spawns: Vec<PickupSpawn>,
+    pub instances: Vec<RawInstance>,
+    pub instance_buffer: Buffer,
+    pub num_instances: u32,
+    /// Current turn of every pickup around the vertical axis, in radians.
+    angle: f32,
+}
+
+impl PickupModel {
+    const NO_INSTANCES: u32 = 0;
+    const SCALE: f32 = 0.4;
+    /// Radians per second pickups spin at.
+    const SPIN_SPEED: f32 = 2.0;
+    /// How far pickups bob above and below their position.
+    const BOB_HEIGHT: f32 = 0.05;
+
+    pub fn new(device: &Device, mesh: Mesh, spawns: Vec<PickupSpawn>) -> Self {
+        let instances = vec![
+            RawInstance {
+                model_mat: Matrix4::identity().into(),
+                normal_mat: Matrix3::identity().into(),
+            };
+            spawns.len().max(1)
+        ];
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Pickup Instance Buffer"),
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            mesh,
+            spawns,
+            instances,
+            instance_buffer,
+            num_instances: Self::NO_INSTANCES,
+            angle: 0.0,
+        }
+    }
+
+    /// Spins the pickups on by `dt` seconds and keeps the ones for which
+    /// `is_available` holds.
+    pub fn update(&mut self, queue: &Queue, dt: f32, is_available: impl Fn(usize) -> bool) {
+        self.angle = (self.angle + Self::SPIN_SPEED * dt) % std::f32::consts::TAU;
+        let rotation = Rotation3::from_axis_angle(&Vector3::y_axis(), self.angle);
+        let bob = Vector3::y() * self.angle.sin() * Self::BOB_HEIGHT;
+        self.instances = self
+            .spawns
+            .iter()
+            .enumerate()
+            .filter(|(pickup, _)| is_available(*pickup))
+            .map(|(_, spawn)| {
+                let translation = Vector3::from(spawn.position) + bob;
+                let model_mat = Matrix4::new_translation(&translation)
+                    * rotation.to_homogeneous()
+                    * Matrix4::new_scaling(Self::SCALE);
+                RawInstance {
+                    model_mat: model_mat.into(),
+                    normal_mat: (*rotation.matrix()).into(),
+                }
+            })
+            .collect();
+        self.num_instances = self.instances.len() as u32;
+        queue.write_buffer(
+            &self.instance_buffer,
+            0,
+            bytemuck::cast_slice(&self.instances),
+        );
+    }
+
+    pub fn draw(&self, render_pass: &mut RenderPass) {
+        if self.num_instances == Self::NO_INSTANCES {
+            return;
+        }
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_vertex_buffer(0, self.mesh.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..self.mesh.num_elements, 0, 0..self.num_instances);
+    }
+}
@@ -1,7 +1,8 @@
 use nalgebra::{Matrix3, Matrix4, Point3, Rotation3, Vector3};
 use wgpu::{Buffer, Device, Queue, RenderPass};
 
-use crate::{network::player_state::TimedPlayerState, renderer::Renderer};
+use crate::renderer::Renderer;
+use protocol::player_state::PlayerState;
 
 use super::{Mesh, model_instance::RawInstance};
 use wgpu::util::DeviceExt;
@@ -21,7 +22,7 @@ impl PlayerModel {
     const NO_INSTANCES: u32 = 0;
     pub fn new(
         device: &Device,
-        player_states: &[TimedPlayerState],
+        player_states: &[PlayerState],
         head_mesh: Mesh,
         body_mesh: Mesh,
     ) -> Self {
@@ -73,7 +74,7 @@ impl PlayerModel {
         }
     }
 
-    pub fn update(&mut self, queue: &Queue, player_states: &[TimedPlayerState]) {
+    pub fn update(&mut self, queue: &Queue, player_states: &[PlayerState]) {
         self.head_instances = player_states
             .iter()
             .map(Self::compute_head_instance)
@@ -118,9 +119,7 @@ impl PlayerModel {
         render_pass.draw_indexed(0..body_mesh.num_elements, 0, 0..self.body_num_instances);
     }
 
-    fn compute_head_instance(timed_player_state: &TimedPlayerState) -> RawInstance {
-        let dt = timed_player_state.time.elapsed().as_secs_f32();
-        let player_state = timed_player_state.player_state;
+    fn compute_head_instance(player_state: &PlayerState) -> RawInstance {
         let yaw_rotation = Matrix4::from(Rotation3::from_axis_angle(
             &Vector3::y_axis(),
             player_state.yaw,
@@ -129,8 +128,7 @@ impl PlayerModel {
             &Vector3::x_axis(),
             -player_state.pitch,
         ));
-        let new_pos =
-            Point3::from(player_state.position) + Vector3::from(player_state.velocity) * dt;
+        let new_pos = Point3::from(player_state.position);
         let rotation_mat = yaw_rotation * pitch_rotation;
 
         let model_mat = Matrix4::new_translation(&new_pos.coords) * rotation_mat;
@@ -141,11 +139,8 @@ impl PlayerModel {
         }
     }
 
-    fn compute_body_instance(timed_player_state: &TimedPlayerState) -> RawInstance {
-        let dt = timed_player_state.time.elapsed().as_secs_f32();
-        let player_state = timed_player_state.player_state;
-        let new_pos =
-            Point3::from(player_state.position) + Vector3::from(player_state.velocity) * dt;
+    fn compute_body_instance(player_state: &PlayerState) -> RawInstance {
+        let new_pos = Point3::from(player_state.position);
 
         let model_mat = Matrix4::new_translation(&new_pos.coords);
         RawInstance {
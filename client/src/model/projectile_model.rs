@@ -0,0 +1,75 @@
+use nalgebra::{Matrix3, Matrix4, Point3};
+use protocol::snapshot::Snapshot;
+use wgpu::{Buffer, Device, Queue, RenderPass};
+
+use super::{Mesh, model_instance::RawInstance};
+use wgpu::util::DeviceExt;
+
+pub struct ProjectileModel {
+    pub mesh: Mesh,
+    pub instances: Vec<RawInstance>,
+    pub instance_buffer: Buffer,
+    pub num_instances: u32,
+}
+
+impl ProjectileModel {
+    const NO_INSTANCES: u32 = 0;
+    /// Projectiles are drawn with the mesh shrunk by this much.
+    const SCALE: f32 = 0.3;
+    const MAX_PROJECTILES: usize = Snapshot::MAX_PROJECTILES;
+
+    pub fn new(device: &Device, mesh: Mesh) -> Self {
+        let instances = vec![
+            RawInstance {
+                model_mat: Matrix4::identity().into(),
+                normal_mat: Matrix3::identity().into(),
+            };
+            Self::MAX_PROJECTILES
+        ];
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Projectile Instance Buffer"),
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            mesh,
+            instances,
+            instance_buffer,
+            num_instances: Self::NO_INSTANCES,
+        }
+    }
+
+    pub fn update(&mut self, queue: &Queue, positions: &[Point3<f32>]) {
+        self.instances = positions
+            .iter()
+            .take(Self::MAX_PROJECTILES)
+            .map(Self::compute_instance)
+            .collect();
+        self.num_instances = self.instances.len() as u32;
+        queue.write_buffer(
+            &self.instance_buffer,
+            0,
+            bytemuck::cast_slice(&self.instances),
+        );
+    }
+
+    pub fn draw(&self, render_pass: &mut RenderPass) {
+        if self.num_instances == Self::NO_INSTANCES {
+            return;
+        }
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_vertex_buffer(0, self.mesh.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..self.mesh.num_elements, 0, 0..self.num_instances);
+    }
+
+    fn compute_instance(position: &Point3<f32>) -> RawInstance {
+        let model_mat =
+            Matrix4::new_translation(&position.coords) * Matrix4::new_scaling(Self::SCALE);
+        RawInstance {
+            model_mat: model_mat.into(),
+            normal_mat: Matrix3::identity().into(),
+        }
+    }
+}
@@ -1,109 +1,602 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    error::Error,
+    fmt::{self, Display},
     io,
-    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
-    time::{SystemTime, UNIX_EPOCH},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
 };
 
-use command::{Command, CommandType};
-use log::{error, info};
-use player_state::TimedPlayerState;
+use log::{error, info, log, warn};
+use nalgebra::{Point3, Vector3};
+use player_state::{SnapshotBuffer, TimedPlayerState};
+use protocol::chat::{self, ChatChannel};
+use protocol::command::{
+    Command, CommandType, MAX_DATAGRAM_SIZE, PROTOCOL_VERSION, RejectReason, Weapon, unix_millis,
+};
+use protocol::connection::{Connection, Packet};
+use protocol::input::PlayerInput;
+use protocol::match_state::{GameModeKind, MatchPhase, ScoreEntry};
+use protocol::player_state::PlayerState;
+use protocol::snapshot::{ProjectileState, Snapshot};
 use uuid::Uuid;
 
-pub mod command;
 pub mod player_state;
 
 pub struct Network {
     socket: UdpSocket,
-    pub player_states: HashMap<Uuid, TimedPlayerState>,
+    connection: Connection,
+    player_snapshots: HashMap<Uuid, SnapshotBuffer>,
+    /// Projectiles in the newest snapshot and the server time it was sent at.
+    projectiles: (Vec<ProjectileState>, u128),
+    /// Pickups the server said were taken and have not come back yet.
+    taken_pickups: HashSet<u16>,
+    scoreboard: BTreeMap<Uuid, ScoreEntry>,
+    /// Each player's round trip time to the server in millis, as last reported.
+    pings: HashMap<Uuid, u16>,
+    /// The match's mode, phase and the server time the phase ends at.
+    match_state: Option<(GameModeKind, MatchPhase, Option<u128>)>,
+    /// Recent kills, described for display, with when they arrived.
+    kill_feed: VecDeque<(Instant, String)>,
+    /// Recent chat lines, with the sender's name, and when they arrived.
+    chat_log: VecDeque<(Instant, String)>,
+    /// Decoded snapshots the server may send the next ones as deltas against.
+    received_snapshots: VecDeque<Snapshot>,
+    /// Newest snapshot tick not yet acknowledged, sent along with the next input.
+    snapshot_ack: Option<u32>,
+    join_reply: Option<Result<ServerInfo, JoinError>>,
+    local_player_update: Option<LocalPlayerUpdate>,
+    started: Instant,
+    /// Smoothed difference between the server clock and `started`, in millis.
+    server_clock_offset: Option<f64>,
+    interpolation_delay: Duration,
+}
+
+/// The server's view of our own player along with the newest input it used.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalPlayerUpdate {
+    pub player_state: PlayerState,
+    pub last_input_sequence: u32,
+}
+
+/// What the server told us when it accepted our join.
+#[derive(Debug, Clone)]
+pub struct ServerInfo {
+    pub player_id: Uuid,
+    pub tick_rate: Duration,
+    pub map: String,
+}
+
+#[derive(Debug)]
+pub enum JoinError {
+    Io(io::Error),
+    Rejected(RejectReason),
+    TimedOut,
+}
+
+impl Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not reach server: {e}"),
+            Self::Rejected(reason) => write!(f, "server rejected join: {reason}"),
+            Self::TimedOut => write!(f, "server did not answer the join request"),
+        }
+    }
+}
+
+impl Error for JoinError {}
+
+impl From<io::Error> for JoinError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
 }
 
 impl Network {
-    pub fn new(ip_addr: Ipv4Addr, port: u16) -> io::Result<Self> {
-        let addr = SocketAddr::new(IpAddr::V4(ip_addr), port);
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
+    const JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+    /// The leave is sent a few times over since nobody stays around to resend it.
+    const LEAVE_COPIES: usize = 3;
+    const DEFAULT_INTERPOLATION_DELAY: Duration = Duration::from_millis(100);
+    /// How far past the newest snapshot remote players keep moving before they stop.
+    const MAX_EXTRAPOLATION: Duration = Duration::from_millis(250);
+    /// Weight given to each new clock sample when smoothing the offset.
+    const CLOCK_SMOOTHING: f64 = 0.1;
+    /// Comfortably more than the server keeps, so its baselines are always here.
+    const SNAPSHOT_HISTORY: usize = 64;
+    /// Most kills shown at once, and how long each one stays.
+    const KILL_FEED_LENGTH: usize = 5;
+    const KILL_FEED_DURATION: Duration = Duration::from_secs(6);
+    /// Chat lines kept for scrolling back, and how long each one is shown
+    /// while nobody is typing.
+    const CHAT_LOG_LENGTH: usize = 8;
+    const CHAT_DURATION: Duration = Duration::from_secs(10);
+
+    /// Resolves `host` (a name or an IPv4/IPv6 address) and points a socket
+    /// of the matching family at the first address found.
+    pub fn new(host: &str, port: u16) -> io::Result<Self> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let addr = (host, port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "host has no addresses"))?;
+        let local_addr = match addr {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = UdpSocket::bind(local_addr)?;
         socket.connect(addr)?;
         socket.set_nonblocking(true)?;
 
         Ok(Self {
             socket,
-            player_states: HashMap::new(),
+            connection: Connection::default(),
+            player_snapshots: HashMap::new(),
+            projectiles: (vec![], 0),
+            taken_pickups: HashSet::new(),
+            scoreboard: BTreeMap::new(),
+            pings: HashMap::new(),
+            match_state: None,
+            kill_feed: VecDeque::with_capacity(Self::KILL_FEED_LENGTH),
+            chat_log: VecDeque::with_capacity(Self::CHAT_LOG_LENGTH),
+            received_snapshots: VecDeque::with_capacity(Self::SNAPSHOT_HISTORY),
+            snapshot_ack: None,
+            join_reply: None,
+            local_player_update: None,
+            started: Instant::now(),
+            server_clock_offset: None,
+            interpolation_delay: Self::DEFAULT_INTERPOLATION_DELAY,
         })
     }
 
+    /// How far behind the server remote players are drawn. Larger values hide
+    /// more packet loss and jitter at the cost of showing older positions.
+    pub fn set_interpolation_delay(&mut self, interpolation_delay: Duration) {
+        self.interpolation_delay = interpolation_delay;
+    }
+
+    /// Remote players as they should be drawn right now.
+    pub fn remote_player_states(&self) -> Vec<PlayerState> {
+        let Some(render_time) = self.render_time() else {
+            return vec![];
+        };
+        let max_extrapolation = Self::MAX_EXTRAPOLATION.as_secs_f64() * 1000.0;
+        self.player_snapshots
+            .values()
+            .filter_map(|snapshots| snapshots.sample(render_time, max_extrapolation))
+            .collect()
+    }
+
+    /// Where the projectiles from the newest snapshot are by now, moved along
+    /// their velocity for the time since it was sent.
+    pub fn projectile_positions(&self) -> Vec<Point3<f32>> {
+        let Some(offset) = self.server_clock_offset else {
+            return vec![];
+        };
+        let (projectiles, sent_at) = &self.projectiles;
+        let elapsed = (self.local_millis() + offset - *sent_at as f64)
+            .clamp(0.0, Self::MAX_EXTRAPOLATION.as_secs_f64() * 1000.0)
+            / 1000.0;
+        projectiles
+            .iter()
+            .map(|projectile| {
+                Point3::from(projectile.position())
+                    + Vector3::from(projectile.velocity()) * elapsed as f32
+            })
+            .collect()
+    }
+
+    /// Kills from the last few seconds, oldest first.
+    pub fn kill_feed(&self) -> Vec<String> {
+        self.kill_feed
+            .iter()
+            .filter(|(received, _)| received.elapsed() < Self::KILL_FEED_DURATION)
+            .map(|(_, kill)| kill.clone())
+            .collect()
+    }
+
+    /// Chat from the last few seconds, oldest first.
+    pub fn recent_chat(&self) -> Vec<String> {
+        self.chat_log
+            .iter()
+            .filter(|(received, _)| received.elapsed() < Self::CHAT_DURATION)
+            .map(|(_, line)| line.clone())
+            .collect()
+    }
+
+    /// Every chat line still kept, oldest first.
+    pub fn chat_history(&self) -> Vec<String> {
+        self.chat_log.iter().map(|(_, line)| line.clone()).collect()
+    }
+
+    /// Every player's scoreboard line and ping, best first within each team.
+    pub fn scoreboard(&self) -> Vec<(ScoreEntry, Option<u16>)> {
+        let mut lines: Vec<(ScoreEntry, Option<u16>)> = self
+            .scoreboard
+            .values()
+            .map(|entry| (entry.clone(), self.pings.get(&entry.player_id).copied()))
+            .collect();
+        lines.sort_by(|(a, _), (b, _)| {
+            let team = |entry: &ScoreEntry| entry.team.map(|team| team as u8);
+            team(a)
+                .cmp(&team(b))
+                .then(b.score.cmp(&a.score))
+                .then(b.frags.cmp(&a.frags))
+                .then(a.deaths.cmp(&b.deaths))
+        });
+        lines
+    }
+
+    pub fn match_phase(&self) -> Option<MatchPhase> {
+        self.match_state.map(|(_, phase, _)| phase)
+    }
+
+    /// How long until the current match phase is over, if it is timed.
+    pub fn phase_time_left(&self) -> Option<Duration> {
+        let ends_at = self.match_state?.2?;
+        let now = self.local_millis() + self.server_clock_offset?;
+        Some(Duration::from_secs_f64(
+            ((ends_at as f64 - now) / 1000.0).max(0.0),
+        ))
+    }
+
+    fn player_name(&self, player_id: &Uuid) -> String {
+        self.scoreboard.get(player_id).map_or_else(
+            || player_id.to_string()[..8].to_owned(),
+            |entry| entry.name.clone(),
+        )
+    }
+
+    pub fn is_pickup_available(&self, pickup: usize) -> bool {
+        !self.taken_pickups.contains(&(pickup as u16))
+    }
+
+    /// Server time that remote players are currently drawn at.
+    fn render_time(&self) -> Option<f64> {
+        let offset = self.server_clock_offset?;
+        Some(self.local_millis() + offset - self.interpolation_delay.as_secs_f64() * 1000.0)
+    }
+
+    fn local_millis(&self) -> f64 {
+        self.started.elapsed().as_secs_f64() * 1000.0
+    }
+
+    fn observe_server_time(&mut self, server_time: u128) {
+        let sample = server_time as f64 - self.local_millis();
+        let offset = self.server_clock_offset.get_or_insert(sample);
+        *offset += (sample - *offset) * Self::CLOCK_SMOOTHING;
+    }
+
+    /// Reads datagrams until the socket has none left.
     pub fn poll(&mut self) {
-        let mut buffer = [0; 1024];
-        match self.socket.recv_from(&mut buffer) {
-            Ok((number_of_bytes, src_addr)) => {
-                if let Ok(command) = Command::deserialize(&buffer[..number_of_bytes]) {
-                    self.player_states.clear();
-                    info!("recieved {:?} from {}", command.command_type, src_addr);
-                    self.handle_command(command.command_type);
+        loop {
+            match self.receive() {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    error!("{e}");
+                    return;
                 }
             }
-            Err(e) if e.kind() != io::ErrorKind::WouldBlock => {
-                error!("{e}");
-            }
-            _ => {}
         }
     }
 
-    pub fn send_player_join(&self) -> io::Result<()> {
-        let connect_command = Command {
-            command_type: command::CommandType::PlayerJoin,
-            time: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_millis(),
-        };
-        self.socket.send(&connect_command.serialize().unwrap())?;
+    /// Reads one datagram and handles every message it makes deliverable.
+    fn receive(&mut self) -> io::Result<()> {
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        let (number_of_bytes, src_addr) = self.socket.recv_from(&mut buffer)?;
+        if let Ok(packet) = Packet::deserialize(&buffer[..number_of_bytes]) {
+            for command in self.connection.receive(packet, Instant::now()) {
+                log!(
+                    command.command_type.log_level(),
+                    "received {:?} from {}",
+                    command.command_type,
+                    src_addr
+                );
+                self.handle_command(command);
+            }
+        }
         Ok(())
     }
 
-    pub fn send_player_leave(&self) -> io::Result<()> {
-        let disconnect_command = Command {
-            command_type: command::CommandType::PlayerLeave,
-            time: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_millis(),
-        };
-        self.socket.send(&disconnect_command.serialize().unwrap())?;
+    /// Performs the join handshake, blocking until the server accepts or rejects us.
+    /// The request goes over the reliable channel, which resends it while unanswered.
+    pub fn send_player_join(&mut self, player_name: &str) -> Result<ServerInfo, JoinError> {
+        self.send_reliable(CommandType::PlayerJoin {
+            protocol_version: PROTOCOL_VERSION,
+            player_name: String::from(player_name),
+        });
+
+        self.socket.set_nonblocking(false)?;
+        self.socket
+            .set_read_timeout(Some(Connection::RESEND_INTERVAL))?;
+        let result = self.await_join_reply();
+        self.socket.set_nonblocking(true)?;
+        result
+    }
+
+    fn await_join_reply(&mut self) -> Result<ServerInfo, JoinError> {
+        let deadline = Instant::now() + Self::JOIN_TIMEOUT;
+        while Instant::now() < deadline {
+            self.send_packet(vec![])?;
+            match self.receive() {
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => return Err(e.into()),
+                Ok(()) => {}
+            }
+            if let Some(reply) = self.join_reply.take() {
+                return reply;
+            }
+        }
+        Err(JoinError::TimedOut)
+    }
+
+    /// Sends a packet with `unreliable` and whatever reliable messages are due.
+    fn send_packet(&mut self, unreliable: Vec<Command>) -> io::Result<()> {
+        let mut packet = self.connection.next_packet(Instant::now());
+        packet.unreliable = unreliable;
+        let serialized = packet
+            .serialize()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        self.socket.send(&serialized)?;
         Ok(())
     }
 
-    pub fn send_player_move(
-        &self,
-        position: [f32; 3],
-        velocity: [f32; 3],
-        pitch: f32,
-        yaw: f32,
-    ) -> io::Result<()> {
-        let movement_command = Command {
-            command_type: command::CommandType::PlayerMove {
-                position,
-                velocity,
-                pitch,
-                yaw,
-            },
-            time: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_millis(),
-        };
-        self.socket.send(&movement_command.serialize().unwrap())?;
+    pub fn send_player_leave(&mut self) -> io::Result<()> {
+        self.send_reliable(CommandType::PlayerLeave);
+        let serialized = self
+            .connection
+            .next_packet(Instant::now())
+            .serialize()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        for _ in 0..Self::LEAVE_COPIES {
+            self.socket.send(&serialized)?;
+        }
         Ok(())
     }
 
-    fn handle_command(&mut self, command: CommandType) {
-        if let CommandType::Data((uuid, player_states)) = command {
-            player_states.as_ref().iter().for_each(|player_state| {
-                let player_id = player_state.player_id;
-                if player_id != uuid {
-                    self.player_states
-                        .insert(player_state.player_id, TimedPlayerState::new(*player_state));
+    pub fn send_player_input(&mut self, input: PlayerInput) -> io::Result<()> {
+        let mut commands = vec![Command::new(CommandType::PlayerInput(input))];
+        if let Some(tick) = self.snapshot_ack.take() {
+            commands.push(Command::new(CommandType::SnapshotAck { tick }));
+        }
+        self.send_packet(commands)
+    }
+
+    /// Queues a shot along `direction`. It is stamped with the server time
+    /// other players are drawn at, so the server can check it against what we
+    /// saw, and goes out with the next packet.
+    pub fn send_fire(&mut self, weapon: Weapon, direction: Vector3<f32>) {
+        let view_time = self
+            .render_time()
+            .map_or_else(unix_millis, |render_time| render_time as u128);
+        self.send_reliable(CommandType::Fire {
+            weapon,
+            direction: direction.into(),
+            view_time,
+        });
+    }
+
+    /// Queues a line of chat for `channel`; it goes out reliably with the
+    /// next packet. Lines that are empty once sanitized are not sent.
+    pub fn send_chat(&mut self, channel: ChatChannel, text: &str) {
+        let text = chat::sanitize(text);
+        if text.is_empty() {
+            return;
+        }
+        self.send_reliable(CommandType::ChatSend { channel, text });
+    }
+
+    /// Queues `command_type` on the reliable channel, or logs why it cannot
+    /// be sent.
+    fn send_reliable(&mut self, command_type: CommandType) {
+        if let Err(e) = self.connection.send_reliable(Command::new(command_type)) {
+            error!("dropped a message to the server: {e}");
+        }
+    }
+
+    /// Hands out the newest server state of our own player, once.
+    pub fn take_local_player_update(&mut self) -> Option<LocalPlayerUpdate> {
+        self.local_player_update.take()
+    }
+
+    fn handle_command(&mut self, command: Command) {
+        match command.command_type {
+            CommandType::JoinAccepted {
+                player_id,
+                session_token,
+                tick_rate_millis,
+                map,
+            } => {
+                self.connection.set_session(session_token);
+                self.join_reply = Some(Ok(ServerInfo {
+                    player_id,
+                    tick_rate: Duration::from_millis(tick_rate_millis),
+                    map,
+                }));
+            }
+            CommandType::JoinRejected(reason) => {
+                self.join_reply = Some(Err(JoinError::Rejected(reason)));
+            }
+            CommandType::Data { .. } => self.handle_data(command),
+            CommandType::Hit {
+                shooter,
+                target,
+                damage,
+            } => {
+                info!("{shooter} hit {target} for {damage}");
+            }
+            CommandType::Kill { killer, victim } => {
+                info!("{killer} killed {victim}");
+                let kill = if killer == victim {
+                    format!("{} died", self.player_name(&victim))
+                } else {
+                    format!(
+                        "{} > {}",
+                        self.player_name(&killer),
+                        self.player_name(&victim)
+                    )
+                };
+                if self.kill_feed.len() == Self::KILL_FEED_LENGTH {
+                    self.kill_feed.pop_front();
                 }
+                self.kill_feed.push_back((Instant::now(), kill));
+            }
+            CommandType::MatchState {
+                mode,
+                phase,
+                phase_ends_at,
+            } => {
+                info!("{mode} match is now {phase:?}");
+                self.match_state = Some((mode, phase, phase_ends_at));
+            }
+            CommandType::PlayerScore(entry) => {
+                self.scoreboard.insert(entry.player_id, entry);
+            }
+            CommandType::ScoreRemoved { player_id } => {
+                self.scoreboard.remove(&player_id);
+            }
+            CommandType::PlayerPings(pings) => {
+                self.pings = pings.into_iter().collect();
+            }
+            CommandType::ChatMessage {
+                sender,
+                channel,
+                text,
+            } => {
+                let name = self.player_name(&sender);
+                let line = match channel {
+                    ChatChannel::All => format!("{name}: {text}"),
+                    ChatChannel::Team => format!("({name}): {text}"),
+                };
+                if self.chat_log.len() == Self::CHAT_LOG_LENGTH {
+                    self.chat_log.pop_front();
+                }
+                self.chat_log.push_back((Instant::now(), line));
+            }
+            CommandType::PickupAvailability { pickup, available } => {
+                if available {
+                    self.taken_pickups.remove(&pickup);
+                } else {
+                    self.taken_pickups.insert(pickup);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_data(&mut self, command: Command) {
+        if let CommandType::Data {
+            player_id: _,
+            last_input_sequence,
+            local_player,
+            snapshot,
+        } = command.command_type
+        {
+            self.observe_server_time(command.time);
+
+            let snapshot = match Snapshot::decode(&snapshot, |tick| {
+                self.received_snapshots
+                    .iter()
+                    .find(|snapshot| snapshot.tick == tick)
+            }) {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    warn!("dropping snapshot: {e}");
+                    return;
+                }
+            };
+            if self.snapshot_ack.is_none_or(|tick| snapshot.tick > tick) {
+                self.snapshot_ack = Some(snapshot.tick);
+            }
+            let is_newest = self
+                .received_snapshots
+                .back()
+                .is_none_or(|newest| snapshot.tick > newest.tick);
+            // Kept in tick order, so a late datagram never passes for the newest.
+            let index = self
+                .received_snapshots
+                .partition_point(|received| received.tick < snapshot.tick);
+            if self
+                .received_snapshots
+                .get(index)
+                .is_none_or(|received| received.tick != snapshot.tick)
+            {
+                self.received_snapshots.insert(index, snapshot);
+                if self.received_snapshots.len() > Self::SNAPSHOT_HISTORY {
+                    self.received_snapshots.pop_front();
+                }
+            }
+            if !is_newest {
+                return;
+            }
+            // Only the newest data may move the local player, never a
+            // datagram that arrived out of order.
+            self.local_player_update = Some(LocalPlayerUpdate {
+                player_state: local_player,
+                last_input_sequence,
+            });
+
+            let Some(snapshot) = self.received_snapshots.back() else {
+                return;
+            };
+            self.projectiles = (snapshot.projectiles.clone(), command.time);
+            let player_states: Vec<PlayerState> = snapshot
+                .entities
+                .values()
+                .map(|entity| entity.dequantize())
+                .collect();
+            self.player_snapshots.retain(|player_id, _| {
+                player_states
+                    .iter()
+                    .any(|player_state| player_state.player_id == *player_id)
             });
+            for player_state in player_states {
+                self.player_snapshots
+                    .entry(player_state.player_id)
+                    .or_default()
+                    .push(TimedPlayerState::new(player_state, command.time));
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(tick: u32, last_input_sequence: u32) -> Command {
+        let snapshot = Snapshot {
+            tick,
+            entities: Default::default(),
+            projectiles: vec![],
+        };
+        Command::new(CommandType::Data {
+            player_id: Uuid::nil(),
+            last_input_sequence,
+            local_player: PlayerState::default(),
+            snapshot: snapshot.encode(None, usize::MAX).0,
+        })
+    }
+
+    #[test]
+    fn late_data_does_not_roll_the_local_player_back() {
+        let mut network = Network::new("127.0.0.1", 8003).unwrap();
+        network.handle_data(data(5, 7));
+        network.handle_data(data(3, 4));
+        let update = network.take_local_player_update().unwrap();
+        assert_eq!(update.last_input_sequence, 7);
+
+        network.handle_data(data(4, 5));
+        assert!(network.take_local_player_update().is_none());
+        let ticks: Vec<u32> = network
+            .received_snapshots
+            .iter()
+            .map(|snapshot| snapshot.tick)
+            .collect();
+        assert_eq!(ticks, [3, 4, 5]);
+    }
+}
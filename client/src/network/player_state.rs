@@ -1,29 +1,97 @@
-use std::time::Instant;
-
-use serde::{Deserialize, Serialize};
-use uuid::Uuid;
-
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct PlayerState {
-    pub player_id: Uuid,
-    pub position: [f32; 3],
-    pub velocity: [f32; 3],
-    pub pitch: f32,
-    pub yaw: f32,
-    pub health: u8,
-}
+use std::collections::VecDeque;
+use std::f32::consts::{PI, TAU};
+
+use nalgebra::{Point3, Vector3};
+use protocol::player_state::PlayerState;
 
+/// A player state stamped with the server time (unix millis) it was sent at.
 #[derive(Debug, Clone, Copy)]
 pub struct TimedPlayerState {
     pub player_state: PlayerState,
-    pub time: Instant,
+    pub server_time: u128,
 }
 
 impl TimedPlayerState {
-    pub fn new(player_state: PlayerState) -> Self {
+    pub fn new(player_state: PlayerState, server_time: u128) -> Self {
         Self {
             player_state,
-            time: Instant::now(),
+            server_time,
+        }
+    }
+}
+
+/// Recent snapshots of one remote player, oldest first. Remote players are
+/// drawn slightly in the past so there is usually a snapshot on either side
+/// of the render time to blend between.
+#[derive(Debug, Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<TimedPlayerState>,
+}
+
+impl SnapshotBuffer {
+    const CAPACITY: usize = 32;
+
+    /// Adds a snapshot, ignoring ones that arrive out of order.
+    pub fn push(&mut self, snapshot: TimedPlayerState) {
+        if self
+            .snapshots
+            .back()
+            .is_some_and(|newest| newest.server_time >= snapshot.server_time)
+        {
+            return;
+        }
+        if self.snapshots.len() == Self::CAPACITY {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// The state at `render_time` (server millis). Blends the two snapshots
+    /// around it, or extrapolates from the newest for at most
+    /// `max_extrapolation` millis when newer ones have not arrived yet.
+    pub fn sample(&self, render_time: f64, max_extrapolation: f64) -> Option<PlayerState> {
+        let oldest = self.snapshots.front()?;
+        let newest = self.snapshots.back()?;
+        if render_time <= oldest.server_time as f64 {
+            return Some(oldest.player_state);
+        }
+        if render_time >= newest.server_time as f64 {
+            let ahead = (render_time - newest.server_time as f64).min(max_extrapolation);
+            let mut state = newest.player_state;
+            let position = Point3::from(state.position)
+                + Vector3::from(state.velocity) * (ahead / 1000.0) as f32;
+            state.position = position.into();
+            return Some(state);
+        }
+
+        let after = self
+            .snapshots
+            .iter()
+            .position(|snapshot| snapshot.server_time as f64 > render_time)?;
+        let from = &self.snapshots[after - 1];
+        let to = &self.snapshots[after];
+        let span = (to.server_time - from.server_time) as f64;
+        let t = ((render_time - from.server_time as f64) / span) as f32;
+        Some(Self::blend(&from.player_state, &to.player_state, t))
+    }
+
+    /// Signed angle from `from` to `to` the short way round, since yaw wraps
+    /// at a full turn once it has been quantized.
+    fn shortest_turn(from: f32, to: f32) -> f32 {
+        (to - from + PI).rem_euclid(TAU) - PI
+    }
+
+    fn blend(from: &PlayerState, to: &PlayerState, t: f32) -> PlayerState {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let lerp3 = |a: [f32; 3], b: [f32; 3]| {
+            (Vector3::from(a) + (Vector3::from(b) - Vector3::from(a)) * t).into()
+        };
+        PlayerState {
+            position: lerp3(from.position, to.position),
+            velocity: lerp3(from.velocity, to.velocity),
+            pitch: lerp(from.pitch, to.pitch),
+            yaw: from.yaw + Self::shortest_turn(from.yaw, to.yaw) * t,
+            ..*to
         }
     }
 }
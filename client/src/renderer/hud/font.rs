@@ -0,0 +1,150 @@
+/// A built-in 5x7 pixel font covering digits, capital letters and the
+/// punctuation the HUD uses. Lowercase letters are drawn as capitals and
+/// anything else as a question mark.
+pub struct BitmapFont;
+
+/// Rows from top to bottom, the leftmost pixel in the highest of five bits.
+#[rustfmt::skip]
+const GLYPHS: [(char, [u8; 7]); 57] = [
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11110, 0b00001, 0b00001, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    ('=', [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('!', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+    ('\'', [0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+    ('[', [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110]),
+    (']', [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110]),
+    ('<', [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010]),
+    ('>', [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+    ('#', [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010]),
+    ('*', [0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000]),
+    ('_', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111]),
+];
+
+impl BitmapFont {
+    pub const GLYPH_WIDTH: u32 = 5;
+    pub const GLYPH_HEIGHT: u32 = 7;
+    /// Glyphs sit in cells one pixel wider and taller, which spaces out text
+    /// and keeps neighbouring glyphs from bleeding into each other.
+    pub const CELL_WIDTH: u32 = Self::GLYPH_WIDTH + 1;
+    pub const CELL_HEIGHT: u32 = Self::GLYPH_HEIGHT + 1;
+    const COLUMNS: u32 = 16;
+    /// The cell after the last glyph is filled in for drawing plain rectangles.
+    const SOLID_CELL: usize = GLYPHS.len();
+
+    fn atlas_size() -> (u32, u32) {
+        let rows = (Self::SOLID_CELL as u32 + 1).div_ceil(Self::COLUMNS);
+        (Self::COLUMNS * Self::CELL_WIDTH, rows * Self::CELL_HEIGHT)
+    }
+
+    /// Every glyph rasterized into one white RGBA image whose alpha holds
+    /// the shapes. Returns the width, height and pixels.
+    pub fn atlas() -> (u32, u32, Vec<u8>) {
+        let (width, height) = Self::atlas_size();
+        let mut pixels = vec![0; (width * height * 4) as usize];
+        let solid = [0b11111; 7];
+        let cells = GLYPHS
+            .iter()
+            .map(|(_, rows)| rows)
+            .chain(std::iter::once(&solid));
+        for (cell, rows) in cells.enumerate() {
+            let (cell_x, cell_y) = Self::cell_origin(cell);
+            for (y, row) in rows.iter().enumerate() {
+                for x in 0..Self::GLYPH_WIDTH {
+                    if row & (1 << (Self::GLYPH_WIDTH - 1 - x)) == 0 {
+                        continue;
+                    }
+                    let index = ((cell_y + y as u32) * width + cell_x + x) as usize * 4;
+                    pixels[index..index + 4].copy_from_slice(&[255; 4]);
+                }
+            }
+        }
+        (width, height, pixels)
+    }
+
+    /// Texture coordinates of `c`'s glyph as `[left, top, right, bottom]`.
+    pub fn glyph_uv(c: char) -> [f32; 4] {
+        let c = c.to_ascii_uppercase();
+        let cell = GLYPHS
+            .iter()
+            .position(|(glyph, _)| *glyph == c)
+            .or_else(|| GLYPHS.iter().position(|(glyph, _)| *glyph == '?'))
+            .unwrap_or(0);
+        Self::cell_uv(cell, 0.0)
+    }
+
+    /// Texture coordinates well inside the solid cell, for untextured quads.
+    pub fn solid_uv() -> [f32; 4] {
+        Self::cell_uv(Self::SOLID_CELL, 1.0)
+    }
+
+    /// Width in pixels of `text` drawn with each font pixel `scale` pixels wide.
+    pub fn text_width(text: &str, scale: f32) -> f32 {
+        let chars = text.chars().count() as f32;
+        (chars * Self::CELL_WIDTH as f32 - 1.0).max(0.0) * scale
+    }
+
+    fn cell_origin(cell: usize) -> (u32, u32) {
+        let cell = cell as u32;
+        (
+            cell % Self::COLUMNS * Self::CELL_WIDTH,
+            cell / Self::COLUMNS * Self::CELL_HEIGHT,
+        )
+    }
+
+    /// The glyph area of `cell`, shrunk by `inset` pixels on every side.
+    fn cell_uv(cell: usize, inset: f32) -> [f32; 4] {
+        let (width, height) = Self::atlas_size();
+        let (x, y) = Self::cell_origin(cell);
+        [
+            (x as f32 + inset) / width as f32,
+            (y as f32 + inset) / height as f32,
+            ((x + Self::GLYPH_WIDTH) as f32 - inset) / width as f32,
+            ((y + Self::GLYPH_HEIGHT) as f32 - inset) / height as f32,
+        ]
+    }
+}
@@ -0,0 +1,509 @@
+use std::time::Duration;
+
+use font::BitmapFont;
+use nalgebra::Matrix4;
+use protocol::match_state::MatchPhase;
+use protocol::player_state::Team;
+use quad_batcher::{QuadBatcher, QuadVertex};
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPass, RenderPipeline};
+
+use super::pipeline_factory::PipelineFactory;
+
+mod font;
+mod quad_batcher;
+
+/// Everything the overlay shows, gathered fresh every frame.
+#[derive(Debug, Clone, Default)]
+pub struct HudState {
+    pub alive: bool,
+    pub health: u8,
+    pub armor: u8,
+    /// Rockets left, if the player has a launcher to fire them with.
+    pub rockets: Option<u8>,
+    /// Newest kill last.
+    pub kill_feed: Vec<String>,
+    pub phase: Option<MatchPhase>,
+    pub time_left: Option<Duration>,
+    /// Chat lines to show, newest last.
+    pub chat: Vec<String>,
+    /// The line being typed, prompt included.
+    pub chat_draft: Option<String>,
+    /// Shown while the scoreboard key is held.
+    pub scoreboard: Option<Vec<ScoreboardRow>>,
+}
+
+/// One player's line on the scoreboard.
+#[derive(Debug, Clone)]
+pub struct ScoreboardRow {
+    pub name: String,
+    pub team: Option<Team>,
+    pub score: i32,
+    pub frags: u32,
+    pub deaths: u32,
+    pub ping: Option<u16>,
+    /// Whether this is the player at this screen.
+    pub is_local: bool,
+}
+
+/// The 2D overlay drawn on top of the world: crosshair, health, armor, ammo,
+/// kill feed, match timer, chat and scoreboard. Everything is laid out in window pixels and
+/// scaled up with the window height.
+pub struct Hud {
+    pipeline: RenderPipeline,
+    batcher: QuadBatcher,
+    projection_buffer: Buffer,
+    projection_bind_group: BindGroup,
+    font_bind_group: BindGroup,
+    width: f32,
+    height: f32,
+    /// Window pixels per font pixel.
+    scale: f32,
+}
+
+impl Hud {
+    /// Window height that text is drawn at one pixel per font pixel.
+    const BASE_HEIGHT: f32 = 360.0;
+    const MARGIN: f32 = 6.0;
+    const CROSSHAIR_LENGTH: f32 = 4.0;
+    const CROSSHAIR_GAP: f32 = 2.0;
+    /// Health at or below this is shown in red.
+    const LOW_HEALTH: u8 = 25;
+    const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+    const RED: [f32; 4] = [1.0, 0.2, 0.2, 0.9];
+    const BLUE: [f32; 4] = [0.4, 0.6, 1.0, 0.9];
+    const YELLOW: [f32; 4] = [1.0, 0.85, 0.3, 0.9];
+    const SHADOW: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+    const PANEL: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+    const HIGHLIGHT: [f32; 4] = [1.0, 1.0, 1.0, 0.15];
+    /// Scoreboard columns: heading and width in characters. The name is
+    /// left aligned and the numbers are right aligned.
+    const COLUMNS: [(&str, usize); 6] = [
+        ("NAME", 16),
+        ("TEAM", 5),
+        ("SCORE", 6),
+        ("FRAGS", 6),
+        ("DEATHS", 7),
+        ("PING", 5),
+    ];
+
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        color_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let projection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("HUD Projection Buffer"),
+            contents: bytemuck::cast_slice(&[Self::projection(width as f32, height as f32)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let projection_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("hud_projection_bind_group_layout"),
+        });
+        let projection_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &projection_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: projection_buffer.as_entire_binding(),
+            }],
+            label: Some("hud_projection_bind_group"),
+        });
+        let (font_layout, font_bind_group) = Self::create_font_bind_group(device, queue);
+
+        let layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            &[&projection_layout, &font_layout],
+        );
+        let pipeline = PipelineFactory::create_overlay_pipeline(
+            device,
+            &layout,
+            color_format,
+            &[QuadVertex::desc()],
+            wgpu::ShaderModuleDescriptor {
+                label: Some("HUD Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/hud.wgsl").into()),
+            },
+        );
+
+        let mut hud = Self {
+            pipeline,
+            batcher: QuadBatcher::new(device),
+            projection_buffer,
+            projection_bind_group,
+            font_bind_group,
+            width: 0.0,
+            height: 0.0,
+            scale: 1.0,
+        };
+        hud.resize(queue, width, height);
+        hud
+    }
+
+    /// Maps window pixels, with y pointing down, onto clip space.
+    fn projection(width: f32, height: f32) -> [[f32; 4]; 4] {
+        Matrix4::new_orthographic(0.0, width, height, 0.0, -1.0, 1.0).into()
+    }
+
+    /// Lays the widgets out again for a window of `width` by `height` pixels.
+    pub fn resize(&mut self, queue: &Queue, width: u32, height: u32) {
+        self.width = width as f32;
+        self.height = height as f32;
+        self.scale = (self.height / Self::BASE_HEIGHT).floor().max(1.0);
+        queue.write_buffer(
+            &self.projection_buffer,
+            0,
+            bytemuck::cast_slice(&[Self::projection(self.width, self.height)]),
+        );
+    }
+
+    /// Rebuilds the overlay from `state`.
+    pub fn update(&mut self, queue: &Queue, state: &HudState) {
+        self.batcher.clear();
+        let margin = Self::MARGIN * self.scale;
+        let line_height = BitmapFont::CELL_HEIGHT as f32 * self.scale;
+        let big = self.scale * 2.0;
+
+        if state.alive {
+            self.crosshair();
+        }
+
+        let bottom = self.height - margin - BitmapFont::GLYPH_HEIGHT as f32 * big;
+        let health_color = if state.health <= Self::LOW_HEALTH {
+            Self::RED
+        } else {
+            Self::WHITE
+        };
+        let mut x = margin;
+        x = self.text("HP", x, bottom, self.scale, Self::WHITE) + self.scale * 2.0;
+        x = self.text(&state.health.to_string(), x, bottom, big, health_color) + margin * 2.0;
+        if state.armor > 0 {
+            x = self.text("AR", x, bottom, self.scale, Self::WHITE) + self.scale * 2.0;
+            self.text(&state.armor.to_string(), x, bottom, big, Self::BLUE);
+        }
+        if let Some(rockets) = state.rockets {
+            let count = rockets.to_string();
+            let x = self.width - margin - BitmapFont::text_width(&count, big);
+            self.text(&count, x, bottom, big, Self::YELLOW);
+            let label = "ROCKETS";
+            let label_x = x - self.scale * 2.0 - BitmapFont::text_width(label, self.scale);
+            self.text(label, label_x, bottom, self.scale, Self::WHITE);
+        }
+
+        for (line, kill) in state.kill_feed.iter().enumerate() {
+            let x = self.width - margin - BitmapFont::text_width(kill, self.scale);
+            let y = margin + line as f32 * line_height;
+            self.text(kill, x, y, self.scale, Self::WHITE);
+        }
+
+        // Chat fills the left half of the window upwards from above the
+        // health, with the line being typed at the bottom.
+        let advance = BitmapFont::CELL_WIDTH as f32 * self.scale;
+        let max_chars = ((self.width / 2.0 - margin) / advance).max(1.0) as usize;
+        let mut lines: Vec<(String, [f32; 4])> = state
+            .chat
+            .iter()
+            .flat_map(|line| Self::wrap(line, max_chars))
+            .map(|line| (line, Self::WHITE))
+            .collect();
+        if let Some(draft) = &state.chat_draft {
+            let chars = draft.chars().count();
+            let visible = draft
+                .chars()
+                .skip(chars.saturating_sub(max_chars))
+                .collect();
+            lines.push((visible, Self::YELLOW));
+        }
+        let mut y = bottom - margin - line_height;
+        for (line, color) in lines.iter().rev() {
+            if y < margin + line_height {
+                break;
+            }
+            self.text(line, margin, y, self.scale, *color);
+            y -= line_height;
+        }
+
+        if let Some(phase) = state.phase {
+            let mut timer = String::from(match phase {
+                MatchPhase::Warmup => "WARMUP",
+                MatchPhase::Live => "",
+                MatchPhase::Intermission => "MATCH OVER",
+            });
+            if let Some(time_left) = state.time_left {
+                let secs = time_left.as_secs_f32().ceil() as u64;
+                if !timer.is_empty() {
+                    timer.push(' ');
+                }
+                timer.push_str(&format!("{}:{:02}", secs / 60, secs % 60));
+            }
+            let x = (self.width - BitmapFont::text_width(&timer, self.scale)) / 2.0;
+            self.text(&timer, x, margin, self.scale, Self::WHITE);
+        }
+
+        if let Some(rows) = &state.scoreboard {
+            self.scoreboard(rows);
+        }
+
+        self.batcher.upload(queue);
+    }
+
+    /// A table of every player in the middle of the window, with the team
+    /// totals above it in team modes.
+    fn scoreboard(&mut self, rows: &[ScoreboardRow]) {
+        let advance = BitmapFont::CELL_WIDTH as f32 * self.scale;
+        let line_height = BitmapFont::CELL_HEIGHT as f32 * self.scale * 1.5;
+        let padding = Self::MARGIN * self.scale;
+        let chars: usize = Self::COLUMNS.iter().map(|(_, width)| width).sum();
+        let width = chars as f32 * advance + padding * 2.0;
+        let height = (rows.len() + 2) as f32 * line_height + padding * 2.0;
+        let left = ((self.width - width) / 2.0).floor();
+        let top = ((self.height - height) / 2.0).floor().max(0.0);
+        self.rect([left, top, width, height], Self::PANEL);
+
+        let team_color = |team| match team {
+            Some(Team::Red) => Self::RED,
+            Some(Team::Blue) => Self::BLUE,
+            None => Self::WHITE,
+        };
+        let x = left + padding;
+        let mut y = top + padding;
+        if rows.iter().any(|row| row.team.is_some()) {
+            let mut total_x = x;
+            for team in [Team::Red, Team::Blue] {
+                let score: i32 = rows
+                    .iter()
+                    .filter(|row| row.team == Some(team))
+                    .map(|row| row.score)
+                    .sum();
+                let total = format!("{team:?} {score}");
+                total_x = self.text(&total, total_x, y, self.scale, team_color(Some(team)))
+                    + advance * 3.0;
+            }
+        } else {
+            self.text("SCOREBOARD", x, y, self.scale, Self::WHITE);
+        }
+        y += line_height;
+
+        let headings = Self::COLUMNS.map(|(heading, _)| heading.to_owned());
+        self.scoreboard_row(&headings, x, y, Self::YELLOW);
+        for row in rows {
+            y += line_height;
+            if row.is_local {
+                let highlight_top =
+                    y - (line_height - BitmapFont::GLYPH_HEIGHT as f32 * self.scale) / 2.0;
+                self.rect(
+                    [left, highlight_top.floor(), width, line_height],
+                    Self::HIGHLIGHT,
+                );
+            }
+            let name_chars = Self::COLUMNS[0].1 - 1;
+            let cells = [
+                row.name.chars().take(name_chars).collect(),
+                row.team
+                    .map_or_else(String::new, |team| format!("{team:?}")),
+                row.score.to_string(),
+                row.frags.to_string(),
+                row.deaths.to_string(),
+                row.ping
+                    .map_or_else(|| String::from("-"), |ping| ping.to_string()),
+            ];
+            self.scoreboard_row(&cells, x, y, team_color(row.team));
+        }
+    }
+
+    /// Draws one scoreboard line of `cells`, one for each column.
+    fn scoreboard_row(&mut self, cells: &[String; 6], x: f32, y: f32, color: [f32; 4]) {
+        let advance = BitmapFont::CELL_WIDTH as f32 * self.scale;
+        let mut column_x = x;
+        for (column, ((_, width), cell)) in Self::COLUMNS.iter().zip(cells).enumerate() {
+            let column_width = *width as f32 * advance;
+            let cell_x = if column < 2 {
+                column_x
+            } else {
+                column_x + column_width - advance - BitmapFont::text_width(cell, self.scale)
+            };
+            self.text(cell, cell_x, y, self.scale, color);
+            column_x += column_width;
+        }
+    }
+
+    pub fn draw(&self, render_pass: &mut RenderPass) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.projection_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.font_bind_group, &[]);
+        self.batcher.draw(render_pass);
+    }
+
+    /// Four bars around the centre of the window.
+    fn crosshair(&mut self) {
+        let (cx, cy) = ((self.width / 2.0).floor(), (self.height / 2.0).floor());
+        let thickness = self.scale;
+        let length = Self::CROSSHAIR_LENGTH * self.scale;
+        let gap = Self::CROSSHAIR_GAP * self.scale;
+        let half = thickness / 2.0;
+        for rect in [
+            [cx - gap - length, cy - half, length, thickness],
+            [cx + gap, cy - half, length, thickness],
+            [cx - half, cy - gap - length, thickness, length],
+            [cx - half, cy + gap, thickness, length],
+        ] {
+            self.rect(rect, Self::WHITE);
+        }
+    }
+
+    /// Splits `text` into lines of at most `max_chars` characters, breaking
+    /// between words where it can.
+    fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+        let mut lines = vec![];
+        let mut line = String::new();
+        let mut line_chars = 0;
+        for word in text.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            while !word.is_empty() {
+                let room = if line_chars == 0 {
+                    max_chars
+                } else {
+                    max_chars.saturating_sub(line_chars + 1)
+                };
+                if word.len() <= room {
+                    if line_chars > 0 {
+                        line.push(' ');
+                        line_chars += 1;
+                    }
+                    line_chars += word.len();
+                    line.extend(word.drain(..));
+                } else if line_chars > 0 {
+                    lines.push(std::mem::take(&mut line));
+                    line_chars = 0;
+                } else {
+                    line.extend(word.drain(..max_chars));
+                    lines.push(std::mem::take(&mut line));
+                }
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    fn rect(&mut self, rect: [f32; 4], color: [f32; 4]) {
+        self.batcher.push(rect, BitmapFont::solid_uv(), color);
+    }
+
+    /// Draws `text` with its top left corner at `x`, `y` and a drop shadow.
+    /// Returns where the text ends.
+    fn text(&mut self, text: &str, x: f32, y: f32, scale: f32, color: [f32; 4]) -> f32 {
+        let glyph_width = BitmapFont::GLYPH_WIDTH as f32 * scale;
+        let glyph_height = BitmapFont::GLYPH_HEIGHT as f32 * scale;
+        let advance = BitmapFont::CELL_WIDTH as f32 * scale;
+        for (offset, color) in [(scale, Self::SHADOW), (0.0, color)] {
+            for (i, c) in text.chars().enumerate() {
+                if c == ' ' {
+                    continue;
+                }
+                let rect = [
+                    x + i as f32 * advance + offset,
+                    y + offset,
+                    glyph_width,
+                    glyph_height,
+                ];
+                self.batcher.push(rect, BitmapFont::glyph_uv(c), color);
+            }
+        }
+        x + BitmapFont::text_width(text, scale)
+    }
+
+    fn create_font_bind_group(device: &Device, queue: &Queue) -> (BindGroupLayout, BindGroup) {
+        let (width, height, pixels) = BitmapFont::atlas();
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("HUD Font Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Nearest filtering keeps the font crisp at whole-pixel scales.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("hud_font_bind_group_layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("hud_font_bind_group"),
+        });
+        (layout, bind_group)
+    }
+}
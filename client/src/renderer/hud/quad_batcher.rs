@@ -0,0 +1,109 @@
+use wgpu::{Buffer, Device, Queue, RenderPass};
+
+use wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct QuadVertex {
+    /// Pixels from the top left corner of the window.
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// Collects textured, tinted quads over a frame and draws them all with a
+/// single call. Every quad uses the same texture.
+pub struct QuadBatcher {
+    vertices: Vec<QuadVertex>,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    num_indices: u32,
+}
+
+impl QuadVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+impl QuadBatcher {
+    /// Quads beyond this many in one frame are dropped; it keeps every index
+    /// within `u16`.
+    pub const MAX_QUADS: usize = 4096;
+
+    pub fn new(device: &Device) -> Self {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Quad Vertex Buffer"),
+            size: (Self::MAX_QUADS * 4 * std::mem::size_of::<QuadVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Every quad is two triangles over its own four vertices, so the
+        // indices never change.
+        let indices: Vec<u16> = (0..Self::MAX_QUADS as u16)
+            .flat_map(|quad| {
+                let first = quad * 4;
+                [first, first + 1, first + 2, first, first + 2, first + 3]
+            })
+            .collect();
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Quad Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            vertices: Vec::with_capacity(Self::MAX_QUADS * 4),
+            vertex_buffer,
+            index_buffer,
+            num_indices: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    /// Queues a quad covering `rect` (left, top, width and height in pixels)
+    /// showing the `uv` area (left, top, right, bottom) of the texture.
+    pub fn push(&mut self, rect: [f32; 4], uv: [f32; 4], color: [f32; 4]) {
+        if self.vertices.len() >= Self::MAX_QUADS * 4 {
+            return;
+        }
+        let [x, y, width, height] = rect;
+        let [u0, v0, u1, v1] = uv;
+        let vertex = |position, tex_coords| QuadVertex {
+            position,
+            tex_coords,
+            color,
+        };
+        self.vertices.extend([
+            vertex([x, y], [u0, v0]),
+            vertex([x, y + height], [u0, v1]),
+            vertex([x + width, y + height], [u1, v1]),
+            vertex([x + width, y], [u1, v0]),
+        ]);
+    }
+
+    /// Sends the queued quads to the GPU for the next `draw`.
+    pub fn upload(&mut self, queue: &Queue) {
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.num_indices = (self.vertices.len() / 4 * 6) as u32;
+    }
+
+    pub fn draw(&self, render_pass: &mut RenderPass) {
+        if self.num_indices == 0 {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+}
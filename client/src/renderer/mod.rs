@@ -1,3 +1,4 @@
+use log::error;
 use nalgebra::{Point3, Vector3};
 use pipeline_factory::PipelineFactory;
 use shadow_baker::ShadowBaker;
@@ -16,7 +17,7 @@ use crate::camera::camera_uniform::CameraUniform;
 use crate::camera::light::Light;
 use crate::camera::light_uniform::LightUniformArray;
 use crate::camera::shadow_map_uniform::ShadowMapUniform;
-use crate::game::collision_manager::CollisionManager;
+use crate::game::chat::ChatInput;
 use crate::game::player::Player;
 use crate::game::player_controller::PlayerController;
 use crate::model::Model;
@@ -24,11 +25,21 @@ use crate::model::cube_texture::{CubeTexture, CubeTextureBuilder};
 use crate::model::depth_texture::DepthTexture;
 use crate::model::map_loader::MapLoader;
 use crate::model::model_instance::RawInstance;
+use crate::model::pickup_model::PickupModel;
 use crate::model::player_model::PlayerModel;
+use crate::model::projectile_model::ProjectileModel;
 use crate::model::texture::TextureBuilder;
 use crate::model::vertex::{LineVertex, Vertex};
 use crate::network::Network;
-
+use game::player_body::PlayerBody;
+use game::world::World;
+use hud::{Hud, HudState, ScoreboardRow};
+use protocol::chat::ChatChannel;
+use protocol::command::Weapon;
+use protocol::input::PlayerInput;
+use protocol::player_state::PlayerState;
+
+mod hud;
 mod pipeline_factory;
 mod shadow_baker;
 
@@ -44,11 +55,17 @@ pub struct Renderer {
     is_surface_configured: bool,
     debug_lines_len: u32,
     player_controller: PlayerController,
+    chat_input: ChatInput,
     map_file: String,
     depth_texture: DepthTexture,
-    collision_manager: CollisionManager,
+    world: World,
     shadow_baker: ShadowBaker,
     player_model_renderer: PlayerModel,
+    projectile_model_renderer: ProjectileModel,
+    pickup_model_renderer: PickupModel,
+    hud: Hud,
+    /// The server's latest word on the local player; default while offline.
+    local_player_state: PlayerState,
     camera_uniform: CameraUniform,
     camera_buffer: Buffer,
     debug_buffer: Buffer,
@@ -65,14 +82,10 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    const MOVE_SPEED: f32 = 2.0;
     const SENSITIVITY: f32 = 0.3;
-    const JUMP_STRENGTH: f32 = 1.6;
-    const HITBOX_WIDTH: f32 = 0.1;
     pub const FAR_PLANE: f32 = 200.0;
     pub const NEAR_PLANE: f32 = 0.01;
-    pub const MAX_PLAYERS: u8 = 32;
-    pub const CAMERA_HEIGHT: f32 = 0.5;
+    pub const MAX_PLAYERS: u8 = protocol::command::MAX_PLAYERS;
     pub async fn new(window: Arc<Window>, map_file: String) -> Result<Self, String> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
@@ -153,7 +166,8 @@ impl Renderer {
         let debug_pipeline_layout =
             PipelineFactory::create_render_pipeline_layout(&device, &[&camera_bind_group_layout]);
 
-        let map_loader = MapLoader::from_file(&map_file).unwrap();
+        let map_loader = MapLoader::from_file(&map_file)
+            .map_err(|e| format!("could not load map {map_file}: {e}"))?;
         let map = map_loader.load(&device, &queue, &diffuse_texture_layout);
         let models = map.models;
         let skybox_files = map.skybox_textures;
@@ -163,28 +177,28 @@ impl Renderer {
         let debug_lines_len = debug_lines.len() as u32;
         let player_head_mesh = map.player_head_mesh;
         let player_body_mesh = map.player_body_mesh;
+        // Offline this is where we play; online the server moves us on join.
+        let spawn_point = map.spawn_points.first().cloned().unwrap_or_default();
+        let eye = Point3::from(spawn_point.position);
         let camera = Camera {
-            position: Point3::new(1.0, Self::CAMERA_HEIGHT, 1.0),
-            target: Point3::new(0.0, Self::CAMERA_HEIGHT, 0.0),
+            position: eye,
+            target: eye + PlayerBody::aim_direction(0.0, spawn_point.yaw),
             up: Vector3::new(0.0, 1.0, 0.0),
             aspect: size.width as f32 / size.height as f32,
             fovy: 1.0,
             near: Self::NEAR_PLANE,
             far: Self::FAR_PLANE,
         };
-        let player = Player::new(
-            Self::SENSITIVITY,
-            Self::MOVE_SPEED,
-            Self::JUMP_STRENGTH,
-            Self::HITBOX_WIDTH,
-            Self::CAMERA_HEIGHT,
-            camera,
-        );
+        let mut world = World::new(collision_manager);
+        let player = Player::new(Self::SENSITIVITY, camera, spawn_point.yaw, &mut world);
         let player_controller = PlayerController::default();
         let light_ids: Vec<u32> = lights.iter().map(|light| light.id).collect();
         let shadow_baker = ShadowBaker::new(&light_ids, &device);
         let player_model_renderer =
             PlayerModel::new(&device, &[], player_head_mesh, player_body_mesh);
+        let projectile_model_renderer = ProjectileModel::new(&device, map.projectile_mesh);
+        let pickup_model_renderer = PickupModel::new(&device, map.pickup_mesh, map.pickups);
+        let hud = Hud::new(&device, &queue, config.format, size.width, size.height);
 
         // uniforms
         let mut camera_uniform = CameraUniform::new(player.camera.position);
@@ -322,7 +336,7 @@ impl Renderer {
             models,
             lights,
             player,
-            collision_manager,
+            world,
             map_file,
             camera_uniform,
             camera_buffer,
@@ -333,6 +347,7 @@ impl Renderer {
             skybox_bind_group,
             skybox_render_pipeline,
             player_controller,
+            chat_input: ChatInput::default(),
             debug_render_pipeline,
             debug_lines_len,
             debug_buffer,
@@ -342,6 +357,10 @@ impl Renderer {
             shadow_baker,
             player_pipeline,
             player_model_renderer,
+            projectile_model_renderer,
+            pickup_model_renderer,
+            hud,
+            local_player_state: PlayerState::default(),
         })
     }
 
@@ -412,6 +431,8 @@ impl Renderer {
             render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
             render_pass.set_bind_group(1, &self.point_light_bind_group, &[]);
             self.player_model_renderer.draw(&mut render_pass);
+            self.projectile_model_renderer.draw(&mut render_pass);
+            self.pickup_model_renderer.draw(&mut render_pass);
 
             render_pass.set_pipeline(&self.skybox_render_pipeline);
             render_pass.set_bind_group(0, &self.skybox_bind_group, &[]);
@@ -425,6 +446,23 @@ impl Renderer {
                 render_pass.draw(0..self.debug_lines_len, 0..1);
             }
         }
+        {
+            let mut overlay_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("HUD Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            self.hud.draw(&mut overlay_pass);
+        }
 
         // submit will accept anything that implements IntoIter
         self.queue.submit(std::iter::once(encoder.finish()));
@@ -433,24 +471,87 @@ impl Renderer {
         Ok(())
     }
 
-    pub fn update(&mut self, dt: Duration, network_handler: &Option<Network>) {
+    /// Steps the local player and returns the input it was moved with.
+    pub fn update(&mut self, dt: Duration, network_handler: &mut Option<Network>) -> PlayerInput {
         if let Some(network_handler) = network_handler {
-            let player_states = network_handler
-                .player_states
-                .values()
-                .cloned()
-                .collect::<Vec<_>>();
+            if let Some(update) = network_handler.take_local_player_update() {
+                self.player.reconcile(&update, &mut self.world);
+                self.local_player_state = update.player_state;
+            }
+            let mut player_states = network_handler.remote_player_states();
+            player_states.retain(|player_state| player_state.health > 0);
             self.player_model_renderer
                 .update(&self.queue, &player_states);
+            self.projectile_model_renderer
+                .update(&self.queue, &network_handler.projectile_positions());
         }
-        self.player
-            .update(dt, &mut self.collision_manager, &mut self.player_controller);
+        let network_handler = network_handler.as_ref();
+        self.pickup_model_renderer
+            .update(&self.queue, dt.as_secs_f32(), |pickup| {
+                network_handler
+                    .is_none_or(|network_handler| network_handler.is_pickup_available(pickup))
+            });
+        let chat_draft = self
+            .chat_input
+            .draft()
+            .map(|(channel, text)| match channel {
+                ChatChannel::All => format!("SAY: {text}_"),
+                ChatChannel::Team => format!("SAY TEAM: {text}_"),
+            });
+        let chat = network_handler
+            .map(|network_handler| {
+                if chat_draft.is_some() {
+                    network_handler.chat_history()
+                } else {
+                    network_handler.recent_chat()
+                }
+            })
+            .unwrap_or_default();
+        let local_player_id = self.local_player_state.player_id;
+        let scoreboard = network_handler
+            .filter(|_| self.player_controller.show_scoreboard)
+            .map(|network_handler| {
+                network_handler
+                    .scoreboard()
+                    .into_iter()
+                    .map(|(entry, ping)| ScoreboardRow {
+                        is_local: entry.player_id == local_player_id,
+                        name: entry.name,
+                        team: entry.team,
+                        score: entry.score,
+                        frags: entry.frags,
+                        deaths: entry.deaths,
+                        ping,
+                    })
+                    .collect()
+            });
+        let state = &self.local_player_state;
+        let hud_state = HudState {
+            alive: !self.player.is_dead(),
+            health: state.health,
+            armor: state.armor,
+            rockets: state
+                .has_weapon(Weapon::RocketLauncher)
+                .then_some(state.rockets),
+            kill_feed: network_handler.map(Network::kill_feed).unwrap_or_default(),
+            phase: network_handler.and_then(Network::match_phase),
+            time_left: network_handler.and_then(Network::phase_time_left),
+            chat,
+            chat_draft,
+            scoreboard,
+        };
+        self.hud.update(&self.queue, &hud_state);
+
+        let input = self
+            .player
+            .update(dt, &mut self.world, &mut self.player_controller);
         self.camera_uniform.update_cam(&self.player.camera);
         self.queue.write_buffer(
             &self.camera_buffer,
             0,
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
+        input
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -461,6 +562,7 @@ impl Renderer {
             self.is_surface_configured = true;
             self.depth_texture =
                 DepthTexture::create_depth_texture(&self.device, &self.config, "depth_texture");
+            self.hud.resize(&self.queue, width, height);
         }
     }
 
@@ -470,7 +572,13 @@ impl Renderer {
         let point_light_bind_group_layout =
             LightUniformArray::create_bind_group_layout(&self.device);
 
-        let map_loader = MapLoader::from_file(&self.map_file).unwrap();
+        let map_loader = match MapLoader::from_file(&self.map_file) {
+            Ok(map_loader) => map_loader,
+            Err(e) => {
+                error!("could not reload map {}: {e}", self.map_file);
+                return;
+            }
+        };
         let map = map_loader.load(&self.device, &self.queue, &diffuse_texture_layout);
         let models = map.models;
         let skybox_files = map.skybox_textures;
@@ -517,19 +625,26 @@ impl Renderer {
         self.point_light_bind_group = point_light_bind_group;
         self.debug_buffer = debug_buffer;
         self.debug_lines_len = debug_lines_len;
-        self.collision_manager = collision_manager;
+        self.world.collision_manager = collision_manager;
         self.shadow_baker.update_scene_version();
         for light in &self.lights {
             self.shadow_baker.update_light_version_from_id(light.id);
         }
     }
 
+    /// Weapon and direction of the shot the player asked for since the last
+    /// call, if any.
+    pub fn take_shot(&mut self) -> Option<(Weapon, Vector3<f32>)> {
+        let weapon = self.player_controller.fire_requested.take()?;
+        Some((weapon, self.player.aim_direction(&self.world)?))
+    }
+
     pub fn get_mut_player_controller(&mut self) -> &mut PlayerController {
         &mut self.player_controller
     }
 
-    pub fn get_player(&self) -> &Player {
-        &self.player
+    pub fn get_mut_chat_input(&mut self) -> &mut ChatInput {
+        &mut self.chat_input
     }
 
     pub fn get_window(&self) -> &Arc<Window> {
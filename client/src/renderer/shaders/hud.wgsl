@@ -0,0 +1,39 @@
+struct Projection {
+    ortho: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> projection: Projection;
+
+@group(1) @binding(0)
+var t_font: texture_2d<f32>;
+@group(1) @binding(1)
+var s_font: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+    in: VertexInput
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = in.tex_coords;
+    out.color = in.color;
+    out.clip_position = projection.ortho * vec4<f32>(in.position, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color * textureSample(t_font, s_font, in.tex_coords);
+}
@@ -0,0 +1,138 @@
+//! Collision queries against large generated maps, through the grid and by
+//! testing every box, e.g. `cargo bench -p game --bench collision`.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use game::{
+    bounding_box::{BoundingBox, Contact},
+    collision_manager::CollisionManager,
+    player_body::PlayerBody,
+};
+use nalgebra::{Point3, Vector3};
+
+const MAP_SIZES: [usize; 2] = [10_000, 50_000];
+
+/// Minimal linear congruential generator so every run gets the same map.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> f32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next()
+    }
+}
+
+/// A floor with `count` crates and pillars of up to four units scattered
+/// over a square sized to keep them about as dense whatever the count.
+fn generated_map(count: usize) -> Vec<BoundingBox> {
+    let mut rng = Lcg(count as u64);
+    let half = (count as f32).sqrt() * 2.0;
+    let mut boxes = vec![BoundingBox {
+        top_left: Point3::new(-half, 0.0, -half),
+        bottom_right: Point3::new(half, -1.0, half),
+        collide_on_top: false,
+    }];
+    for _ in 1..count {
+        let (x, z) = (rng.range(-half, half), rng.range(-half, half));
+        let (width, height, depth) = (
+            rng.range(0.2, 4.0),
+            rng.range(0.2, 4.0),
+            rng.range(0.2, 4.0),
+        );
+        boxes.push(BoundingBox {
+            top_left: Point3::new(x, height, z),
+            bottom_right: Point3::new(x + width, 0.0, z + depth),
+            collide_on_top: false,
+        });
+    }
+    boxes
+}
+
+fn brute_force_segment_hit(
+    boxes: &[BoundingBox],
+    start: Point3<f32>,
+    delta: Vector3<f32>,
+) -> Option<f32> {
+    boxes
+        .iter()
+        .filter_map(|map_box| map_box.segment_hit(start, delta))
+        .min_by(f32::total_cmp)
+}
+
+/// The first contact of a move, which is what every step of sliding looks
+/// for.
+fn brute_force_sweep(
+    boxes: &[BoundingBox],
+    player_box: &BoundingBox,
+    velocity: Vector3<f32>,
+) -> Option<Contact> {
+    boxes
+        .iter()
+        .filter_map(|map_box| player_box.sweep(map_box, velocity))
+        .min_by(|a, b| a.time.total_cmp(&b.time))
+}
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    for count in MAP_SIZES {
+        let boxes = generated_map(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &boxes, |b, boxes| {
+            b.iter(|| CollisionManager::new(black_box(boxes.clone())))
+        });
+    }
+    group.finish();
+}
+
+fn segment_hit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("segment_hit");
+    for count in MAP_SIZES {
+        let boxes = generated_map(count);
+        let collision_manager = CollisionManager::new(boxes.clone());
+        // A rifle shot across part of the map at head height.
+        let start = Point3::new(0.0, 0.4, 0.0);
+        let delta = Vector3::new(60.0, -0.2, 35.0);
+        group.bench_function(BenchmarkId::new("grid", count), |b| {
+            b.iter(|| collision_manager.segment_hit(black_box(start), black_box(delta)))
+        });
+        group.bench_function(BenchmarkId::new("brute_force", count), |b| {
+            b.iter(|| brute_force_segment_hit(&boxes, black_box(start), black_box(delta)))
+        });
+    }
+    group.finish();
+}
+
+fn move_player(c: &mut Criterion) {
+    let mut group = c.benchmark_group("move_player");
+    for count in MAP_SIZES {
+        let boxes = generated_map(count);
+        let collision_manager = CollisionManager::new(boxes.clone());
+        let hitbox = PlayerBody::hitbox_at(Point3::new(0.5, PlayerBody::HITBOX_HEIGHT, 0.5));
+        // One tick of running and falling.
+        let velocity = Vector3::new(0.1, -0.05, 0.07);
+        group.bench_function(BenchmarkId::new("grid", count), |b| {
+            b.iter(|| {
+                let mut player_box = hitbox.clone();
+                collision_manager.move_player(
+                    &mut player_box,
+                    black_box(velocity),
+                    PlayerBody::STEP_HEIGHT,
+                )
+            })
+        });
+        group.bench_function(BenchmarkId::new("brute_force", count), |b| {
+            b.iter(|| brute_force_sweep(&boxes, &hitbox, black_box(velocity)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, build, segment_hit, move_player);
+criterion_main!(benches);
@@ -0,0 +1,36 @@
+//! Builds the navigation graph of a map and saves it next to the map, e.g.
+//! `cargo run -p game --bin navgen -- client/src/model/maps/map_1.json`.
+
+use std::{env, path::PathBuf, process::ExitCode};
+
+use game::map::CollisionMapLoader;
+use game::navigation::{NavGraph, Reach};
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let Some(map_file) = args.next().map(PathBuf::from) else {
+        eprintln!("usage: navgen MAP_FILE");
+        return ExitCode::FAILURE;
+    };
+    let map = match CollisionMapLoader::from_file(&map_file.to_string_lossy()) {
+        Ok(map) => map,
+        Err(e) => {
+            eprintln!("error: {}: {e}", map_file.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let graph = NavGraph::build(&map.load(), Reach::default());
+    let nav_file = NavGraph::file_for_map(&map_file);
+    if let Err(e) = graph.write(&nav_file) {
+        eprintln!("error: {}: {e}", nav_file.display());
+        return ExitCode::FAILURE;
+    }
+    let links: usize = (0..graph.len()).map(|node| graph.edges(node).len()).sum();
+    println!(
+        "wrote {} nodes and {links} links to {}",
+        graph.len(),
+        nav_file.display()
+    );
+    ExitCode::SUCCESS
+}
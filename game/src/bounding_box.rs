@@ -0,0 +1,133 @@
+use nalgebra::{Point3, Vector3};
+
+#[derive(Debug, Clone)]
+pub struct BoundingBox {
+    pub top_left: Point3<f32>,
+    pub bottom_right: Point3<f32>,
+    pub collide_on_top: bool,
+}
+
+impl BoundingBox {
+    /// How close a moving box stops short of what it hits, so rounding
+    /// never leaves the two overlapping.
+    pub const SKIN: f32 = 0.0001;
+
+    pub fn is_colliding_with(&self, other: &Self) -> bool {
+        self.top_left.x < other.bottom_right.x
+            && self.bottom_right.x > other.top_left.x
+            && self.top_left.y > other.bottom_right.y
+            && self.bottom_right.y < other.top_left.y
+            && self.top_left.z < other.bottom_right.z
+            && self.bottom_right.z > other.top_left.z
+    }
+
+    /// Fraction of `delta` a point moving from `start` travels before it
+    /// enters this box, or `None` if the segment misses it.
+    pub fn segment_hit(&self, start: Point3<f32>, delta: Vector3<f32>) -> Option<f32> {
+        let min = [self.top_left.x, self.bottom_right.y, self.top_left.z];
+        let max = [self.bottom_right.x, self.top_left.y, self.bottom_right.z];
+        let mut t_enter: f32 = 0.0;
+        let mut t_exit: f32 = 1.0;
+        for axis in 0..3 {
+            if delta[axis].abs() < f32::EPSILON {
+                if start[axis] < min[axis] || start[axis] > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (min[axis] - start[axis]) / delta[axis];
+            let t1 = (max[axis] - start[axis]) / delta[axis];
+            t_enter = t_enter.max(t0.min(t1));
+            t_exit = t_exit.min(t0.max(t1));
+            if t_enter > t_exit {
+                return None;
+            }
+        }
+        Some(t_enter)
+    }
+
+    /// The point inside (or on) this box nearest to `point`.
+    pub fn closest_point(&self, point: Point3<f32>) -> Point3<f32> {
+        Point3::new(
+            point.x.clamp(self.top_left.x, self.bottom_right.x),
+            point.y.clamp(self.bottom_right.y, self.top_left.y),
+            point.z.clamp(self.top_left.z, self.bottom_right.z),
+        )
+    }
+
+    pub fn move_by(&mut self, delta: Vector3<f32>) {
+        self.top_left += delta;
+        self.bottom_right += delta;
+    }
+
+    /// Corner with the lowest coordinates on every axis.
+    pub fn min_corner(&self) -> Point3<f32> {
+        Point3::new(self.top_left.x, self.bottom_right.y, self.top_left.z)
+    }
+
+    /// Corner with the highest coordinates on every axis.
+    pub fn max_corner(&self) -> Point3<f32> {
+        Point3::new(self.bottom_right.x, self.top_left.y, self.bottom_right.z)
+    }
+
+    /// When this box, moving by `delta`, first runs into `other`. Boxes
+    /// already touching count if moving into each other, but boxes
+    /// overlapping by more than `SKIN` are let through so a player stuck in
+    /// one can walk out. A box with `collide_on_top` only stops boxes coming
+    /// down onto it.
+    pub fn sweep(&self, other: &Self, delta: Vector3<f32>) -> Option<Contact> {
+        let (min, max) = (self.min_corner(), self.max_corner());
+        let (other_min, other_max) = (other.min_corner(), other.max_corner());
+        let mut t_enter = f32::NEG_INFINITY;
+        let mut t_exit = f32::INFINITY;
+        let mut hit_axis = None;
+        for axis in 0..3 {
+            if delta[axis] == 0.0 {
+                if max[axis] <= other_min[axis] || min[axis] >= other_max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let (entry, exit) = if delta[axis] > 0.0 {
+                (other_min[axis] - max[axis], other_max[axis] - min[axis])
+            } else {
+                (other_max[axis] - min[axis], other_min[axis] - max[axis])
+            };
+            let (entry, exit) = (entry / delta[axis], exit / delta[axis]);
+            if entry > t_enter {
+                t_enter = entry;
+                hit_axis = Some(axis);
+            }
+            t_exit = t_exit.min(exit);
+        }
+        let axis = hit_axis?;
+        // Sliding past an edge, out of reach, or moving away.
+        if t_enter >= t_exit || t_enter > 1.0 || t_exit <= 0.0 {
+            return None;
+        }
+        if t_enter * delta[axis].abs() < -Self::SKIN {
+            return None;
+        }
+        let mut normal = Vector3::zeros();
+        normal[axis] = -delta[axis].signum();
+        if other.collide_on_top && normal.y <= 0.0 {
+            return None;
+        }
+        Some(Contact {
+            time: t_enter.max(0.0),
+            axis,
+            normal,
+        })
+    }
+}
+
+/// Where a moving box first touches another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    /// Fraction of the movement travelled before touching.
+    pub time: f32,
+    /// Axis the two faces meet on, 0 to 2 for x to z.
+    pub axis: usize,
+    /// Unit normal of the face that was hit, pointing back at the mover.
+    pub normal: Vector3<f32>,
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use nalgebra::{Point3, Vector3};
+
+use crate::bounding_box::BoundingBox;
+
+/// A uniform grid over the map's boxes, built once when the map loads, that
+/// narrows a query down to the boxes near it. Boxes spanning too many
+/// cells are kept aside and handed to every query instead.
+#[derive(Debug, Clone, Default)]
+pub struct BoxGrid {
+    cell_size: f32,
+    /// Indices of the boxes touching each cell, in ascending order.
+    cells: HashMap<[i32; 3], Vec<u32>>,
+    /// Indices of the boxes too big to be put in cells.
+    large: Vec<u32>,
+    /// Everything the cells cover, to cut rays short.
+    bounds: Option<BoundingBox>,
+}
+
+impl BoxGrid {
+    /// Boxes covering more cells than this go in the large list.
+    const MAX_CELLS_PER_BOX: i64 = 64;
+    /// Cells are grown by this much so boxes touching a cell's side are
+    /// found from either side despite rounding.
+    const MARGIN: f32 = 1e-4;
+
+    pub fn new(boxes: &[BoundingBox]) -> Self {
+        // Cells about the size of a typical box keep both the boxes per
+        // cell and the cells per box low.
+        let mut extents: Vec<f32> = boxes
+            .iter()
+            .map(|map_box| {
+                let size = map_box.max_corner() - map_box.min_corner();
+                size.x.max(size.y).max(size.z)
+            })
+            .collect();
+        extents.sort_by(f32::total_cmp);
+        let cell_size = extents
+            .get(extents.len() / 2)
+            .map_or(1.0, |median| median.clamp(1.0, 32.0));
+
+        let mut grid = Self {
+            cell_size,
+            ..Self::default()
+        };
+        for (index, map_box) in boxes.iter().enumerate() {
+            let (low, high) = grid.cell_range(map_box);
+            let cells: i64 = (0..3)
+                .map(|axis| (high[axis] - low[axis] + 1) as i64)
+                .product();
+            if cells > Self::MAX_CELLS_PER_BOX {
+                grid.large.push(index as u32);
+                continue;
+            }
+            for x in low[0]..=high[0] {
+                for y in low[1]..=high[1] {
+                    for z in low[2]..=high[2] {
+                        grid.cells.entry([x, y, z]).or_default().push(index as u32);
+                    }
+                }
+            }
+            grid.bounds = Some(match grid.bounds.take() {
+                Some(bounds) => Self::union(&bounds, map_box),
+                None => map_box.clone(),
+            });
+        }
+        grid
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Indices of every box that may touch `region`, in ascending order.
+    pub fn candidates(&self, region: &BoundingBox) -> Vec<usize> {
+        let (low, high) = self.cell_range(region);
+        let mut found: Vec<u32> = self.large.clone();
+        for x in low[0]..=high[0] {
+            for y in low[1]..=high[1] {
+                for z in low[2]..=high[2] {
+                    if let Some(cell) = self.cells.get(&[x, y, z]) {
+                        found.extend_from_slice(cell);
+                    }
+                }
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        found.into_iter().map(|index| index as usize).collect()
+    }
+
+    /// Indices of every box that may touch `region` once it has been moved
+    /// by `delta`, anywhere along the way.
+    pub fn swept_candidates(&self, region: &BoundingBox, delta: Vector3<f32>) -> Vec<usize> {
+        let mut moved = region.clone();
+        moved.move_by(delta);
+        self.candidates(&Self::union(region, &moved))
+    }
+
+    /// Walks the cells the segment from `start` along `delta` passes
+    /// through, nearest first, handing each cell's boxes to `visit`. `visit`
+    /// returns the nearest hit among them, as a fraction of `delta`, and the
+    /// walk stops once no further cell can hold anything nearer. Large boxes
+    /// are visited first. Boxes in several cells may be visited repeatedly.
+    pub fn walk_segment(
+        &self,
+        start: Point3<f32>,
+        delta: Vector3<f32>,
+        mut visit: impl FnMut(&[u32]) -> Option<f32>,
+    ) {
+        let mut nearest = visit(&self.large);
+        if !start
+            .iter()
+            .chain(delta.iter())
+            .all(|value| value.is_finite())
+        {
+            return;
+        }
+        let Some((t_start, t_end)) = self
+            .bounds
+            .as_ref()
+            .and_then(|bounds| Self::clip(bounds, start, delta))
+        else {
+            return;
+        };
+
+        let entry = start + delta * t_start;
+        let mut cell = [0; 3];
+        let mut step = [0; 3];
+        let mut t_next = [f32::INFINITY; 3];
+        let mut t_step = [f32::INFINITY; 3];
+        for axis in 0..3 {
+            cell[axis] = (entry[axis] / self.cell_size).floor() as i32;
+            if delta[axis] > 0.0 {
+                step[axis] = 1;
+                let boundary = (cell[axis] + 1) as f32 * self.cell_size;
+                t_next[axis] = (boundary - start[axis]) / delta[axis];
+                t_step[axis] = self.cell_size / delta[axis];
+            } else if delta[axis] < 0.0 {
+                step[axis] = -1;
+                let boundary = cell[axis] as f32 * self.cell_size;
+                t_next[axis] = (boundary - start[axis]) / delta[axis];
+                t_step[axis] = -self.cell_size / delta[axis];
+            }
+        }
+
+        loop {
+            if let Some(boxes) = self.cells.get(&cell)
+                && let Some(hit) = visit(boxes)
+            {
+                nearest = Some(nearest.map_or(hit, |nearest: f32| nearest.min(hit)));
+            }
+            let axis = (0..3)
+                .min_by(|a, b| t_next[*a].total_cmp(&t_next[*b]))
+                .unwrap_or(0);
+            let t_exit = t_next[axis];
+            // Anything in a later cell is entered after this one is left.
+            if nearest.is_some_and(|nearest| nearest <= t_exit) || t_exit > t_end {
+                return;
+            }
+            cell[axis] += step[axis];
+            t_next[axis] += t_step[axis];
+        }
+    }
+
+    /// The inclusive range of cells `region` touches.
+    fn cell_range(&self, region: &BoundingBox) -> ([i32; 3], [i32; 3]) {
+        let cell = |value: f32| (value / self.cell_size).floor() as i32;
+        let (min, max) = (region.min_corner(), region.max_corner());
+        (
+            [0, 1, 2].map(|axis| cell(min[axis] - Self::MARGIN)),
+            [0, 1, 2].map(|axis| cell(max[axis] + Self::MARGIN)),
+        )
+    }
+
+    /// The part of the segment from `start` along `delta` inside `bounds`,
+    /// as fractions of `delta`.
+    fn clip(bounds: &BoundingBox, start: Point3<f32>, delta: Vector3<f32>) -> Option<(f32, f32)> {
+        let (min, max) = (bounds.min_corner(), bounds.max_corner());
+        let (mut t_enter, mut t_exit) = (0.0_f32, 1.0_f32);
+        for axis in 0..3 {
+            if delta[axis] == 0.0 {
+                if start[axis] < min[axis] - Self::MARGIN || start[axis] > max[axis] + Self::MARGIN
+                {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (min[axis] - Self::MARGIN - start[axis]) / delta[axis];
+            let t1 = (max[axis] + Self::MARGIN - start[axis]) / delta[axis];
+            t_enter = t_enter.max(t0.min(t1));
+            t_exit = t_exit.min(t0.max(t1));
+            if t_enter > t_exit {
+                return None;
+            }
+        }
+        Some((t_enter, t_exit))
+    }
+
+    fn union(a: &BoundingBox, b: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            top_left: Point3::new(
+                a.top_left.x.min(b.top_left.x),
+                a.top_left.y.max(b.top_left.y),
+                a.top_left.z.min(b.top_left.z),
+            ),
+            bottom_right: Point3::new(
+                a.bottom_right.x.max(b.bottom_right.x),
+                a.bottom_right.y.min(b.bottom_right.y),
+                a.bottom_right.z.max(b.bottom_right.z),
+            ),
+            collide_on_top: false,
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use nalgebra::{Point3, Vector3};
+
+use super::{bounding_box::BoundingBox, broad_phase::BoxGrid};
+
+/// The map's boxes, with a grid over them so queries only look at the
+/// boxes nearby.
+#[derive(Debug)]
+pub struct CollisionManager {
+    map_boxes: Vec<BoundingBox>,
+    grid: BoxGrid,
+}
+
+impl CollisionManager {
+    pub fn new(map_boxes: Vec<BoundingBox>) -> Self {
+        Self {
+            grid: BoxGrid::new(&map_boxes),
+            map_boxes,
+        }
+    }
+
+    pub fn map_boxes(&self) -> &[BoundingBox] {
+        &self.map_boxes
+    }
+
+    /// Every map box `region` collides with, in map order.
+    pub fn overlapping<'a>(
+        &'a self,
+        region: &'a BoundingBox,
+    ) -> impl Iterator<Item = &'a BoundingBox> + 'a {
+        self.grid
+            .candidates(region)
+            .into_iter()
+            .map(|index| &self.map_boxes[index])
+            .filter(|map_box| region.is_colliding_with(map_box))
+    }
+
+    /// Fraction of `delta` travelled from `start` before hitting the map.
+    pub fn segment_hit(&self, start: Point3<f32>, delta: Vector3<f32>) -> Option<f32> {
+        let mut nearest = None;
+        self.grid.walk_segment(start, delta, |indices| {
+            let hit = indices
+                .iter()
+                .filter_map(|index| self.map_boxes[*index as usize].segment_hit(start, delta))
+                .min_by(f32::total_cmp);
+            if let Some(hit) = hit {
+                nearest = Some(nearest.map_or(hit, |nearest: f32| nearest.min(hit)));
+            }
+            hit
+        });
+        nearest
+    }
+
+    /// Moves `player_box` by `velocity` as far as the map lets it, sliding
+    /// along whatever it hits. Given a `step_height`, a box blocked by a
+    /// ledge no higher than that climbs onto it instead.
+    pub fn move_player(
+        &self,
+        player_box: &mut BoundingBox,
+        velocity: Vector3<f32>,
+        step_height: f32,
+    ) -> Movement {
+        let mut reach = player_box.clone();
+        reach.top_left.y += step_height.max(0.0);
+        let candidates: Vec<&BoundingBox> = self
+            .grid
+            .swept_candidates(&reach, velocity)
+            .into_iter()
+            .map(|index| &self.map_boxes[index])
+            .collect();
+        Self::move_among(&candidates, player_box, velocity, step_height)
+    }
+
+    fn move_among(
+        candidates: &[&BoundingBox],
+        player_box: &mut BoundingBox,
+        velocity: Vector3<f32>,
+        step_height: f32,
+    ) -> Movement {
+        let mut moved = player_box.clone();
+        let slid = Self::slide(candidates, &mut moved, velocity);
+        if step_height <= 0.0 || !slid.blocked {
+            *player_box = moved;
+            return slid.movement;
+        }
+
+        // Try again over the top: up, across, and back down onto the ledge.
+        let mut stepped = player_box.clone();
+        let up = Self::slide(
+            candidates,
+            &mut stepped,
+            Vector3::new(0.0, step_height, 0.0),
+        );
+        let across = Self::slide(
+            candidates,
+            &mut stepped,
+            Vector3::new(velocity.x, 0.0, velocity.z),
+        );
+        let drop = up.movement.displacement.y + (-velocity.y).max(0.0);
+        let down = Self::slide(candidates, &mut stepped, Vector3::new(0.0, -drop, 0.0));
+        let horizontal = |movement: &Movement| movement.displacement.xz().norm_squared();
+        if down.movement.on_ground && horizontal(&across.movement) > horizontal(&slid.movement) {
+            *player_box = stepped;
+            return Movement {
+                displacement: up.movement.displacement
+                    + across.movement.displacement
+                    + down.movement.displacement,
+                on_ground: true,
+            };
+        }
+        *player_box = moved;
+        slid.movement
+    }
+
+    /// Moves `player_box` until it hits something, then on along the face it
+    /// hit with the rest of the movement, once for each axis at most.
+    fn slide(
+        candidates: &[&BoundingBox],
+        player_box: &mut BoundingBox,
+        delta: Vector3<f32>,
+    ) -> Slide {
+        let mut slide = Slide {
+            movement: Movement {
+                displacement: Vector3::zeros(),
+                on_ground: false,
+            },
+            blocked: false,
+        };
+        let mut remaining = delta;
+        for _ in 0..3 {
+            let contact = candidates
+                .iter()
+                .filter_map(|map_box| player_box.sweep(map_box, remaining))
+                .min_by(|a, b| a.time.total_cmp(&b.time));
+            let Some(contact) = contact else {
+                player_box.move_by(remaining);
+                slide.movement.displacement += remaining;
+                break;
+            };
+            let axis = contact.axis;
+            let mut travel = remaining * contact.time;
+            travel[axis] =
+                travel[axis].signum() * (travel[axis].abs() - BoundingBox::SKIN).max(0.0);
+            player_box.move_by(travel);
+            slide.movement.displacement += travel;
+            remaining *= 1.0 - contact.time;
+            remaining[axis] = 0.0;
+            if contact.normal.y > 0.0 {
+                slide.movement.on_ground = true;
+            } else if axis != 1 {
+                slide.blocked = true;
+            }
+        }
+        slide
+    }
+}
+
+/// How a box moved through the map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Movement {
+    pub displacement: Vector3<f32>,
+    /// Whether the box came down onto something it now stands on.
+    pub on_ground: bool,
+}
+
+struct Slide {
+    movement: Movement,
+    /// Whether a wall cut the movement short.
+    blocked: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::player_body::PlayerBody;
+
+    fn block(min: [f32; 3], size: [f32; 3]) -> BoundingBox {
+        BoundingBox {
+            top_left: Point3::new(min[0], min[1] + size[1], min[2]),
+            bottom_right: Point3::new(min[0] + size[0], min[1], min[2] + size[2]),
+            collide_on_top: false,
+        }
+    }
+
+    /// Mostly small boxes anywhere, some on whole units so their sides lie
+    /// on cell borders, and now and then one too big for the grid.
+    fn map_box() -> impl Strategy<Value = BoundingBox> {
+        let anywhere = (prop::array::uniform3(-20.0_f32..20.0), 0.05_f32..3.0)
+            .prop_map(|(min, size)| block(min, [size, size * 0.5, size * 1.5]));
+        let aligned = (
+            prop::array::uniform3(-20_i32..20),
+            prop::array::uniform3(1_i32..4),
+        )
+            .prop_map(|(min, size)| block(min.map(|v| v as f32), size.map(|v| v as f32)));
+        let large = (prop::array::uniform3(-30.0_f32..0.0), 20.0_f32..60.0)
+            .prop_map(|(min, size)| block(min, [size, 1.0, size]));
+        prop_oneof![6 => anywhere, 3 => aligned, 1 => large]
+    }
+
+    fn vector(range: f32) -> impl Strategy<Value = Vector3<f32>> {
+        let any = prop::array::uniform3(-range..range).prop_map(Vector3::from);
+        // Rays along an axis are the ones grids get wrong.
+        let along_axis = (0_usize..3, -range..range).prop_map(|(axis, length)| {
+            let mut vector = Vector3::zeros();
+            vector[axis] = length;
+            vector
+        });
+        prop_oneof![any, along_axis]
+    }
+
+    /// How far from a face a box stops, allowing for rounding.
+    const NEAR: f32 = 2.0 * BoundingBox::SKIN;
+
+    /// A floor from -5 to 5 with its top at y 0.
+    fn floor() -> BoundingBox {
+        block([-5.0, -1.0, -5.0], [10.0, 1.0, 10.0])
+    }
+
+    fn standing_at(x: f32, z: f32) -> BoundingBox {
+        PlayerBody::hitbox_at(Point3::new(x, PlayerBody::HITBOX_HEIGHT, z))
+    }
+
+    fn feet(player_box: &BoundingBox) -> f32 {
+        player_box.bottom_right.y
+    }
+
+    #[test]
+    fn slides_along_walls() {
+        let wall = block([1.0, 0.0, -5.0], [0.5, 2.0, 10.0]);
+        let collision_manager = CollisionManager::new(vec![floor(), wall]);
+        let mut player_box = standing_at(0.5, 0.0);
+        let movement =
+            collision_manager.move_player(&mut player_box, Vector3::new(1.0, -0.01, 1.0), 0.0);
+
+        assert!((player_box.bottom_right.x - 1.0).abs() <= NEAR);
+        assert!(player_box.bottom_right.x <= 1.0);
+        assert!((movement.displacement.z - 1.0).abs() < 1e-6);
+        assert!(movement.on_ground);
+    }
+
+    #[test]
+    fn fast_falls_land_on_thin_floors() {
+        let thin = block([-5.0, -0.02, -5.0], [10.0, 0.02, 10.0]);
+        let collision_manager = CollisionManager::new(vec![thin]);
+        let mut player_box = standing_at(0.0, 0.0);
+        player_box.move_by(Vector3::new(0.0, 3.0, 0.0));
+        let movement =
+            collision_manager.move_player(&mut player_box, Vector3::new(0.0, -50.0, 0.0), 0.0);
+
+        assert!(movement.on_ground);
+        assert!(feet(&player_box) >= 0.0);
+        assert!(feet(&player_box) <= NEAR);
+    }
+
+    #[test]
+    fn standing_still_keeps_ground_contact() {
+        let collision_manager = CollisionManager::new(vec![floor()]);
+        let mut player_box = standing_at(0.0, 0.0);
+        for _ in 0..20 {
+            let movement =
+                collision_manager.move_player(&mut player_box, Vector3::new(0.0, -0.005, 0.0), 0.0);
+            assert!(movement.on_ground);
+        }
+        assert!(feet(&player_box) >= 0.0);
+        assert!(feet(&player_box) <= NEAR);
+
+        let movement =
+            collision_manager.move_player(&mut player_box, Vector3::new(0.0, 0.1, 0.0), 0.0);
+        assert!(!movement.on_ground);
+    }
+
+    #[test]
+    fn one_way_platforms_only_stop_falls() {
+        let platform = BoundingBox {
+            collide_on_top: true,
+            ..block([-1.0, 1.0, -1.0], [2.0, 0.1, 2.0])
+        };
+        let collision_manager = CollisionManager::new(vec![floor(), platform]);
+
+        // Jumping up through it from below.
+        let mut player_box = standing_at(0.0, 0.0);
+        let movement =
+            collision_manager.move_player(&mut player_box, Vector3::new(0.0, 1.5, 0.0), 0.0);
+        assert!((movement.displacement.y - 1.5).abs() < 1e-6);
+
+        // Then falling back onto it.
+        let movement =
+            collision_manager.move_player(&mut player_box, Vector3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(movement.on_ground);
+        assert!((feet(&player_box) - 1.1).abs() <= NEAR);
+
+        // Walking into its side.
+        let mut player_box = standing_at(-2.0, 0.0);
+        player_box.move_by(Vector3::new(0.0, 0.8, 0.0));
+        let movement =
+            collision_manager.move_player(&mut player_box, Vector3::new(2.0, 0.0, 0.0), 0.0);
+        assert_eq!(movement.displacement, Vector3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn steps_up_low_ledges_only() {
+        let stair = block([1.0, 0.0, -5.0], [2.0, 0.1, 10.0]);
+        let wall = block([3.0, 0.0, -5.0], [1.0, 0.3, 10.0]);
+        let collision_manager = CollisionManager::new(vec![floor(), stair, wall]);
+        let velocity = Vector3::new(0.8, -0.005, 0.0);
+
+        let mut player_box = standing_at(0.5, 0.0);
+        collision_manager.move_player(&mut player_box, velocity, 0.0);
+        assert!(player_box.bottom_right.x <= 1.0);
+
+        let mut player_box = standing_at(0.5, 0.0);
+        let movement =
+            collision_manager.move_player(&mut player_box, velocity, PlayerBody::STEP_HEIGHT);
+        assert!(movement.on_ground);
+        assert!((movement.displacement.x - 0.8).abs() < 1e-6);
+        assert!((feet(&player_box) - 0.1).abs() <= NEAR);
+
+        for _ in 0..5 {
+            collision_manager.move_player(&mut player_box, velocity, PlayerBody::STEP_HEIGHT);
+        }
+        assert!(player_box.bottom_right.x <= 3.0);
+        assert!((feet(&player_box) - 0.1).abs() <= NEAR);
+    }
+
+    proptest! {
+        #[test]
+        fn overlaps_match_brute_force(
+            map_boxes in prop::collection::vec(map_box(), 0..150),
+            region in map_box(),
+        ) {
+            let collision_manager = CollisionManager::new(map_boxes.clone());
+            let found: Vec<_> = collision_manager
+                .overlapping(&region)
+                .map(|map_box| (map_box.top_left, map_box.bottom_right))
+                .collect();
+            let expected: Vec<_> = map_boxes
+                .iter()
+                .filter(|map_box| region.is_colliding_with(map_box))
+                .map(|map_box| (map_box.top_left, map_box.bottom_right))
+                .collect();
+            prop_assert_eq!(found, expected);
+        }
+
+        #[test]
+        fn segment_hits_match_brute_force(
+            map_boxes in prop::collection::vec(map_box(), 0..150),
+            start in prop::array::uniform3(-30.0_f32..30.0),
+            delta in vector(40.0),
+        ) {
+            let start = Point3::from(start);
+            let collision_manager = CollisionManager::new(map_boxes.clone());
+            let expected = map_boxes
+                .iter()
+                .filter_map(|map_box| map_box.segment_hit(start, delta))
+                .min_by(f32::total_cmp);
+            prop_assert_eq!(collision_manager.segment_hit(start, delta), expected);
+        }
+
+        #[test]
+        fn moves_match_brute_force(
+            map_boxes in prop::collection::vec(map_box(), 0..150),
+            position in prop::array::uniform3(-20.0_f32..20.0),
+            velocity in vector(2.0),
+            step_height in prop_oneof![Just(0.0), Just(PlayerBody::STEP_HEIGHT)],
+        ) {
+            let collision_manager = CollisionManager::new(map_boxes.clone());
+            let mut moved = PlayerBody::hitbox_at(Point3::from(position));
+            let mut expected = moved.clone();
+            let actual = collision_manager.move_player(&mut moved, velocity, step_height);
+            let every_box: Vec<&BoundingBox> = map_boxes.iter().collect();
+            prop_assert_eq!(
+                actual,
+                CollisionManager::move_among(&every_box, &mut expected, velocity, step_height)
+            );
+            prop_assert_eq!(moved.top_left, expected.top_left);
+            prop_assert_eq!(moved.bottom_right, expected.bottom_right);
+        }
+
+        #[test]
+        fn moves_never_end_inside_the_map(
+            map_boxes in prop::collection::vec(map_box(), 0..150),
+            position in prop::array::uniform3(-20.0_f32..20.0),
+            velocity in vector(2.0),
+            step_height in prop_oneof![Just(0.0), Just(PlayerBody::STEP_HEIGHT)],
+        ) {
+            let mut player_box = PlayerBody::hitbox_at(Point3::from(position));
+            let collision_manager = CollisionManager::new(
+                map_boxes
+                    .into_iter()
+                    .filter(|map_box| !player_box.is_colliding_with(map_box))
+                    .collect(),
+            );
+            collision_manager.move_player(&mut player_box, velocity, step_height);
+            prop_assert!(collision_manager.overlapping(&player_box).next().is_none());
+        }
+    }
+}
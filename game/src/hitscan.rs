@@ -0,0 +1,239 @@
+use std::collections::VecDeque;
+
+use nalgebra::{Point3, Vector3};
+use uuid::Uuid;
+
+use crate::{
+    bounding_box::BoundingBox, collision_manager::CollisionManager, player_body::PlayerBody,
+    world::World,
+};
+
+/// Health taken by one hitscan hit.
+pub const DAMAGE: u8 = 25;
+/// Furthest a hitscan shot reaches.
+pub const RANGE: f32 = 100.0;
+/// Shortest time between two shots of the same player.
+pub const COOLDOWN_MILLIS: u128 = 250;
+
+/// Where every player's hitbox was over the last moments, so shots can be
+/// checked against what the shooter saw rather than where players are now.
+#[derive(Debug, Default)]
+pub struct HitboxHistory {
+    frames: VecDeque<HitboxFrame>,
+}
+
+#[derive(Debug, Clone)]
+struct HitboxFrame {
+    time: u128,
+    hitboxes: Vec<(Uuid, BoundingBox)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub player_id: Uuid,
+    pub point: Point3<f32>,
+}
+
+impl HitboxHistory {
+    /// Shots are never rewound further than this, however old their view.
+    pub const MAX_REWIND_MILLIS: u128 = 250;
+
+    /// Remembers the hitboxes in `world` at `time` (server unix millis).
+    pub fn record(&mut self, time: u128, world: &World) {
+        self.frames.push_back(HitboxFrame {
+            time,
+            hitboxes: world
+                .players
+                .iter()
+                .map(|(player_id, body)| (*player_id, PlayerBody::hitbox_at(body.position)))
+                .collect(),
+        });
+        while self
+            .frames
+            .front()
+            .is_some_and(|frame| frame.time + Self::MAX_REWIND_MILLIS < time)
+        {
+            self.frames.pop_front();
+        }
+    }
+
+    /// The hitboxes as they were at `time`, blended between the recorded
+    /// frames around it. Times outside the history use its oldest or newest frame.
+    pub fn rewind(&self, time: u128) -> Vec<(Uuid, BoundingBox)> {
+        let Some(after) = self.frames.iter().position(|frame| frame.time >= time) else {
+            return self
+                .frames
+                .back()
+                .map(|frame| frame.hitboxes.clone())
+                .unwrap_or_default();
+        };
+        let to = &self.frames[after];
+        if after == 0 || to.time == time {
+            return to.hitboxes.clone();
+        }
+        let from = &self.frames[after - 1];
+        let t = (time - from.time) as f32 / (to.time - from.time) as f32;
+        to.hitboxes
+            .iter()
+            .map(|(player_id, to_box)| {
+                let blended = from
+                    .hitboxes
+                    .iter()
+                    .find(|(id, _)| id == player_id)
+                    .map_or_else(|| to_box.clone(), |(_, from_box)| lerp(from_box, to_box, t));
+                (*player_id, blended)
+            })
+            .collect()
+    }
+}
+
+fn lerp(from: &BoundingBox, to: &BoundingBox, t: f32) -> BoundingBox {
+    BoundingBox {
+        top_left: from.top_left + (to.top_left - from.top_left) * t,
+        bottom_right: from.bottom_right + (to.bottom_right - from.bottom_right) * t,
+        collide_on_top: to.collide_on_top,
+    }
+}
+
+/// Follows a shot from `origin` along the unit vector `direction` and returns
+/// the first player hitbox it reaches before the map or `RANGE` stops it.
+/// The shooter's own hitbox is ignored.
+pub fn trace(
+    collision_manager: &CollisionManager,
+    hitboxes: &[(Uuid, BoundingBox)],
+    shooter: Uuid,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<Hit> {
+    let delta = direction * RANGE;
+    let wall = collision_manager.segment_hit(origin, delta).unwrap_or(1.0);
+    hitboxes
+        .iter()
+        .filter(|(player_id, _)| *player_id != shooter)
+        .filter_map(|(player_id, hitbox)| Some((*player_id, hitbox.segment_hit(origin, delta)?)))
+        .filter(|(_, t)| *t < wall)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(player_id, t)| Hit {
+            player_id,
+            point: origin + delta * t,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOOTER: Uuid = Uuid::from_u128(1);
+    const TARGET: Uuid = Uuid::from_u128(2);
+    const EYE: f32 = PlayerBody::HITBOX_HEIGHT;
+
+    fn block(top_left: [f32; 3], bottom_right: [f32; 3]) -> BoundingBox {
+        BoundingBox {
+            top_left: Point3::from(top_left),
+            bottom_right: Point3::from(bottom_right),
+            collide_on_top: false,
+        }
+    }
+
+    fn arena(walls: Vec<BoundingBox>) -> World {
+        let mut map_boxes = vec![block([-20.0, 0.0, -20.0], [20.0, -1.0, 20.0])];
+        map_boxes.extend(walls);
+        let mut world = World::new(CollisionManager::new(map_boxes));
+        world.spawn_player(SHOOTER, Point3::new(0.0, EYE, 0.0));
+        world.spawn_player(TARGET, Point3::new(0.0, EYE, 10.0));
+        world
+    }
+
+    /// Aimed slightly down so the shot passes through the target's body.
+    fn aim_at(x: f32, z: f32) -> Vector3<f32> {
+        Vector3::new(x, -0.1 * (x * x + z * z).sqrt() / 10.0, z).normalize()
+    }
+
+    fn shoot(
+        world: &World,
+        hitboxes: &[(Uuid, BoundingBox)],
+        direction: Vector3<f32>,
+    ) -> Option<Hit> {
+        let origin = world.player(&SHOOTER).unwrap().position;
+        trace(
+            &world.collision_manager,
+            hitboxes,
+            SHOOTER,
+            origin,
+            direction,
+        )
+    }
+
+    fn current(world: &World) -> Vec<(Uuid, BoundingBox)> {
+        let mut history = HitboxHistory::default();
+        history.record(0, world);
+        history.rewind(0)
+    }
+
+    #[test]
+    fn shots_hit_players_in_their_path() {
+        let world = arena(vec![]);
+        let hit = shoot(&world, &current(&world), aim_at(0.0, 1.0)).unwrap();
+        assert_eq!(hit.player_id, TARGET);
+        assert!((hit.point.z - 9.95).abs() < 1e-3);
+        assert_eq!(shoot(&world, &current(&world), aim_at(1.0, 1.0)), None);
+    }
+
+    #[test]
+    fn walls_block_shots() {
+        let world = arena(vec![block([-1.0, 2.0, 5.0], [1.0, 0.0, 5.5])]);
+        assert_eq!(shoot(&world, &current(&world), aim_at(0.0, 1.0)), None);
+    }
+
+    #[test]
+    fn shooter_cannot_hit_itself() {
+        let mut world = arena(vec![]);
+        world.remove_player(&TARGET);
+        assert_eq!(shoot(&world, &current(&world), aim_at(0.0, 1.0)), None);
+    }
+
+    #[test]
+    fn shots_are_checked_against_where_the_shooter_saw_the_target() {
+        let mut world = arena(vec![]);
+        let mut history = HitboxHistory::default();
+        // The target strafes 0.1 to the side every 50 ms tick.
+        for tick in 0..=4 {
+            world.players.get_mut(&TARGET).unwrap().position.x = tick as f32 * 0.1;
+            history.record(1000 + tick * 50, &world);
+        }
+
+        // The shooter aimed where the target was drawn 100 ms ago.
+        let at_old_position = aim_at(0.2, 10.0);
+        let rewound = shoot(&world, &history.rewind(1100), at_old_position);
+        assert_eq!(rewound.map(|hit| hit.player_id), Some(TARGET));
+        assert_eq!(shoot(&world, &history.rewind(1200), at_old_position), None);
+
+        // Halfway between two ticks the hitbox is halfway between them too.
+        assert_eq!(
+            shoot(&world, &history.rewind(1125), aim_at(0.25, 10.0)).map(|hit| hit.player_id),
+            Some(TARGET)
+        );
+    }
+
+    #[test]
+    fn rewinding_is_limited_to_the_window() {
+        let mut world = arena(vec![]);
+        let mut history = HitboxHistory::default();
+        for tick in 0..=20 {
+            world.players.get_mut(&TARGET).unwrap().position.x = tick as f32 * 0.1;
+            history.record(tick * 50, &world);
+        }
+
+        let oldest = 1000 - HitboxHistory::MAX_REWIND_MILLIS;
+        let target_x = |hitboxes: Vec<(Uuid, BoundingBox)>| {
+            let (_, hitbox) = hitboxes.into_iter().find(|(id, _)| *id == TARGET).unwrap();
+            (hitbox.top_left.x + hitbox.bottom_right.x) / 2.0
+        };
+        assert_eq!(
+            target_x(history.rewind(0)),
+            target_x(history.rewind(oldest))
+        );
+        assert!((target_x(history.rewind(oldest)) - 1.5).abs() < 1e-4);
+        assert!((target_x(history.rewind(5000)) - 2.0).abs() < 1e-4);
+    }
+}
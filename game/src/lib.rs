@@ -0,0 +1,12 @@
+pub mod bounding_box;
+pub mod broad_phase;
+pub mod collision_manager;
+pub mod hitscan;
+pub mod map;
+pub mod navigation;
+pub mod pickup;
+pub mod player_body;
+pub mod prediction;
+pub mod projectile;
+pub mod spawn;
+pub mod world;
@@ -0,0 +1,88 @@
+use std::{error::Error, fs};
+
+use nalgebra::Point3;
+use protocol::player_state::Team;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bounding_box::BoundingBox,
+    collision_manager::CollisionManager,
+    pickup::{PickupSpawn, Pickups},
+};
+
+/// Where players appear on maps that define no spawn points.
+pub const DEFAULT_SPAWN: [f32; 3] = [1.0, 0.5, 1.0];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpawnPoint {
+    /// Eye position of a player standing there.
+    pub position: [f32; 3],
+    /// Direction the player faces, in radians.
+    #[serde(default)]
+    pub yaw: f32,
+    /// Only players on this team spawn here; anyone may when unset.
+    #[serde(default)]
+    pub team: Option<Team>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BoundingBoxLoader {
+    pub top_left: [f32; 3],
+    pub bottom_right: [f32; 3],
+    pub collide_on_top: bool,
+}
+
+/// The gameplay-relevant subset of a map file. Rendering data in the same
+/// file is ignored so the server can load maps without any graphics code.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CollisionMapLoader {
+    pub bounding_boxes: Vec<BoundingBoxLoader>,
+    #[serde(default)]
+    pub spawn_points: Vec<SpawnPoint>,
+    #[serde(default)]
+    pub pickups: Vec<PickupSpawn>,
+}
+
+impl CollisionMapLoader {
+    pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
+        let json_data = fs::read_to_string(filename)?;
+        let l: Self = serde_json::from_str(&json_data)?;
+        Ok(l)
+    }
+
+    pub fn load(&self) -> CollisionManager {
+        CollisionManager::new(self.bounding_boxes.iter().map(BoundingBox::from).collect())
+    }
+
+    /// The map's spawn points, or `DEFAULT_SPAWN` if it has none.
+    pub fn spawn_points(&self) -> Vec<SpawnPoint> {
+        if self.spawn_points.is_empty() {
+            return vec![SpawnPoint::default()];
+        }
+        self.spawn_points.clone()
+    }
+
+    pub fn pickups(&self) -> Pickups {
+        Pickups::new(self.pickups.clone())
+    }
+}
+
+impl Default for SpawnPoint {
+    fn default() -> Self {
+        Self {
+            position: DEFAULT_SPAWN,
+            yaw: 0.0,
+            team: None,
+        }
+    }
+}
+
+impl From<&BoundingBoxLoader> for BoundingBox {
+    fn from(bounding_box: &BoundingBoxLoader) -> Self {
+        BoundingBox {
+            top_left: Point3::from(bounding_box.top_left),
+            bottom_right: Point3::from(bounding_box.bottom_right),
+            collide_on_top: bounding_box.collide_on_top,
+        }
+    }
+}
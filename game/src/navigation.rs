@@ -0,0 +1,477 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::{error::Error, fs, io, path::Path, path::PathBuf};
+
+use nalgebra::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bounding_box::BoundingBox, collision_manager::CollisionManager, player_body::PlayerBody,
+};
+
+/// How far a player gets from where it stands, worked out from the same
+/// constants `PlayerBody::step` moves with.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Reach {
+    /// Room kept free around a standing player, which also keeps it this
+    /// far from any edge it could fall off.
+    pub clearance: f32,
+    /// Room needed above the feet.
+    pub height: f32,
+    /// Highest ledge a jump gets the feet onto.
+    pub climb: f32,
+    /// Widest gap a running jump clears.
+    pub gap: f32,
+    /// Furthest a player may drop.
+    pub drop: f32,
+}
+
+/// Places a player can stand, laid out as a grid on the top of every map
+/// box and linked where one can walk, jump or drop between them. Any map
+/// that can be played on can be navigated without extra data, and a graph
+/// can be saved next to its map to skip building it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NavGraph {
+    reach: Option<Reach>,
+    /// Feet position of a player standing at each node.
+    nodes: Vec<[f32; 3]>,
+    /// Links leaving each node.
+    edges: Vec<Vec<Edge>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Edge {
+    pub to: usize,
+    pub cost: f32,
+    /// Whether the way takes a jump, up a ledge or over a gap.
+    pub jump: bool,
+}
+
+/// A node waiting to be expanded by `NavGraph::path`, most promising first.
+#[derive(PartialEq)]
+struct Frontier {
+    /// Cost so far plus the straight-line distance left.
+    estimate: f32,
+    node: usize,
+}
+
+impl Default for Reach {
+    /// The reach of a player jumping a sub-step at a time, the way
+    /// `PlayerBody::step` moves it.
+    fn default() -> Self {
+        let dt = PlayerBody::SUBSTEP_DT;
+        let mut velocity = PlayerBody::JUMP_SPEED;
+        let (mut height, mut apex, mut airtime) = (0.0_f32, 0.0_f32, 0.0);
+        loop {
+            velocity -= PlayerBody::GRAVITY * dt;
+            height += velocity * dt;
+            airtime += dt;
+            apex = apex.max(height);
+            if height <= 0.0 {
+                break;
+            }
+        }
+        Self {
+            clearance: PlayerBody::HITBOX_WIDTH / 2.0 + 0.15,
+            height: PlayerBody::HITBOX_HEIGHT,
+            // Leave room to be over the ledge before falling back below it,
+            // and to take off short of the gap's edge.
+            climb: apex * 0.8,
+            gap: PlayerBody::MOVE_SPEED * airtime * 0.5,
+            drop: 1.5,
+        }
+    }
+}
+
+impl NavGraph {
+    /// Distance between neighbouring nodes on one box top.
+    pub const SPACING: f32 = 0.5;
+    /// Walkable neighbours further apart than this are not linked directly.
+    const LINK_RADIUS: f32 = Self::SPACING * 1.5;
+
+    pub fn build(collision_manager: &CollisionManager, reach: Reach) -> Self {
+        let nodes: Vec<[f32; 3]> = collision_manager
+            .map_boxes()
+            .iter()
+            .flat_map(|map_box| Self::sample_top(map_box, reach))
+            .filter(|node| Self::has_room(collision_manager, *node, reach))
+            .map(Into::into)
+            .collect();
+        let mut graph = Self {
+            reach: Some(reach),
+            edges: vec![vec![]; nodes.len()],
+            nodes,
+        };
+        graph.link(collision_manager, reach);
+        graph
+    }
+
+    /// Where the graph for the map in `map_file` is saved,
+    /// `maps/map_1.json` becoming `maps/map_1.nav.json`.
+    pub fn file_for_map(map_file: &Path) -> PathBuf {
+        map_file.with_extension("nav.json")
+    }
+
+    pub fn from_file(filename: &Path) -> Result<Self, Box<dyn Error>> {
+        let json_data = fs::read_to_string(filename)?;
+        let graph: Self = serde_json::from_str(&json_data)?;
+        if graph.edges.len() != graph.nodes.len()
+            || graph
+                .edges
+                .iter()
+                .flatten()
+                .any(|edge| edge.to >= graph.nodes.len())
+        {
+            return Err("edges do not match the nodes".into());
+        }
+        Ok(graph)
+    }
+
+    pub fn write(&self, filename: &Path) -> io::Result<()> {
+        let json = serde_json::to_string(self).map_err(io::Error::other)?;
+        fs::write(filename, json)
+    }
+
+    /// What the graph was built for, unless it is empty.
+    pub fn reach(&self) -> Option<Reach> {
+        self.reach
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Feet position of a player standing on `node`.
+    pub fn node(&self, node: usize) -> Point3<f32> {
+        Point3::from(self.nodes[node])
+    }
+
+    pub fn edges(&self, node: usize) -> &[Edge] {
+        &self.edges[node]
+    }
+
+    /// The link from `from` to `to`, if they are linked.
+    pub fn edge(&self, from: usize, to: usize) -> Option<&Edge> {
+        self.edges[from].iter().find(|edge| edge.to == to)
+    }
+
+    /// The node closest to `point`, if there are any.
+    pub fn nearest(&self, point: Point3<f32>) -> Option<usize> {
+        (0..self.nodes.len()).min_by(|a, b| {
+            let distance = |node: &usize| (self.node(*node) - point).norm_squared();
+            distance(a).total_cmp(&distance(b))
+        })
+    }
+
+    /// The cheapest chain of nodes leading from `from` to `to`, both
+    /// included, or `None` if `to` cannot be reached. Searched with A*,
+    /// guided by the straight-line distance, which no chain beats.
+    pub fn path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let goal = self.node(to);
+        let mut best = vec![f32::INFINITY; self.nodes.len()];
+        let mut previous = vec![usize::MAX; self.nodes.len()];
+        let mut frontier = BinaryHeap::new();
+        best[from] = 0.0;
+        frontier.push(Frontier {
+            estimate: (goal - self.node(from)).norm(),
+            node: from,
+        });
+        while let Some(Frontier { estimate, node }) = frontier.pop() {
+            if node == to {
+                let mut path = vec![to];
+                while let Some(&last) = path.last()
+                    && last != from
+                {
+                    path.push(previous[last]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if estimate > best[node] + (goal - self.node(node)).norm() {
+                continue;
+            }
+            for edge in &self.edges[node] {
+                let cost = best[node] + edge.cost;
+                if cost < best[edge.to] {
+                    best[edge.to] = cost;
+                    previous[edge.to] = node;
+                    frontier.push(Frontier {
+                        estimate: cost + (goal - self.node(edge.to)).norm(),
+                        node: edge.to,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Every link as its two ends, a little above the ground so it can be
+    /// drawn over the map, and whether it takes a jump. Links both ways
+    /// come once.
+    pub fn debug_lines(&self) -> impl Iterator<Item = (Point3<f32>, Point3<f32>, bool)> + '_ {
+        let lift = Vector3::new(0.0, 0.02, 0.0);
+        self.edges
+            .iter()
+            .enumerate()
+            .flat_map(move |(from, edges)| {
+                edges
+                    .iter()
+                    .filter(move |edge| edge.to > from || self.edge(edge.to, from).is_none())
+                    .map(move |edge| (self.node(from) + lift, self.node(edge.to) + lift, edge.jump))
+            })
+    }
+
+    /// Evenly spaced points on the top of `map_box`, at least the reach's
+    /// clearance in from its sides.
+    fn sample_top(map_box: &BoundingBox, reach: Reach) -> Vec<Point3<f32>> {
+        let axis = |min: f32, max: f32| -> Vec<f32> {
+            let (min, max) = (min + reach.clearance, max - reach.clearance);
+            if max < min {
+                return vec![];
+            }
+            let steps = ((max - min) / Self::SPACING).floor();
+            let offset = (max - min - steps * Self::SPACING) / 2.0;
+            (0..=steps as usize)
+                .map(|step| min + offset + step as f32 * Self::SPACING)
+                .collect()
+        };
+        let y = map_box.top_left.y;
+        let zs = axis(map_box.top_left.z, map_box.bottom_right.z);
+        axis(map_box.top_left.x, map_box.bottom_right.x)
+            .into_iter()
+            .flat_map(|x| zs.iter().map(move |&z| Point3::new(x, y, z)))
+            .collect()
+    }
+
+    /// Whether a player fits standing at `feet` with clearance to spare.
+    fn has_room(collision_manager: &CollisionManager, feet: Point3<f32>, reach: Reach) -> bool {
+        let room = BoundingBox {
+            top_left: Point3::new(
+                feet.x - reach.clearance,
+                feet.y + reach.height,
+                feet.z - reach.clearance,
+            ),
+            bottom_right: Point3::new(
+                feet.x + reach.clearance,
+                feet.y + 0.01,
+                feet.z + reach.clearance,
+            ),
+            collide_on_top: false,
+        };
+        collision_manager.overlapping(&room).next().is_none()
+    }
+
+    /// Links every pair of nearby nodes a player can move between. Nodes
+    /// are bucketed by position so only neighbouring buckets are compared.
+    fn link(&mut self, collision_manager: &CollisionManager, reach: Reach) {
+        let cell = |point: &[f32; 3]| {
+            (
+                (point[0] / Self::SPACING).floor() as i32,
+                (point[2] / Self::SPACING).floor() as i32,
+            )
+        };
+        let mut buckets: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (node, point) in self.nodes.iter().enumerate() {
+            buckets.entry(cell(point)).or_default().push(node);
+        }
+        let radius = Self::LINK_RADIUS.max(reach.gap);
+        let cells = (radius / Self::SPACING).ceil() as i32;
+        for a in 0..self.nodes.len() {
+            let (cx, cz) = cell(&self.nodes[a]);
+            for dx in -cells..=cells {
+                for dz in -cells..=cells {
+                    let Some(bucket) = buckets.get(&(cx + dx, cz + dz)) else {
+                        continue;
+                    };
+                    for &b in bucket {
+                        if a == b {
+                            continue;
+                        }
+                        if let Some(edge) = self.link_between(collision_manager, reach, a, b) {
+                            self.edges[a].push(edge);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// How a player at node `a` gets to node `b` in a straight line, if it
+    /// can. Neighbours with ground between them are walked to, or jumped
+    /// to if higher; nodes further apart are only linked across a gap.
+    fn link_between(
+        &self,
+        collision_manager: &CollisionManager,
+        reach: Reach,
+        a: usize,
+        b: usize,
+    ) -> Option<Edge> {
+        let (from, to) = (self.node(a), self.node(b));
+        let horizontal = Vector3::new(to.x - from.x, 0.0, to.z - from.z);
+        let distance = horizontal.norm();
+        let rise = to.y - from.y;
+        if distance > Self::LINK_RADIUS.max(reach.gap) || rise > reach.climb || -rise > reach.drop {
+            return None;
+        }
+        // Go the way at the height of the higher node, at the knees and
+        // just under the head.
+        let top = from.y.max(to.y);
+        let blocked = [0.1, reach.height - 0.05].iter().any(|height| {
+            let start = Point3::new(from.x, top + height, from.z);
+            collision_manager.segment_hit(start, horizontal).is_some()
+        });
+        if blocked {
+            return None;
+        }
+        let middle = Point3::new((from.x + to.x) / 2.0, top + 0.05, (from.z + to.z) / 2.0);
+        let down = Vector3::new(0.0, -(rise.abs() + 0.2), 0.0);
+        let grounded = collision_manager.segment_hit(middle, down).is_some();
+        let jump = match (grounded, distance <= Self::LINK_RADIUS) {
+            (true, true) => rise > PlayerBody::STEP_HEIGHT,
+            // Over a gap, which must be jumped from no lower than the far side.
+            (false, _) if rise <= 0.01 && distance > 2.0 * reach.clearance => true,
+            _ => return None,
+        };
+        Some(Edge {
+            to: b,
+            cost: (to - from).norm(),
+            jump,
+        })
+    }
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    /// Reversed so `BinaryHeap`, a max-heap, pops the lowest estimate first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(top_left: [f32; 3], bottom_right: [f32; 3]) -> BoundingBox {
+        BoundingBox {
+            top_left: Point3::from(top_left),
+            bottom_right: Point3::from(bottom_right),
+            collide_on_top: false,
+        }
+    }
+
+    /// A floor split down the middle by a wall with a way around one end.
+    fn walled_floor() -> CollisionManager {
+        CollisionManager::new(vec![
+            block([-5.0, 0.0, -5.0], [5.0, -1.0, 5.0]),
+            block([-0.5, 2.0, -5.0], [0.5, 0.0, 3.0]),
+        ])
+    }
+
+    #[test]
+    fn reach_follows_the_jump() {
+        let reach = Reach::default();
+        // Close to v² / 2g high and 2v / g long, a little less for stepping.
+        let apex = PlayerBody::JUMP_SPEED.powi(2) / (2.0 * PlayerBody::GRAVITY);
+        assert!(reach.climb <= apex * 0.8 && reach.climb > apex * 0.78);
+        let airtime = 2.0 * PlayerBody::JUMP_SPEED / PlayerBody::GRAVITY;
+        assert!((reach.gap - PlayerBody::MOVE_SPEED * airtime * 0.5).abs() < 0.02);
+        assert!(reach.climb > PlayerBody::STEP_HEIGHT);
+    }
+
+    #[test]
+    fn nodes_stay_on_box_tops_and_clear_of_walls() {
+        let graph = NavGraph::build(&walled_floor(), Reach::default());
+        let clearance = Reach::default().clearance;
+        assert!(!graph.is_empty());
+        for node in 0..graph.len() {
+            let point = graph.node(node);
+            if point.y == 2.0 {
+                let on_top = point.x.abs() <= 0.5 - clearance;
+                assert!(on_top, "node at {point:?} is off the wall's top");
+                continue;
+            }
+            assert_eq!(point.y, 0.0);
+            assert!(point.x.abs() <= 5.0 - clearance);
+            assert!(point.z.abs() <= 5.0 - clearance);
+            let in_wall = point.x.abs() < 0.5 + clearance && point.z < 3.0 + clearance;
+            assert!(!in_wall, "node at {point:?} is against the wall");
+        }
+    }
+
+    #[test]
+    fn paths_lead_around_walls() {
+        let graph = NavGraph::build(&walled_floor(), Reach::default());
+        let from = graph.nearest(Point3::new(-3.0, 0.0, -3.0)).unwrap();
+        let to = graph.nearest(Point3::new(3.0, 0.0, -3.0)).unwrap();
+        let path = graph.path(from, to).unwrap();
+        assert_eq!(path.first(), Some(&from));
+        assert_eq!(path.last(), Some(&to));
+        assert!(path.iter().any(|node| graph.node(*node).z > 3.0));
+        for step in path.windows(2) {
+            let edge = graph.edge(step[0], step[1]).unwrap();
+            assert!(!edge.jump);
+            assert!(edge.cost <= NavGraph::LINK_RADIUS);
+        }
+    }
+
+    #[test]
+    fn gaps_are_jumped_and_ledges_only_dropped_from() {
+        let graph = NavGraph::build(
+            &CollisionManager::new(vec![
+                block([0.0, 0.0, 0.0], [2.0, -1.0, 2.0]),
+                block([2.2, 0.0, 0.0], [3.7, -1.0, 2.0]),
+                block([6.0, 0.0, 0.0], [8.0, -1.0, 2.0]),
+                block([0.0, 1.0, 2.0], [2.0, 0.0, 4.0]),
+            ]),
+            Reach::default(),
+        );
+        let floor = graph.nearest(Point3::new(1.0, 0.0, 1.0)).unwrap();
+        let across_gap = graph.nearest(Point3::new(3.0, 0.0, 1.0)).unwrap();
+        let too_far = graph.nearest(Point3::new(7.0, 0.0, 1.0)).unwrap();
+        let ledge = graph.nearest(Point3::new(1.0, 1.0, 3.0)).unwrap();
+        assert_eq!(graph.node(ledge).y, 1.0);
+
+        let path = graph.path(floor, across_gap).unwrap();
+        let jumps = path
+            .windows(2)
+            .filter(|step| graph.edge(step[0], step[1]).unwrap().jump)
+            .count();
+        assert_eq!(jumps, 1);
+        assert!(graph.path(floor, too_far).is_none());
+        assert!(graph.path(ledge, floor).is_some());
+        assert!(graph.path(floor, ledge).is_none());
+    }
+
+    #[test]
+    fn graphs_are_saved_next_to_their_map() {
+        let graph = NavGraph::build(&walled_floor(), Reach::default());
+        let dir = std::env::temp_dir().join(format!("mood-nav-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = NavGraph::file_for_map(&dir.join("map_1.json"));
+        assert_eq!(file, dir.join("map_1.nav.json"));
+
+        graph.write(&file).unwrap();
+        let loaded = NavGraph::from_file(&file).unwrap();
+        fs::write(&file, r#"{"reach":null,"nodes":[],"edges":[[]]}"#).unwrap();
+        let mismatched = NavGraph::from_file(&file);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded, graph);
+        assert!(mismatched.is_err());
+    }
+}
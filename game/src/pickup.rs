@@ -0,0 +1,190 @@
+use nalgebra::{Point3, Vector3};
+use protocol::command::Weapon;
+use protocol::player_state::PlayerState;
+use serde::{Deserialize, Serialize};
+
+use crate::{bounding_box::BoundingBox, player_body::PlayerBody};
+
+/// Half the side of the cube a player has to touch to take a pickup.
+pub const HALF_EXTENT: f32 = 0.25;
+pub const HEALTH_AMOUNT: u8 = 25;
+pub const ARMOR_AMOUNT: u8 = 50;
+/// Rockets in an ammo box, and that come loaded in a picked up launcher.
+pub const ROCKETS_AMOUNT: u8 = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PickupKind {
+    Health,
+    Armor,
+    Rockets,
+    RocketLauncher,
+}
+
+/// A pickup as placed in a map file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PickupSpawn {
+    pub kind: PickupKind,
+    /// Centre of the pickup.
+    pub position: [f32; 3],
+    /// Seconds after being taken before it comes back.
+    pub respawn_secs: f32,
+}
+
+/// Every pickup in a map and how long each taken one is gone for. Pickups
+/// are identified by their index in the map file.
+#[derive(Debug, Clone, Default)]
+pub struct Pickups {
+    spawns: Vec<PickupSpawn>,
+    /// Seconds until each pickup is back, `None` while it is available.
+    respawn_in: Vec<Option<f32>>,
+}
+
+impl PickupKind {
+    /// Gives `state` what this pickup holds. Returns `false` and leaves the
+    /// pickup where it is if the player has no use for it.
+    pub fn apply(self, state: &mut PlayerState) -> bool {
+        let top_up = |value: &mut u8, amount: u8, max: u8| {
+            if *value >= max {
+                return false;
+            }
+            *value = value.saturating_add(amount).min(max);
+            true
+        };
+        match self {
+            Self::Health => top_up(&mut state.health, HEALTH_AMOUNT, PlayerState::MAX_HEALTH),
+            Self::Armor => top_up(&mut state.armor, ARMOR_AMOUNT, PlayerState::MAX_ARMOR),
+            Self::Rockets => top_up(&mut state.rockets, ROCKETS_AMOUNT, PlayerState::MAX_ROCKETS),
+            Self::RocketLauncher => {
+                let new_weapon = !state.has_weapon(Weapon::RocketLauncher);
+                state.give_weapon(Weapon::RocketLauncher);
+                top_up(&mut state.rockets, ROCKETS_AMOUNT, PlayerState::MAX_ROCKETS) || new_weapon
+            }
+        }
+    }
+}
+
+impl PickupSpawn {
+    pub fn bounding_box(&self) -> BoundingBox {
+        let center = Point3::from(self.position);
+        let extent = Vector3::new(HALF_EXTENT, -HALF_EXTENT, HALF_EXTENT);
+        BoundingBox {
+            top_left: center - extent,
+            bottom_right: center + extent,
+            collide_on_top: false,
+        }
+    }
+}
+
+impl Pickups {
+    pub fn new(spawns: Vec<PickupSpawn>) -> Self {
+        let respawn_in = vec![None; spawns.len()];
+        Self { spawns, respawn_in }
+    }
+
+    pub fn spawns(&self) -> &[PickupSpawn] {
+        &self.spawns
+    }
+
+    pub fn is_available(&self, pickup: usize) -> bool {
+        self.respawn_in.get(pickup).is_some_and(Option::is_none)
+    }
+
+    /// Counts the taken pickups down by `dt` seconds and returns the ones
+    /// that came back.
+    pub fn update(&mut self, dt: f32) -> Vec<usize> {
+        let mut respawned = vec![];
+        for (pickup, respawn_in) in self.respawn_in.iter_mut().enumerate() {
+            let Some(seconds) = respawn_in else {
+                continue;
+            };
+            *seconds -= dt;
+            if *seconds <= 0.0 {
+                *respawn_in = None;
+                respawned.push(pickup);
+            }
+        }
+        respawned
+    }
+
+    /// Hands every available pickup the body touches to `state`, if it has
+    /// a use for it, and returns the ones taken.
+    pub fn collect(&mut self, body: &PlayerBody, state: &mut PlayerState) -> Vec<usize> {
+        let mut taken = vec![];
+        for (pickup, spawn) in self.spawns.iter().enumerate() {
+            if self.respawn_in[pickup].is_none()
+                && body.hitbox.is_colliding_with(&spawn.bounding_box())
+                && spawn.kind.apply(state)
+            {
+                self.respawn_in[pickup] = Some(spawn.respawn_secs);
+                taken.push(pickup);
+            }
+        }
+        taken
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pickups(kinds: &[PickupKind]) -> Pickups {
+        Pickups::new(
+            kinds
+                .iter()
+                .enumerate()
+                .map(|(i, kind)| PickupSpawn {
+                    kind: *kind,
+                    position: [i as f32 * 3.0, HALF_EXTENT, 0.0],
+                    respawn_secs: 1.0,
+                })
+                .collect(),
+        )
+    }
+
+    fn body_at(x: f32) -> PlayerBody {
+        PlayerBody::new(Point3::new(x, PlayerBody::HITBOX_HEIGHT, 0.0))
+    }
+
+    #[test]
+    fn touched_pickups_are_taken_and_come_back() {
+        let mut pickups = pickups(&[PickupKind::Armor, PickupKind::Health]);
+        let mut state = PlayerState::default();
+        assert_eq!(pickups.collect(&body_at(0.2), &mut state), [0]);
+        assert_eq!(state.armor, ARMOR_AMOUNT);
+        assert!(!pickups.is_available(0));
+        assert!(pickups.is_available(1));
+        assert!(pickups.collect(&body_at(0.2), &mut state).is_empty());
+
+        assert!(pickups.update(0.6).is_empty());
+        assert_eq!(pickups.update(0.6), [0]);
+        assert!(pickups.is_available(0));
+    }
+
+    #[test]
+    fn pickups_nobody_needs_stay_put() {
+        let mut pickups = pickups(&[PickupKind::Health]);
+        let mut state = PlayerState::default();
+        assert!(pickups.collect(&body_at(0.0), &mut state).is_empty());
+        assert!(pickups.is_available(0));
+
+        state.health = 90;
+        assert_eq!(pickups.collect(&body_at(0.0), &mut state), [0]);
+        assert_eq!(state.health, PlayerState::MAX_HEALTH);
+    }
+
+    #[test]
+    fn the_launcher_comes_loaded() {
+        let mut state = PlayerState {
+            rockets: PlayerState::MAX_ROCKETS,
+            ..PlayerState::default()
+        };
+        assert!(PickupKind::RocketLauncher.apply(&mut state));
+        assert!(state.has_weapon(Weapon::RocketLauncher));
+        assert!(!PickupKind::RocketLauncher.apply(&mut state));
+
+        state.rockets = 0;
+        assert!(PickupKind::RocketLauncher.apply(&mut state));
+        assert_eq!(state.rockets, ROCKETS_AMOUNT);
+    }
+}
@@ -0,0 +1,380 @@
+use nalgebra::{Point3, Vector3};
+use protocol::{input::PlayerInput, player_state::PlayerState};
+
+use crate::{bounding_box::BoundingBox, collision_manager::CollisionManager};
+
+/// The simulated part of a player. Both the client and the server step this
+/// with the same inputs so their results agree.
+#[derive(Debug, Clone)]
+pub struct PlayerBody {
+    /// Eye position, which is also the top of the hitbox.
+    pub position: Point3<f32>,
+    /// Eye position before the last sub-step, to draw the body between the
+    /// two.
+    pub previous_position: Point3<f32>,
+    /// Units per second.
+    pub velocity: Vector3<f32>,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub hitbox: BoundingBox,
+    pub is_on_ground: bool,
+}
+
+impl PlayerBody {
+    /// Seconds simulated at a time, however long the frames or ticks are.
+    pub const SUBSTEP_DT: f32 = 1.0 / 120.0;
+    /// Units per second squared.
+    pub const GRAVITY: f32 = 6.0;
+    /// Top speed running, or steering in the air, gets a player to.
+    pub const MOVE_SPEED: f32 = 2.0;
+    /// Upward speed a jump starts with.
+    pub const JUMP_SPEED: f32 = 2.2;
+    /// Share of `MOVE_SPEED` gained per second while running.
+    pub const GROUND_ACCELERATION: f32 = 10.0;
+    /// Share of `MOVE_SPEED` gained per second while steering in the air.
+    pub const AIR_ACCELERATION: f32 = 1.0;
+    /// Share of its speed a player on the ground loses per second.
+    pub const FRICTION: f32 = 6.0;
+    /// Slower players lose speed as if moving this fast, so they come to a
+    /// stop instead of creeping.
+    pub const STOP_SPEED: f32 = 0.6;
+    pub const HITBOX_WIDTH: f32 = 0.1;
+    pub const HITBOX_HEIGHT: f32 = 0.5;
+    /// Highest ledge a player walks up without jumping, e.g. a stair.
+    pub const STEP_HEIGHT: f32 = 0.15;
+    pub const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+    /// Longest stretch of time a single input may simulate, so a long frame
+    /// is not stepped all at once. The server also limits how much time a
+    /// player's inputs add up to, which is what stops clients that send
+    /// more of them.
+    pub const MAX_STEP_DT: f32 = 0.1;
+
+    pub fn new(position: Point3<f32>) -> Self {
+        Self {
+            position,
+            previous_position: position,
+            velocity: Vector3::zeros(),
+            pitch: 0.0,
+            yaw: 0.0,
+            hitbox: Self::hitbox_at(position),
+            is_on_ground: false,
+        }
+    }
+
+    pub fn hitbox_at(position: Point3<f32>) -> BoundingBox {
+        BoundingBox {
+            top_left: Point3::new(
+                position.x - (Self::HITBOX_WIDTH / 2.0),
+                position.y,
+                position.z - (Self::HITBOX_WIDTH / 2.0),
+            ),
+            bottom_right: Point3::new(
+                position.x + (Self::HITBOX_WIDTH / 2.0),
+                position.y - Self::HITBOX_HEIGHT,
+                position.z + (Self::HITBOX_WIDTH / 2.0),
+            ),
+            collide_on_top: false,
+        }
+    }
+
+    /// Unit vectors pointing forward and to the left on the horizontal plane.
+    pub fn forward_and_left(yaw: f32) -> (Vector3<f32>, Vector3<f32>) {
+        let forward = Vector3::new(yaw.sin(), 0.0, yaw.cos());
+        let left = Vector3::new(yaw.cos(), 0.0, -yaw.sin());
+        (forward, left)
+    }
+
+    /// Unit vector the player is looking along.
+    pub fn aim_direction(pitch: f32, yaw: f32) -> Vector3<f32> {
+        Vector3::new(
+            pitch.cos() * yaw.sin(),
+            pitch.sin(),
+            pitch.cos() * yaw.cos(),
+        )
+    }
+
+    /// Where to draw the body when `alpha` of a sub-step has passed since
+    /// the last one.
+    pub fn interpolated_position(&self, alpha: f32) -> Point3<f32> {
+        self.previous_position + (self.position - self.previous_position) * alpha.clamp(0.0, 1.0)
+    }
+
+    /// Whole sub-steps an input's `dt` stands for, at most `MAX_STEP_DT`'s
+    /// worth.
+    pub fn substeps(dt: f32) -> u32 {
+        if dt.is_nan() || dt <= 0.0 {
+            return 0;
+        }
+        (dt.min(Self::MAX_STEP_DT) / Self::SUBSTEP_DT).round() as u32
+    }
+
+    /// Advances the body by one input, a sub-step at a time, resolving
+    /// collisions against the map.
+    pub fn step(&mut self, input: &PlayerInput, collision_manager: &CollisionManager) {
+        self.pitch = input.pitch.clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+        self.yaw = input.yaw;
+        for _ in 0..Self::substeps(input.dt) {
+            self.substep(input, collision_manager);
+        }
+    }
+
+    fn substep(&mut self, input: &PlayerInput, collision_manager: &CollisionManager) {
+        let dt = Self::SUBSTEP_DT;
+        self.previous_position = self.position;
+        // Rebuilt every step so a body restored from a replicated state
+        // collides exactly like the one that produced it.
+        self.hitbox = Self::hitbox_at(self.position);
+
+        let (forward, left) = Self::forward_and_left(self.yaw);
+        let mut wish = Vector3::zeros();
+        if input.forward {
+            wish += forward;
+        }
+        if input.backward {
+            wish -= forward;
+        }
+        if input.left {
+            wish += left;
+        }
+        if input.right {
+            wish -= left;
+        }
+        let wish = wish.try_normalize(0.0);
+
+        let acceleration = if self.is_on_ground {
+            self.apply_friction(dt);
+            Self::GROUND_ACCELERATION
+        } else {
+            Self::AIR_ACCELERATION
+        };
+        if let Some(wish) = wish {
+            // Speed is only added up to `MOVE_SPEED` along the wished
+            // direction, so anything faster, e.g. from an explosion, is kept.
+            let missing = Self::MOVE_SPEED - self.velocity.dot(&wish);
+            let added = (acceleration * Self::MOVE_SPEED * dt).min(missing);
+            if added > 0.0 {
+                self.velocity += wish * added;
+            }
+        }
+        if input.jump && self.is_on_ground {
+            self.velocity.y = Self::JUMP_SPEED;
+        }
+        self.velocity.y -= Self::GRAVITY * dt;
+
+        // Only walking steps up ledges, so a jump is never cut short.
+        let step_height = if self.is_on_ground && self.velocity.y <= 0.0 {
+            Self::STEP_HEIGHT
+        } else {
+            0.0
+        };
+        let movement =
+            collision_manager.move_player(&mut self.hitbox, self.velocity * dt, step_height);
+        self.is_on_ground = movement.on_ground;
+        self.velocity = movement.displacement / dt;
+        if self.is_on_ground {
+            self.velocity.y = 0.0;
+        }
+        self.position += movement.displacement;
+    }
+
+    fn apply_friction(&mut self, dt: f32) {
+        let speed = self.velocity.xz().norm();
+        if speed <= 0.0 {
+            return;
+        }
+        let drop = speed.max(Self::STOP_SPEED) * Self::FRICTION * dt;
+        let scale = (speed - drop).max(0.0) / speed;
+        self.velocity.x *= scale;
+        self.velocity.z *= scale;
+    }
+
+    /// Copies the simulated values into the replicated player state.
+    pub fn write_state(&self, state: &mut PlayerState) {
+        state.update(
+            self.position.into(),
+            self.velocity.into(),
+            self.pitch,
+            self.yaw,
+        );
+        state.is_on_ground = self.is_on_ground;
+    }
+
+    /// Overwrites the simulation with a replicated state, e.g. from the server.
+    pub fn read_state(&mut self, state: &PlayerState) {
+        self.position = Point3::from(state.position);
+        self.previous_position = self.position;
+        self.velocity = Vector3::from(state.velocity);
+        self.pitch = state.pitch;
+        self.yaw = state.yaw;
+        self.hitbox = Self::hitbox_at(self.position);
+        self.is_on_ground = state.is_on_ground;
+    }
+}
+
+/// Turns frame times into whole sub-steps, carrying what is left over into
+/// the next frame, so bodies move the same at any frame rate.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StepClock {
+    leftover: f32,
+}
+
+impl StepClock {
+    /// Adds `dt` seconds and returns how much of the time so far to simulate
+    /// now, always whole sub-steps.
+    pub fn advance(&mut self, dt: f32) -> f32 {
+        if dt.is_finite() {
+            self.leftover += dt.max(0.0);
+        }
+        let substeps = (self.leftover / PlayerBody::SUBSTEP_DT).floor();
+        self.leftover = (self.leftover - substeps * PlayerBody::SUBSTEP_DT).max(0.0);
+        substeps * PlayerBody::SUBSTEP_DT
+    }
+
+    /// Share of a sub-step that has passed since the last one simulated,
+    /// for drawing bodies in between.
+    pub fn alpha(&self) -> f32 {
+        (self.leftover / PlayerBody::SUBSTEP_DT).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(min: [f32; 3], max: [f32; 3]) -> BoundingBox {
+        BoundingBox {
+            top_left: Point3::new(min[0], max[1], min[2]),
+            bottom_right: Point3::new(max[0], min[1], max[2]),
+            collide_on_top: false,
+        }
+    }
+
+    /// A floor with a stair and a wall ahead of a player starting at the
+    /// origin and facing +z.
+    fn course() -> CollisionManager {
+        CollisionManager::new(vec![
+            block([-10.0, -1.0, -10.0], [10.0, 0.0, 10.0]),
+            block([-10.0, 0.0, 1.0], [10.0, 0.1, 10.0]),
+            block([-10.0, 0.0, 3.0], [10.0, 3.0, 4.0]),
+        ])
+    }
+
+    fn spawn() -> PlayerBody {
+        PlayerBody::new(Point3::new(0.0, PlayerBody::HITBOX_HEIGHT, 0.0))
+    }
+
+    /// Plays `frames` of the given lengths holding `input`, and returns the
+    /// sub-steps simulated so far and the body after every frame.
+    fn play(input: PlayerInput, frames: impl IntoIterator<Item = f32>) -> Vec<(u32, PlayerBody)> {
+        let collision_manager = course();
+        let mut body = spawn();
+        let mut clock = StepClock::default();
+        let mut substeps = 0;
+        frames
+            .into_iter()
+            .map(|frame| {
+                let dt = clock.advance(frame);
+                body.step(&PlayerInput { dt, ..input }, &collision_manager);
+                substeps += PlayerBody::substeps(dt);
+                (substeps, body.clone())
+            })
+            .collect()
+    }
+
+    fn at_fps(fps: f32, seconds: f32) -> impl Iterator<Item = f32> {
+        std::iter::repeat_n(1.0 / fps, (fps * seconds) as usize)
+    }
+
+    #[test]
+    fn trajectories_match_across_frame_rates() {
+        // Running and hopping up the stair into the wall.
+        let input = PlayerInput {
+            forward: true,
+            jump: true,
+            ..PlayerInput::default()
+        };
+        // Frames of 2 to 40 milliseconds in no particular order.
+        let uneven = (0..300).map(|frame| (frame * 7919 % 39 + 2) as f32 / 1000.0);
+        let runs = [
+            play(input, at_fps(30.0, 3.0)),
+            play(input, at_fps(60.0, 3.0)),
+            play(input, at_fps(144.0, 3.0)),
+            play(input, at_fps(300.0, 3.0)),
+            play(input, uneven),
+        ];
+
+        let reference = &runs[3];
+        let highest = reference
+            .iter()
+            .map(|(_, body)| body.position.y)
+            .fold(f32::MIN, f32::max);
+        assert!(highest > PlayerBody::HITBOX_HEIGHT + 0.3);
+        let (_, last) = reference.last().unwrap();
+        assert!(last.position.z > 2.5 && last.position.z < 3.0);
+
+        for run in &runs[..3].iter().chain(&runs[4..]).collect::<Vec<_>>() {
+            let mut compared = 0;
+            for (substeps, body) in run.iter() {
+                let Some((_, expected)) = reference.iter().find(|(at, _)| at == substeps) else {
+                    continue;
+                };
+                assert_eq!(body.position, expected.position);
+                assert_eq!(body.velocity, expected.velocity);
+                assert_eq!(body.is_on_ground, expected.is_on_ground);
+                compared += 1;
+            }
+            assert!(compared > 50);
+        }
+    }
+
+    #[test]
+    fn running_tops_out_and_friction_stops() {
+        let collision_manager = course();
+        let mut body = spawn();
+        let run = PlayerInput {
+            left: true,
+            dt: 0.05,
+            ..PlayerInput::default()
+        };
+        for _ in 0..20 {
+            body.step(&run, &collision_manager);
+        }
+        assert!((body.velocity.norm() - PlayerBody::MOVE_SPEED).abs() < 1e-3);
+
+        let stand = PlayerInput {
+            dt: 0.05,
+            ..PlayerInput::default()
+        };
+        for _ in 0..10 {
+            body.step(&stand, &collision_manager);
+        }
+        assert_eq!(body.velocity, Vector3::zeros());
+        assert!(body.is_on_ground);
+    }
+
+    #[test]
+    fn clock_keeps_up_with_time() {
+        let mut clock = StepClock::default();
+        let simulated: f32 = (0..1000).map(|_| clock.advance(1.0 / 144.0)).sum();
+        assert!((simulated - 1000.0 / 144.0).abs() <= PlayerBody::SUBSTEP_DT);
+        assert!((0.0..=1.0).contains(&clock.alpha()));
+        assert_eq!(clock.advance(f32::NAN), 0.0);
+    }
+
+    #[test]
+    fn drawn_between_sub_steps() {
+        let collision_manager = course();
+        let mut body = spawn();
+        let run = PlayerInput {
+            left: true,
+            dt: PlayerBody::SUBSTEP_DT,
+            ..PlayerInput::default()
+        };
+        body.step(&run, &collision_manager);
+        body.step(&run, &collision_manager);
+        assert_eq!(body.interpolated_position(0.0), body.previous_position);
+        assert_eq!(body.interpolated_position(1.0), body.position);
+        let halfway = body.interpolated_position(0.5);
+        assert!(halfway.x > body.previous_position.x && halfway.x < body.position.x);
+    }
+}
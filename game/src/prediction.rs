@@ -0,0 +1,305 @@
+use std::collections::VecDeque;
+
+use protocol::{input::PlayerInput, player_state::PlayerState};
+
+use crate::{collision_manager::CollisionManager, player_body::PlayerBody};
+
+/// Client-side prediction for the local player. Inputs are applied as soon as
+/// they are produced and kept until the server confirms it simulated them.
+#[derive(Debug, Default)]
+pub struct Prediction {
+    pending: VecDeque<PlayerInput>,
+    last_acknowledged: u32,
+}
+
+impl Prediction {
+    /// Oldest inputs are dropped past this, e.g. while no server is answering.
+    pub const MAX_PENDING: usize = 256;
+
+    /// Applies `input` to `body` immediately and remembers it for replaying.
+    pub fn predict(
+        &mut self,
+        body: &mut PlayerBody,
+        input: PlayerInput,
+        collision_manager: &CollisionManager,
+    ) {
+        body.step(&input, collision_manager);
+        if self.pending.len() == Self::MAX_PENDING {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(input);
+    }
+
+    /// Rewinds `body` to the server's state and replays every input the
+    /// server had not processed yet. Snapshots older than one already
+    /// reconciled against are ignored since their inputs are gone.
+    pub fn reconcile(
+        &mut self,
+        body: &mut PlayerBody,
+        server_state: &PlayerState,
+        last_input_sequence: u32,
+        collision_manager: &CollisionManager,
+    ) {
+        if last_input_sequence < self.last_acknowledged {
+            return;
+        }
+        self.last_acknowledged = last_input_sequence;
+        while self
+            .pending
+            .front()
+            .is_some_and(|input| input.sequence <= last_input_sequence)
+        {
+            self.pending.pop_front();
+        }
+
+        body.read_state(server_state);
+        for input in &self.pending {
+            body.step(input, collision_manager);
+        }
+    }
+
+    /// Drops every pending input and takes the server's state as it is, for
+    /// when the server moved the player somewhere new, e.g. on respawn.
+    pub fn reset(
+        &mut self,
+        body: &mut PlayerBody,
+        server_state: &PlayerState,
+        last_input_sequence: u32,
+    ) {
+        self.pending.clear();
+        self.last_acknowledged = self.last_acknowledged.max(last_input_sequence);
+        body.read_state(server_state);
+    }
+
+    pub fn pending_inputs(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Point3, Vector3};
+
+    use super::*;
+    use crate::bounding_box::BoundingBox;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn block(top_left: [f32; 3], bottom_right: [f32; 3]) -> BoundingBox {
+        BoundingBox {
+            top_left: Point3::from(top_left),
+            bottom_right: Point3::from(bottom_right),
+            collide_on_top: false,
+        }
+    }
+
+    fn arena() -> CollisionManager {
+        CollisionManager::new(vec![block([-10.0, 0.0, -10.0], [10.0, -1.0, 10.0])])
+    }
+
+    /// Same arena with a wall across the path that only the server knows about.
+    fn walled_arena() -> CollisionManager {
+        let mut map_boxes = arena().map_boxes().to_vec();
+        map_boxes.push(block([-10.0, 2.0, 1.0], [10.0, 0.0, 1.5]));
+        CollisionManager::new(map_boxes)
+    }
+
+    /// Delivers whatever is sent after a fixed number of ticks.
+    struct LatencyLink<T> {
+        delay: usize,
+        in_flight: VecDeque<(usize, T)>,
+    }
+
+    impl<T> LatencyLink<T> {
+        fn new(delay: usize) -> Self {
+            Self {
+                delay,
+                in_flight: VecDeque::new(),
+            }
+        }
+
+        fn send(&mut self, now: usize, message: T) {
+            self.in_flight.push_back((now + self.delay, message));
+        }
+
+        fn receive(&mut self, now: usize) -> Vec<T> {
+            let mut arrived = vec![];
+            while self.in_flight.front().is_some_and(|(at, _)| *at <= now) {
+                arrived.push(self.in_flight.pop_front().unwrap().1);
+            }
+            arrived
+        }
+    }
+
+    /// Walk forward, jump, turn while strafing, then stand still.
+    fn recorded_inputs() -> Vec<PlayerInput> {
+        (0..150)
+            .map(|frame| PlayerInput {
+                sequence: frame as u32 + 1,
+                forward: frame < 60,
+                left: (60..100).contains(&frame),
+                jump: frame == 20 || frame == 70,
+                yaw: if frame < 40 { 0.0 } else { 0.5 },
+                pitch: -0.2,
+                dt: DT,
+                ..PlayerInput::default()
+            })
+            .collect()
+    }
+
+    struct Outcome {
+        client: PlayerBody,
+        server: PlayerBody,
+        largest_correction: f32,
+        pending_inputs: usize,
+    }
+
+    /// Plays `inputs` through a client and a server separated by `latency`
+    /// ticks in each direction, reconciling every snapshot that arrives.
+    fn simulate(
+        inputs: &[PlayerInput],
+        latency: usize,
+        client_map: &CollisionManager,
+        server_map: &CollisionManager,
+    ) -> Outcome {
+        let spawn = Point3::new(0.0, PlayerBody::HITBOX_HEIGHT, 0.0);
+        let mut client = PlayerBody::new(spawn);
+        let mut server = PlayerBody::new(spawn);
+        let mut server_last_input = 0;
+        let mut prediction = Prediction::default();
+        let mut uplink = LatencyLink::new(latency);
+        let mut downlink = LatencyLink::new(latency);
+        let mut largest_correction: f32 = 0.0;
+
+        let ticks = inputs.len() + 2 * latency + 1;
+        for now in 0..ticks {
+            for (state, last_input) in downlink.receive(now) {
+                let predicted = client.position;
+                prediction.reconcile(&mut client, &state, last_input, client_map);
+                largest_correction = largest_correction.max((predicted - client.position).norm());
+            }
+            if let Some(input) = inputs.get(now) {
+                prediction.predict(&mut client, *input, client_map);
+                uplink.send(now, *input);
+            }
+
+            for input in uplink.receive(now) {
+                server.step(&input, server_map);
+                server_last_input = input.sequence;
+            }
+            let mut state = PlayerState::default();
+            server.write_state(&mut state);
+            downlink.send(now, (state, server_last_input));
+        }
+
+        Outcome {
+            client,
+            server,
+            largest_correction,
+            pending_inputs: prediction.pending_inputs(),
+        }
+    }
+
+    #[test]
+    fn prediction_matches_server_without_corrections() {
+        for latency in [0, 1, 5, 12] {
+            let outcome = simulate(&recorded_inputs(), latency, &arena(), &arena());
+            assert_eq!(outcome.client.position, outcome.server.position);
+            assert!(
+                outcome.largest_correction < 1e-6,
+                "latency {latency} corrected by {}",
+                outcome.largest_correction
+            );
+            assert_eq!(outcome.pending_inputs, 0);
+        }
+    }
+
+    #[test]
+    fn client_converges_to_server_when_they_disagree() {
+        let outcome = simulate(&recorded_inputs(), 6, &arena(), &walled_arena());
+        assert!(outcome.largest_correction > 1e-3);
+        assert_eq!(outcome.client.position, outcome.server.position);
+        assert!(outcome.server.position.z < 1.0);
+    }
+
+    #[test]
+    fn replays_unacknowledged_inputs_on_top_of_server_state() {
+        let map = arena();
+        let inputs = recorded_inputs();
+        let mut client = PlayerBody::new(Point3::new(0.0, PlayerBody::HITBOX_HEIGHT, 0.0));
+        let mut server = client.clone();
+        let mut prediction = Prediction::default();
+        for input in &inputs[..30] {
+            prediction.predict(&mut client, *input, &map);
+        }
+        for input in &inputs[..10] {
+            server.step(input, &map);
+        }
+        let mut state = PlayerState::default();
+        server.write_state(&mut state);
+
+        let predicted = client.position;
+        prediction.reconcile(&mut client, &state, inputs[9].sequence, &map);
+        assert_eq!(prediction.pending_inputs(), 20);
+        assert_eq!(client.position, predicted);
+    }
+
+    #[test]
+    fn stale_snapshots_are_ignored() {
+        let map = arena();
+        let inputs = recorded_inputs();
+        let mut client = PlayerBody::new(Point3::new(0.0, PlayerBody::HITBOX_HEIGHT, 0.0));
+        let mut prediction = Prediction::default();
+        for input in &inputs[..20] {
+            prediction.predict(&mut client, *input, &map);
+        }
+        let predicted = client.position;
+        let stale = PlayerState {
+            position: [5.0, 5.0, 5.0],
+            velocity: Vector3::zeros().into(),
+            ..PlayerState::default()
+        };
+        prediction.reconcile(&mut client, &stale, 10, &map);
+        let after_newer = client.position;
+        prediction.reconcile(&mut client, &stale, 5, &map);
+        assert_ne!(after_newer, predicted);
+        assert_eq!(client.position, after_newer);
+    }
+
+    #[test]
+    fn reset_discards_pending_inputs() {
+        let map = arena();
+        let inputs = recorded_inputs();
+        let mut client = PlayerBody::new(Point3::new(0.0, PlayerBody::HITBOX_HEIGHT, 0.0));
+        let mut prediction = Prediction::default();
+        for input in &inputs[..20] {
+            prediction.predict(&mut client, *input, &map);
+        }
+        let spawn = PlayerState {
+            position: [3.0, PlayerBody::HITBOX_HEIGHT, -2.0],
+            velocity: [0.0; 3],
+            yaw: 1.5,
+            ..PlayerState::default()
+        };
+        prediction.reset(&mut client, &spawn, 5);
+        assert_eq!(prediction.pending_inputs(), 0);
+        assert_eq!(client.position, Point3::from(spawn.position));
+        assert_eq!(client.yaw, spawn.yaw);
+    }
+
+    #[test]
+    fn pending_inputs_are_bounded() {
+        let map = arena();
+        let mut body = PlayerBody::new(Point3::new(0.0, PlayerBody::HITBOX_HEIGHT, 0.0));
+        let mut prediction = Prediction::default();
+        for sequence in 1..=(Prediction::MAX_PENDING as u32 * 2) {
+            let input = PlayerInput {
+                sequence,
+                dt: DT,
+                ..PlayerInput::default()
+            };
+            prediction.predict(&mut body, input, &map);
+        }
+        assert_eq!(prediction.pending_inputs(), Prediction::MAX_PENDING);
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use nalgebra::{Point3, Vector3};
+use uuid::Uuid;
+
+use crate::player_body::PlayerBody;
+
+/// Units per second a rocket leaves the launcher with.
+pub const SPEED: f32 = 10.0;
+/// Shortest time between two rockets from the same player.
+pub const COOLDOWN_MILLIS: u128 = 800;
+/// Projectiles that hit nothing are removed after this many seconds.
+pub const LIFETIME: f32 = 5.0;
+/// Players further than this from an explosion are not affected.
+pub const SPLASH_RADIUS: f32 = 1.5;
+/// Damage at the centre of an explosion, falling off linearly to the edge.
+pub const MAX_DAMAGE: u8 = 80;
+/// Speed added away from the centre of an explosion, with the same falloff.
+pub const KNOCKBACK: f32 = 4.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projectile {
+    pub id: u32,
+    pub owner: Uuid,
+    pub position: Point3<f32>,
+    /// Units per second.
+    pub velocity: Vector3<f32>,
+    /// Seconds left before it disappears on its own.
+    pub lifetime: f32,
+}
+
+/// Damage an explosion did to one player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplashHit {
+    pub owner: Uuid,
+    pub target: Uuid,
+    pub damage: u8,
+}
+
+/// Pushes every player within `SPLASH_RADIUS` of `center` away from it and
+/// returns the damage each one takes. The owner is included, so players can
+/// hurt (and launch) themselves.
+pub fn explode(
+    owner: Uuid,
+    center: Point3<f32>,
+    players: &mut BTreeMap<Uuid, PlayerBody>,
+) -> Vec<SplashHit> {
+    let mut hits = vec![];
+    for (player_id, body) in players.iter_mut() {
+        let hitbox = PlayerBody::hitbox_at(body.position);
+        let distance = (hitbox.closest_point(center) - center).norm();
+        if distance >= SPLASH_RADIUS {
+            continue;
+        }
+        let falloff = 1.0 - distance / SPLASH_RADIUS;
+        let body_center = nalgebra::center(&hitbox.top_left, &hitbox.bottom_right);
+        let away = (body_center - center)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::y);
+        body.velocity += away * KNOCKBACK * falloff;
+
+        let damage = (MAX_DAMAGE as f32 * falloff).round() as u8;
+        if damage > 0 {
+            hits.push(SplashHit {
+                owner,
+                target: *player_id,
+                damage,
+            });
+        }
+    }
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn players_at(positions: &[[f32; 3]]) -> BTreeMap<Uuid, PlayerBody> {
+        positions
+            .iter()
+            .enumerate()
+            .map(|(i, position)| {
+                (
+                    Uuid::from_u128(i as u128 + 1),
+                    PlayerBody::new(Point3::from(*position)),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn damage_falls_off_with_distance() {
+        let mut players = players_at(&[[0.0, 0.5, 0.0], [0.0, 0.5, 1.0], [0.0, 0.5, 5.0]]);
+        let owner = Uuid::from_u128(9);
+        let hits = explode(owner, Point3::new(0.0, 0.25, 0.0), &mut players);
+
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].target, Uuid::from_u128(1));
+        assert_eq!(hits[0].damage, MAX_DAMAGE);
+        assert_eq!(hits[1].target, Uuid::from_u128(2));
+        assert!(hits[1].damage > 0 && hits[1].damage < MAX_DAMAGE);
+        assert!(hits.iter().all(|hit| hit.owner == owner));
+    }
+
+    #[test]
+    fn explosions_push_players_away() {
+        let mut players = players_at(&[[1.0, 0.5, 0.0], [0.0, 1.0, 0.0]]);
+        explode(Uuid::nil(), Point3::new(0.0, 0.25, 0.0), &mut players);
+
+        let beside = &players[&Uuid::from_u128(1)];
+        assert!(beside.velocity.x > 0.0);
+        let above = &players[&Uuid::from_u128(2)];
+        assert!(above.velocity.y > 0.0);
+        assert_eq!(above.velocity.x, 0.0);
+    }
+}
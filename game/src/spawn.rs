@@ -0,0 +1,164 @@
+use nalgebra::Point3;
+use protocol::player_state::Team;
+use uuid::Uuid;
+
+use crate::{map::SpawnPoint, player_body::PlayerBody, world::World};
+
+/// Players closer than this to a spawn point make it occupied.
+pub const MIN_CLEARANCE: f32 = 1.0;
+/// Cosine of half the angle an enemy is considered to see, about 140 degrees.
+const VIEW_COS: f32 = 0.35;
+
+/// How good a spawn point is right now, from worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Safety {
+    Occupied,
+    Seen,
+    Safe,
+}
+
+/// Picks where a player on `team` should appear. Points of another team are
+/// skipped. Points that are free and out of every enemy's sight are
+/// preferred, and among equally safe points the one furthest from the
+/// nearest enemy wins. Every player in `world` blocks a point; those for which
+/// `is_enemy` holds also watch for it.
+pub fn choose_spawn<'a>(
+    spawn_points: &'a [SpawnPoint],
+    world: &World,
+    team: Option<Team>,
+    is_enemy: impl Fn(&Uuid) -> bool,
+) -> Option<&'a SpawnPoint> {
+    let enemies: Vec<&PlayerBody> = world
+        .players
+        .iter()
+        .filter(|(player_id, _)| is_enemy(player_id))
+        .map(|(_, body)| body)
+        .collect();
+    let allowed =
+        |point: &&SpawnPoint| team.is_none() || point.team.is_none() || point.team == team;
+    let candidates: Vec<&SpawnPoint> = if spawn_points.iter().any(|point| allowed(&point)) {
+        spawn_points.iter().filter(allowed).collect()
+    } else {
+        spawn_points.iter().collect()
+    };
+
+    candidates
+        .into_iter()
+        .map(|point| {
+            let position = Point3::from(point.position);
+            let safety = if world
+                .players
+                .values()
+                .any(|body| (body.position - position).norm() < MIN_CLEARANCE)
+            {
+                Safety::Occupied
+            } else if enemies.iter().any(|enemy| sees(world, enemy, position)) {
+                Safety::Seen
+            } else {
+                Safety::Safe
+            };
+            let nearest_enemy = enemies
+                .iter()
+                .map(|enemy| (enemy.position - position).norm())
+                .fold(f32::INFINITY, f32::min);
+            (point, safety, nearest_enemy)
+        })
+        .reduce(|best, candidate| {
+            if (candidate.1, candidate.2) > (best.1, best.2) {
+                candidate
+            } else {
+                best
+            }
+        })
+        .map(|(point, _, _)| point)
+}
+
+/// Whether `body` faces `position` with no part of the map in between.
+fn sees(world: &World, body: &PlayerBody, position: Point3<f32>) -> bool {
+    let to_position = position - body.position;
+    let Some(direction) = to_position.try_normalize(f32::EPSILON) else {
+        return true;
+    };
+    PlayerBody::aim_direction(body.pitch, body.yaw).dot(&direction) >= VIEW_COS
+        && world
+            .collision_manager
+            .segment_hit(body.position, to_position)
+            .is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::{bounding_box::BoundingBox, collision_manager::CollisionManager};
+
+    fn point(x: f32, z: f32, team: Option<Team>) -> SpawnPoint {
+        SpawnPoint {
+            position: [x, PlayerBody::HITBOX_HEIGHT, z],
+            yaw: 0.0,
+            team,
+        }
+    }
+
+    /// Open floor with a wall across x = 5 from z = 5 onwards.
+    fn arena() -> World {
+        World::new(CollisionManager::new(vec![BoundingBox {
+            top_left: Point3::new(4.5, 3.0, 5.0),
+            bottom_right: Point3::new(5.5, -1.0, 20.0),
+            collide_on_top: false,
+        }]))
+    }
+
+    fn add_player(world: &mut World, id: u128, x: f32, z: f32, yaw: f32) -> Uuid {
+        let player_id = Uuid::from_u128(id);
+        world
+            .spawn_player(player_id, Point3::new(x, PlayerBody::HITBOX_HEIGHT, z))
+            .yaw = yaw;
+        player_id
+    }
+
+    #[test]
+    fn occupied_points_are_avoided() {
+        let mut world = arena();
+        add_player(&mut world, 1, 0.0, 0.2, PI);
+        let points = [point(0.0, 0.0, None), point(0.0, -3.0, None)];
+        let chosen = choose_spawn(&points, &world, None, |_| false).unwrap();
+        assert_eq!(chosen, &points[1]);
+    }
+
+    #[test]
+    fn points_in_view_of_enemies_are_avoided() {
+        let mut world = arena();
+        // Looks along +z, past the open side of the wall.
+        add_player(&mut world, 1, 0.0, 0.0, 0.0);
+        let in_view = point(0.0, 8.0, None);
+        let behind_wall = point(8.0, 8.0, None);
+        let behind_enemy = point(0.0, -2.0, None);
+        let points = [in_view.clone(), behind_wall.clone(), behind_enemy.clone()];
+
+        let chosen = choose_spawn(&points, &world, None, |_| true).unwrap();
+        assert_eq!(chosen, &behind_wall);
+
+        // A teammate looking the same way does not matter.
+        let chosen = choose_spawn(&points, &world, None, |_| false).unwrap();
+        assert_eq!(chosen, &in_view);
+        let points = [in_view, behind_enemy.clone()];
+        let chosen = choose_spawn(&points, &world, None, |_| true).unwrap();
+        assert_eq!(chosen, &behind_enemy);
+    }
+
+    #[test]
+    fn teams_spawn_at_their_own_points() {
+        let world = arena();
+        let points = [
+            point(0.0, 0.0, Some(Team::Red)),
+            point(3.0, 0.0, Some(Team::Blue)),
+        ];
+        let chosen = choose_spawn(&points, &world, Some(Team::Blue), |_| true).unwrap();
+        assert_eq!(chosen, &points[1]);
+        let chosen = choose_spawn(&points, &world, Some(Team::Red), |_| true).unwrap();
+        assert_eq!(chosen, &points[0]);
+        assert!(choose_spawn(&[], &world, None, |_| true).is_none());
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+use nalgebra::{Point3, Vector3};
+use protocol::input::PlayerInput;
+use uuid::Uuid;
+
+use crate::{
+    collision_manager::CollisionManager,
+    player_body::PlayerBody,
+    projectile::{self, Projectile, SplashHit},
+};
+
+/// Everything the simulation acts on. The server, the client's prediction,
+/// bots and tests all step the same `World`, so they agree on the outcome.
+#[derive(Debug)]
+pub struct World {
+    pub collision_manager: CollisionManager,
+    pub players: BTreeMap<Uuid, PlayerBody>,
+    pub projectiles: Vec<Projectile>,
+    next_projectile_id: u32,
+}
+
+impl World {
+    pub fn new(collision_manager: CollisionManager) -> Self {
+        Self {
+            collision_manager,
+            players: BTreeMap::new(),
+            projectiles: vec![],
+            next_projectile_id: 0,
+        }
+    }
+
+    pub fn spawn_player(&mut self, player_id: Uuid, position: Point3<f32>) -> &mut PlayerBody {
+        self.players
+            .entry(player_id)
+            .insert_entry(PlayerBody::new(position))
+            .into_mut()
+    }
+
+    pub fn remove_player(&mut self, player_id: &Uuid) -> Option<PlayerBody> {
+        self.players.remove(player_id)
+    }
+
+    pub fn player(&self, player_id: &Uuid) -> Option<&PlayerBody> {
+        self.players.get(player_id)
+    }
+
+    pub fn spawn_projectile(
+        &mut self,
+        owner: Uuid,
+        position: Point3<f32>,
+        velocity: Vector3<f32>,
+    ) -> u32 {
+        let id = self.next_projectile_id;
+        self.next_projectile_id = self.next_projectile_id.wrapping_add(1);
+        self.projectiles.push(Projectile {
+            id,
+            owner,
+            position,
+            velocity,
+            lifetime: projectile::LIFETIME,
+        });
+        id
+    }
+
+    /// Advances the world by `dt` seconds. Each input moves its player by the
+    /// input's own `dt`, in order, exactly as the client predicted it; inputs
+    /// for players that are not in the world are ignored. Returns the damage
+    /// done by projectiles that exploded during the step.
+    pub fn step(&mut self, dt: f32, inputs: &[(Uuid, PlayerInput)]) -> Vec<SplashHit> {
+        for (player_id, input) in inputs {
+            if let Some(body) = self.players.get_mut(player_id) {
+                body.step(input, &self.collision_manager);
+            }
+        }
+
+        let mut hits = vec![];
+        let collision_manager = &self.collision_manager;
+        let players = &mut self.players;
+        self.projectiles.retain_mut(|projectile| {
+            let delta = projectile.velocity * dt;
+            let player_hit = players
+                .iter()
+                .filter(|(player_id, _)| **player_id != projectile.owner)
+                .filter_map(|(_, body)| {
+                    PlayerBody::hitbox_at(body.position).segment_hit(projectile.position, delta)
+                })
+                .min_by(f32::total_cmp);
+            let impact = collision_manager
+                .segment_hit(projectile.position, delta)
+                .into_iter()
+                .chain(player_hit)
+                .min_by(f32::total_cmp);
+            if let Some(t) = impact {
+                let center = projectile.position + delta * t;
+                hits.extend(projectile::explode(projectile.owner, center, players));
+                return false;
+            }
+            projectile.position += delta;
+            projectile.lifetime -= dt;
+            projectile.lifetime > 0.0
+        });
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bounding_box::BoundingBox;
+
+    fn walled_arena() -> World {
+        let block = |top_left: [f32; 3], bottom_right: [f32; 3]| BoundingBox {
+            top_left: Point3::from(top_left),
+            bottom_right: Point3::from(bottom_right),
+            collide_on_top: false,
+        };
+        World::new(CollisionManager::new(vec![
+            block([-10.0, 0.0, -10.0], [10.0, -1.0, 10.0]),
+            block([-10.0, 3.0, 5.0], [10.0, 0.0, 6.0]),
+        ]))
+    }
+
+    #[test]
+    fn inputs_move_only_their_own_player() {
+        let mut world = walled_arena();
+        let walker = Uuid::from_u128(1);
+        let idle = Uuid::from_u128(2);
+        world.spawn_player(walker, Point3::new(0.0, PlayerBody::HITBOX_HEIGHT, 0.0));
+        world.spawn_player(idle, Point3::new(2.0, PlayerBody::HITBOX_HEIGHT, 0.0));
+        let forward = PlayerInput {
+            sequence: 1,
+            forward: true,
+            dt: 0.05,
+            ..PlayerInput::default()
+        };
+
+        world.step(0.05, &[(walker, forward), (Uuid::from_u128(3), forward)]);
+        assert!(world.player(&walker).unwrap().position.z > 0.0);
+        assert_eq!(world.player(&idle).unwrap().position.z, 0.0);
+    }
+
+    #[test]
+    fn projectiles_stop_at_walls_and_expire() {
+        let mut world = walled_arena();
+        let owner = Uuid::from_u128(1);
+        let into_wall = world.spawn_projectile(
+            owner,
+            Point3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 20.0),
+        );
+        let into_sky = world.spawn_projectile(
+            owner,
+            Point3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 20.0, 0.0),
+        );
+
+        for _ in 0..5 {
+            world.step(0.05, &[]);
+        }
+        let ids: Vec<u32> = world.projectiles.iter().map(|p| p.id).collect();
+        assert_eq!(ids, [into_sky]);
+        assert_ne!(into_wall, into_sky);
+
+        for _ in 0..100 {
+            world.step(0.05, &[]);
+        }
+        assert!(world.projectiles.is_empty());
+    }
+
+    #[test]
+    fn projectiles_explode_on_players_but_not_their_owner() {
+        let mut world = walled_arena();
+        let owner = Uuid::from_u128(1);
+        let target = Uuid::from_u128(2);
+        world.spawn_player(owner, Point3::new(0.0, PlayerBody::HITBOX_HEIGHT, 0.0));
+        world.spawn_player(target, Point3::new(0.0, PlayerBody::HITBOX_HEIGHT, 3.0));
+        world.spawn_projectile(
+            owner,
+            Point3::new(0.0, 0.25, 0.0),
+            Vector3::new(0.0, 0.0, projectile::SPEED),
+        );
+
+        let mut hits = vec![];
+        for _ in 0..10 {
+            hits.extend(world.step(0.05, &[]));
+        }
+        assert!(world.projectiles.is_empty());
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].owner, owner);
+        assert_eq!(hits[0].target, target);
+        assert_eq!(hits[0].damage, projectile::MAX_DAMAGE);
+        assert!(world.player(&target).unwrap().velocity.z > 0.0);
+    }
+}
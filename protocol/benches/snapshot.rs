@@ -0,0 +1,74 @@
+use std::hint::black_box;
+
+use bincode::config;
+use criterion::{Criterion, criterion_group, criterion_main};
+use protocol::player_state::PlayerState;
+use protocol::snapshot::{EntityState, Snapshot};
+use uuid::Uuid;
+
+const PLAYERS: u8 = 32;
+const MTU: usize = 1200;
+
+/// Every player running in its own circle, as a busy match would look.
+fn players(tick: u32) -> Vec<PlayerState> {
+    (0..PLAYERS)
+        .map(|slot| {
+            let angle = tick as f32 * 0.05 + slot as f32;
+            PlayerState {
+                player_id: Uuid::from_u128(slot as u128 + 1),
+                position: [angle.cos() * 10.0, 0.5, angle.sin() * 10.0],
+                velocity: [-angle.sin() * 2.0, 0.0, angle.cos() * 2.0],
+                pitch: 0.1,
+                yaw: angle,
+                health: 100,
+                is_on_ground: true,
+                ..PlayerState::default()
+            }
+        })
+        .collect()
+}
+
+fn snapshot(tick: u32) -> Snapshot {
+    Snapshot {
+        tick,
+        entities: players(tick)
+            .iter()
+            .enumerate()
+            .map(|(slot, state)| (slot as u8, EntityState::quantize(state)))
+            .collect(),
+        projectiles: vec![],
+    }
+}
+
+fn bytes_per_tick(c: &mut Criterion) {
+    let legacy = bincode::serde::encode_to_vec(players(1), config::standard()).unwrap();
+    // Sizes are measured without a budget, so neither encoding is cut short.
+    let (full, _) = snapshot(1).encode(None, usize::MAX);
+    let (delta, _) = snapshot(1).encode(Some(&snapshot(0)), usize::MAX);
+    println!(
+        "{PLAYERS} players: legacy {} bytes, full snapshot {} bytes, delta {} bytes",
+        legacy.len(),
+        full.len(),
+        delta.len()
+    );
+
+    let baseline = snapshot(0);
+    let current = snapshot(1);
+    c.bench_function("legacy encode", |b| {
+        let states = players(1);
+        b.iter(|| bincode::serde::encode_to_vec(black_box(&states), config::standard()))
+    });
+    c.bench_function("full snapshot encode", |b| {
+        b.iter(|| black_box(&current).encode(None, usize::MAX))
+    });
+    c.bench_function("delta snapshot encode", |b| {
+        b.iter(|| black_box(&current).encode(Some(&baseline), MTU))
+    });
+    c.bench_function("delta snapshot decode", |b| {
+        let (bytes, _) = current.encode(Some(&baseline), MTU);
+        b.iter(|| Snapshot::decode(black_box(&bytes), |_| Some(&baseline)))
+    });
+}
+
+criterion_group!(benches, bytes_per_tick);
+criterion_main!(benches);
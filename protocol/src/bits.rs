@@ -0,0 +1,104 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
+/// Packs values into a byte buffer using only as many bits as each one needs.
+#[derive(Debug, Default, Clone)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    bit_len: usize,
+}
+
+/// Reads values written by a [`BitWriter`] back out, in the same order.
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBits;
+
+impl BitWriter {
+    /// Writes the lowest `bits` bits of `value`, most significant first.
+    pub fn write_bits(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            self.push_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.push_bit(value);
+    }
+
+    /// Writes `value` in two's complement; it must fit in `bits` bits.
+    pub fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write_bits(value as u64 & Self::mask(bits), bits);
+    }
+
+    pub fn append(&mut self, other: &BitWriter) {
+        for i in 0..other.bit_len {
+            self.push_bit(other.bytes[i / 8] & (0x80 >> (i % 8)) != 0);
+        }
+    }
+
+    pub fn bit_len(&self) -> usize {
+        self.bit_len
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    fn push_bit(&mut self, bit: bool) {
+        if self.bit_len.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            self.bytes[self.bit_len / 8] |= 0x80 >> (self.bit_len % 8);
+        }
+        self.bit_len += 1;
+    }
+
+    fn mask(bits: u32) -> u64 {
+        if bits >= 64 {
+            u64::MAX
+        } else {
+            (1 << bits) - 1
+        }
+    }
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub fn read_bits(&mut self, bits: u32) -> Result<u64, OutOfBits> {
+        let mut value = 0;
+        for _ in 0..bits {
+            value = (value << 1) | self.read_bool()? as u64;
+        }
+        Ok(value)
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, OutOfBits> {
+        let byte = self.bytes.get(self.position / 8).ok_or(OutOfBits)?;
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+        Ok(bit)
+    }
+
+    /// Reads a two's complement value written with [`BitWriter::write_signed`].
+    pub fn read_signed(&mut self, bits: u32) -> Result<i64, OutOfBits> {
+        let value = self.read_bits(bits)?;
+        let shift = 64 - bits;
+        Ok(((value << shift) as i64) >> shift)
+    }
+}
+
+impl Display for OutOfBits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ran out of bits while decoding")
+    }
+}
+
+impl Error for OutOfBits {}
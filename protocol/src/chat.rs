@@ -0,0 +1,60 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+/// Longest chat line in bytes of UTF-8. A packet full of reliable chat
+/// lines still fits in one datagram.
+pub const MAX_CHAT_LENGTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatChannel {
+    /// Everyone on the server.
+    All,
+    /// The sender's team, or everyone in modes without teams.
+    Team,
+}
+
+impl Display for ChatChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::Team => write!(f, "team"),
+        }
+    }
+}
+
+/// `text` as it may be shown: control characters dropped, surrounding
+/// whitespace trimmed and cut to [`MAX_CHAT_LENGTH`] on a character boundary.
+pub fn sanitize(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len().min(MAX_CHAT_LENGTH));
+    for c in text.trim().chars().filter(|c| !c.is_control()) {
+        if sanitized.len() + c.len_utf8() > MAX_CHAT_LENGTH {
+            break;
+        }
+        sanitized.push(c);
+    }
+    sanitized.trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_characters_and_padding_are_removed() {
+        assert_eq!(sanitize("  gg\n wp\u{7}\t "), "gg wp");
+        assert_eq!(sanitize(" \r\n "), "");
+    }
+
+    #[test]
+    fn long_lines_are_cut_on_a_character_boundary() {
+        let text = "é".repeat(MAX_CHAT_LENGTH);
+        let sanitized = sanitize(&text);
+        assert_eq!(sanitized.len(), MAX_CHAT_LENGTH);
+        assert!(sanitized.chars().all(|c| c == 'é'));
+        assert_eq!(
+            sanitize(&"a".repeat(MAX_CHAT_LENGTH + 1)).len(),
+            MAX_CHAT_LENGTH
+        );
+    }
+}
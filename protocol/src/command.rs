@@ -0,0 +1,365 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::Level;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::chat::ChatChannel;
+use crate::connection::SessionToken;
+use crate::input::PlayerInput;
+use crate::match_state::{GameModeKind, MatchPhase, ScoreEntry};
+use crate::player_state::PlayerState;
+use bincode::config as bconfig;
+use bincode::{config::Configuration, serde as bserde};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Command {
+    pub command_type: CommandType,
+    pub time: u128,
+}
+
+/// Bumped whenever the wire format changes so mismatched builds refuse to talk.
+pub const PROTOCOL_VERSION: u32 = 13;
+/// Largest datagram either side will read; anything sent must fit in this.
+pub const MAX_DATAGRAM_SIZE: usize = 1500;
+/// Most players a server may hold, which clients size their buffers for.
+pub const MAX_PLAYERS: u8 = 32;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CommandType {
+    PlayerJoin {
+        protocol_version: u32,
+        player_name: String,
+    },
+    JoinAccepted {
+        player_id: Uuid,
+        /// Must accompany every later packet from this client.
+        session_token: SessionToken,
+        tick_rate_millis: u64,
+        map: String,
+    },
+    JoinRejected(RejectReason),
+    PlayerLeave,
+    PlayerInput(PlayerInput),
+    Data {
+        player_id: Uuid,
+        /// Sequence of the newest input from the receiving player the server has simulated.
+        last_input_sequence: u32,
+        /// The receiving player at full precision, since it is replayed on top of.
+        local_player: PlayerState,
+        /// Every other player as an encoded [`crate::snapshot::Snapshot`].
+        snapshot: Vec<u8>,
+    },
+    SnapshotAck {
+        tick: u32,
+    },
+    /// A shot along `direction`, fired while the client was drawing other
+    /// players as they were at `view_time` (server unix millis).
+    Fire {
+        weapon: Weapon,
+        direction: [f32; 3],
+        view_time: u128,
+    },
+    Hit {
+        shooter: Uuid,
+        target: Uuid,
+        damage: u8,
+    },
+    Kill {
+        killer: Uuid,
+        victim: Uuid,
+    },
+    MatchState {
+        mode: GameModeKind,
+        phase: MatchPhase,
+        /// Server unix millis at which the phase is over, if it is timed.
+        phase_ends_at: Option<u128>,
+    },
+    /// Adds or replaces the scoreboard line of `ScoreEntry::player_id`.
+    PlayerScore(ScoreEntry),
+    ScoreRemoved {
+        player_id: Uuid,
+    },
+    /// Whether the map's pickup at index `pickup` can be taken right now.
+    /// Every pickup is available until told otherwise.
+    PickupAvailability {
+        pickup: u16,
+        available: bool,
+    },
+    /// A line of chat typed by the sending player, at most
+    /// [`crate::chat::MAX_CHAT_LENGTH`] bytes long.
+    ChatSend {
+        channel: ChatChannel,
+        text: String,
+    },
+    /// Round trip times in millis the server measured to each player, sent
+    /// every so often.
+    PlayerPings(Vec<(Uuid, u16)>),
+    /// A line of chat relayed to everyone on its channel.
+    ChatMessage {
+        sender: Uuid,
+        channel: ChatChannel,
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Weapon {
+    /// Hits instantly along the aim, checked against what the shooter saw.
+    Rifle,
+    /// Launches a projectile that explodes on impact.
+    RocketLauncher,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectReason {
+    ServerFull,
+    VersionMismatch {
+        server_version: u32,
+    },
+    Banned,
+    /// The name was empty once control characters and padding were removed.
+    InvalidName,
+}
+
+/// The current unix time in millis, the clock every timestamp on the wire uses.
+pub fn unix_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+impl Command {
+    const CONFIG: Configuration = bconfig::standard();
+
+    /// Wraps `command_type` in a command stamped with the current unix time in millis.
+    pub fn new(command_type: CommandType) -> Self {
+        Self {
+            command_type,
+            time: unix_millis(),
+        }
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(bserde::encode_to_vec(self, Self::CONFIG)?)
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let command: (Command, _) = bserde::decode_from_slice(data, Self::CONFIG)?;
+        Ok(command.0)
+    }
+}
+
+impl CommandType {
+    pub fn log_level(&self) -> Level {
+        match self {
+            Self::PlayerInput(_) | Self::SnapshotAck { .. } | Self::PlayerPings(_) => Level::Debug,
+            _ => Level::Info,
+        }
+    }
+}
+
+impl Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ServerFull => write!(f, "server is full"),
+            Self::VersionMismatch { server_version } => write!(
+                f,
+                "protocol version mismatch (server {server_version}, client {PROTOCOL_VERSION})"
+            ),
+            Self::Banned => write!(f, "banned from this server"),
+            Self::InvalidName => write!(f, "invalid player name"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player_state::Team;
+
+    fn round_trip(command_type: CommandType) {
+        let command = Command {
+            command_type,
+            time: 1_700_000_000_000,
+        };
+        let bytes = command.serialize().unwrap();
+        let decoded = Command::deserialize(&bytes).unwrap();
+        assert_eq!(command, decoded);
+    }
+
+    #[test]
+    fn player_join_round_trip() {
+        round_trip(CommandType::PlayerJoin {
+            protocol_version: PROTOCOL_VERSION,
+            player_name: String::from("doomguy"),
+        });
+    }
+
+    #[test]
+    fn join_accepted_round_trip() {
+        round_trip(CommandType::JoinAccepted {
+            player_id: Uuid::new_v4(),
+            session_token: 0x0123_4567_89ab_cdef,
+            tick_rate_millis: 50,
+            map: String::from("map_1"),
+        });
+    }
+
+    #[test]
+    fn join_rejected_round_trip() {
+        round_trip(CommandType::JoinRejected(RejectReason::ServerFull));
+        round_trip(CommandType::JoinRejected(RejectReason::VersionMismatch {
+            server_version: PROTOCOL_VERSION + 1,
+        }));
+        round_trip(CommandType::JoinRejected(RejectReason::Banned));
+        round_trip(CommandType::JoinRejected(RejectReason::InvalidName));
+    }
+
+    #[test]
+    fn player_leave_round_trip() {
+        round_trip(CommandType::PlayerLeave);
+    }
+
+    #[test]
+    fn fire_round_trip() {
+        round_trip(CommandType::Fire {
+            weapon: Weapon::Rifle,
+            direction: [0.0, -0.1, 1.0],
+            view_time: 1_700_000_000_000,
+        });
+        round_trip(CommandType::Fire {
+            weapon: Weapon::RocketLauncher,
+            direction: [1.0, 0.0, 0.0],
+            view_time: 0,
+        });
+    }
+
+    #[test]
+    fn hit_and_kill_round_trip() {
+        let shooter = Uuid::new_v4();
+        let target = Uuid::new_v4();
+        round_trip(CommandType::Hit {
+            shooter,
+            target,
+            damage: 25,
+        });
+        round_trip(CommandType::Kill {
+            killer: shooter,
+            victim: target,
+        });
+    }
+
+    #[test]
+    fn match_state_and_scores_round_trip() {
+        round_trip(CommandType::MatchState {
+            mode: GameModeKind::TeamDeathmatch,
+            phase: MatchPhase::Live,
+            phase_ends_at: Some(1_700_000_600_000),
+        });
+        round_trip(CommandType::MatchState {
+            mode: GameModeKind::Deathmatch,
+            phase: MatchPhase::Warmup,
+            phase_ends_at: None,
+        });
+        let player_id = Uuid::new_v4();
+        round_trip(CommandType::PlayerScore(ScoreEntry {
+            frags: 12,
+            deaths: 3,
+            score: 11,
+            ..ScoreEntry::new(player_id, String::from("doomguy"), Some(Team::Blue))
+        }));
+        round_trip(CommandType::ScoreRemoved { player_id });
+        round_trip(CommandType::PlayerPings(vec![
+            (player_id, 48),
+            (Uuid::new_v4(), 0),
+        ]));
+    }
+
+    #[test]
+    fn snapshot_ack_round_trip() {
+        round_trip(CommandType::SnapshotAck { tick: 1234 });
+    }
+
+    #[test]
+    fn player_input_round_trip() {
+        round_trip(CommandType::PlayerInput(PlayerInput {
+            sequence: 42,
+            forward: true,
+            left: true,
+            jump: true,
+            pitch: 0.3,
+            yaw: -1.2,
+            dt: 0.016,
+            ..PlayerInput::default()
+        }));
+    }
+
+    #[test]
+    fn pickup_availability_round_trip() {
+        round_trip(CommandType::PickupAvailability {
+            pickup: 3,
+            available: false,
+        });
+    }
+
+    #[test]
+    fn chat_round_trip() {
+        round_trip(CommandType::ChatSend {
+            channel: ChatChannel::Team,
+            text: String::from("rockets at mid"),
+        });
+        round_trip(CommandType::ChatMessage {
+            sender: Uuid::new_v4(),
+            channel: ChatChannel::All,
+            text: String::from("gg"),
+        });
+    }
+
+    #[test]
+    fn data_round_trip() {
+        let player = PlayerState {
+            position: [4.0, 0.5, 4.0],
+            health: 42,
+            armor: 17,
+            rockets: 3,
+            ..PlayerState::default()
+        };
+        round_trip(CommandType::Data {
+            player_id: player.player_id,
+            last_input_sequence: 1234,
+            local_player: player,
+            snapshot: vec![0xde, 0xad, 0xbe, 0xef],
+        });
+    }
+
+    #[test]
+    fn empty_data_round_trip() {
+        round_trip(CommandType::Data {
+            player_id: Uuid::new_v4(),
+            last_input_sequence: 0,
+            local_player: PlayerState::default(),
+            snapshot: vec![],
+        });
+    }
+
+    #[test]
+    fn truncated_command_is_rejected() {
+        let bytes = Command {
+            command_type: CommandType::PlayerInput(PlayerInput {
+                sequence: 7,
+                forward: true,
+                yaw: 1.0,
+                dt: 0.016,
+                ..PlayerInput::default()
+            }),
+            time: 0,
+        }
+        .serialize()
+        .unwrap();
+        assert!(Command::deserialize(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
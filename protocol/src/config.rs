@@ -0,0 +1,197 @@
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use clap::{Arg, ArgMatches, Command, value_parser};
+use serde::de::DeserializeOwned;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    Invalid {
+        field: &'static str,
+        reason: String,
+    },
+}
+
+/// Settings layered from their defaults, then an optional TOML file, then
+/// command-line flags, and checked once all are in.
+pub trait LayeredConfig: Default + DeserializeOwned {
+    /// The program's flags. `--config` is added to them.
+    fn cli() -> Command;
+
+    /// Overwrites every setting that was given as a flag.
+    fn apply_flags(&mut self, matches: &ArgMatches);
+
+    fn validate(&self) -> Result<(), ConfigError>;
+
+    /// Builds the config from `args` (including the program name), reading
+    /// the file passed with `--config` if there is one. Malformed flags are
+    /// reported by clap, which exits the process.
+    fn from_args<I, T>(args: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::cli()
+            .arg(
+                Arg::new("config")
+                    .long("config")
+                    .short('c')
+                    .value_name("FILE")
+                    .value_parser(value_parser!(PathBuf))
+                    .help("TOML file to read settings from; flags take precedence"),
+            )
+            .get_matches_from(args);
+        let mut config = match matches.get_one::<PathBuf>("config") {
+            Some(path) => read_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_flags(&matches);
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Reads settings from the TOML file at `path`. Settings it leaves out are
+/// up to `T`'s serde defaults.
+pub fn read_file<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let contents = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+        path: path.to_owned(),
+        source,
+    })?;
+    toml::from_str(&contents).map_err(|source| ConfigError::Parse {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Overwrites `field` with the value of the flag `id`, if it was given.
+pub fn set_flag<T: Clone + Send + Sync + 'static>(matches: &ArgMatches, id: &str, field: &mut T) {
+    if let Some(value) = matches.get_one::<T>(id) {
+        *field = value.clone();
+    }
+}
+
+impl ConfigError {
+    pub fn invalid(field: &'static str, reason: impl Into<String>) -> Self {
+        Self::Invalid {
+            field,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read { path, source } => {
+                write!(f, "could not read config {}: {source}", path.display())
+            }
+            Self::Parse { path, source } => {
+                write!(f, "invalid config {}: {source}", path.display())
+            }
+            Self::Invalid { field, reason } => write!(f, "invalid {field}: {reason}"),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    struct Example {
+        port: u16,
+        name: String,
+    }
+
+    impl Default for Example {
+        fn default() -> Self {
+            Self {
+                port: 1,
+                name: String::from("default"),
+            }
+        }
+    }
+
+    impl LayeredConfig for Example {
+        fn cli() -> Command {
+            Command::new("example")
+                .arg(
+                    Arg::new("port")
+                        .long("port")
+                        .value_parser(value_parser!(u16)),
+                )
+                .arg(Arg::new("name").long("name"))
+        }
+
+        fn apply_flags(&mut self, matches: &ArgMatches) {
+            set_flag(matches, "port", &mut self.port);
+            set_flag(matches, "name", &mut self.name);
+        }
+
+        fn validate(&self) -> Result<(), ConfigError> {
+            if self.port == 0 {
+                return Err(ConfigError::invalid("port", "must not be 0"));
+            }
+            Ok(())
+        }
+    }
+
+    /// Writes `contents` to a file only this test uses and returns its path.
+    fn config_file(test: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("mood-{test}-{}.toml", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn flags_override_the_file_and_the_file_the_defaults() {
+        let path = config_file("layers", "port = 2\nname = \"file\"\n");
+        let args = [
+            "example",
+            "--config",
+            path.to_str().unwrap(),
+            "--name",
+            "flag",
+        ];
+        let config = Example::from_args(args);
+        fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+        assert_eq!(config.port, 2);
+        assert_eq!(config.name, "flag");
+
+        assert_eq!(Example::from_args(["example"]).unwrap().name, "default");
+    }
+
+    #[test]
+    fn bad_files_and_values_are_reported() {
+        let path = config_file("unknown", "colour = \"red\"\n");
+        let unknown = Example::from_args(["example", "--config", path.to_str().unwrap()]);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(unknown, Err(ConfigError::Parse { .. })));
+
+        let missing = Example::from_args(["example", "--config", "/nonexistent/mood.toml"]);
+        assert!(matches!(missing, Err(ConfigError::Read { .. })));
+
+        let invalid = Example::from_args(["example", "--port", "0"]).unwrap_err();
+        assert_eq!(invalid.to_string(), "invalid port: must not be 0");
+    }
+}
@@ -0,0 +1,594 @@
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::time::{Duration, Instant};
+
+use bincode::config as bconfig;
+use bincode::{config::Configuration, serde as bserde};
+use serde::{Deserialize, Serialize};
+
+use crate::command::Command;
+
+/// Random id the server hands out on join. It identifies a client across
+/// address changes and keeps others from speaking for it.
+pub type SessionToken = u64;
+
+/// One datagram on the wire. Every packet acknowledges what its sender has
+/// received so far and carries any reliable messages still waiting for an
+/// acknowledgement next to the unreliable ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Packet {
+    /// The sender's session, absent until the server has assigned one.
+    pub session: Option<SessionToken>,
+    pub sequence: u16,
+    /// Newest packet sequence received from the other side, if any.
+    pub ack: Option<u16>,
+    /// Bit `n` is set if packet `ack - n - 1` was received as well.
+    pub ack_bits: u32,
+    /// Reliable messages with the id that orders them.
+    pub reliable: Vec<(u16, Command)>,
+    /// Delivered at most once, in whatever order they arrive.
+    pub unreliable: Vec<Command>,
+}
+
+/// Reliability state for one peer. Unreliable messages are handed through
+/// as they arrive minus duplicates; reliable messages are resent until a
+/// packet carrying them is acknowledged and are delivered exactly once, in
+/// the order they were sent.
+#[derive(Debug, Default)]
+pub struct Connection {
+    session: Option<SessionToken>,
+    local_sequence: u16,
+    remote_sequence: Option<u16>,
+    received_bits: u32,
+    /// When each recently sent packet went out and the reliable ids it carried.
+    sent_packets: VecDeque<(u16, Instant, Vec<u16>)>,
+    /// Smoothed round trip time, from how long packets take to be acknowledged.
+    rtt: Option<Duration>,
+    outgoing: VecDeque<PendingMessage>,
+    next_reliable_id: u16,
+    next_expected_id: u16,
+    incoming: HashMap<u16, Command>,
+}
+
+#[derive(Debug)]
+struct PendingMessage {
+    id: u16,
+    command: Command,
+    /// Bytes the message takes in a packet, id included.
+    size: usize,
+    last_sent: Option<Instant>,
+}
+
+/// A reliable message too large to ever be sent, which would hold back
+/// every message after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OversizedMessage {
+    pub size: usize,
+}
+
+impl Packet {
+    const CONFIG: Configuration = bconfig::standard();
+    /// Most bytes a packet takes besides its messages, with fewer than 251
+    /// of each kind.
+    pub const MAX_HEADER_SIZE: usize = 24;
+
+    pub fn serialize(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(bserde::encode_to_vec(self, Self::CONFIG)?)
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let packet: (Packet, _) = bserde::decode_from_slice(data, Self::CONFIG)?;
+        Ok(packet.0)
+    }
+}
+
+impl Connection {
+    /// How long a reliable message waits for an acknowledgement before it is sent again.
+    pub const RESEND_INTERVAL: Duration = Duration::from_millis(100);
+    /// Most reliable messages put into one packet, to leave room for the rest.
+    pub const MAX_RELIABLE_PER_PACKET: usize = 8;
+    /// Largest reliable message accepted, id included. Packets with at least
+    /// this much room for reliable messages never hold one back for good.
+    pub const MAX_RELIABLE_SIZE: usize = 512;
+    /// Packets older than this can no longer be acknowledged by `ack_bits`.
+    const SENT_HISTORY: usize = 33;
+    /// Reliable messages further ahead than this are dropped and resent later.
+    const MAX_OUT_OF_ORDER: u16 = 1024;
+    /// Weight given to each new round trip sample when smoothing.
+    const RTT_SMOOTHING: f64 = 0.125;
+
+    /// Session stamped on every packet from now on.
+    pub fn set_session(&mut self, session: SessionToken) {
+        self.session = Some(session);
+    }
+
+    pub fn session(&self) -> Option<SessionToken> {
+        self.session
+    }
+
+    /// Queues `command` to be delivered reliably and in order, unless it is
+    /// larger than [`Self::MAX_RELIABLE_SIZE`].
+    pub fn send_reliable(&mut self, command: Command) -> Result<(), OversizedMessage> {
+        // The id takes up to three bytes in front of the command.
+        let size = command
+            .serialize()
+            .map_or(usize::MAX, |bytes| bytes.len() + 3);
+        if size > Self::MAX_RELIABLE_SIZE {
+            return Err(OversizedMessage { size });
+        }
+        self.outgoing.push_back(PendingMessage {
+            id: self.next_reliable_id,
+            command,
+            size,
+            last_sent: None,
+        });
+        self.next_reliable_id = self.next_reliable_id.wrapping_add(1);
+        Ok(())
+    }
+
+    /// How long packets take to be acknowledged, smoothed over recent ones.
+    /// Includes however long the peer waits before sending its next packet.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    /// Whether some reliable message has not been acknowledged yet.
+    pub fn has_unacked(&self) -> bool {
+        !self.outgoing.is_empty()
+    }
+
+    /// Starts the next packet with the current acknowledgements and every
+    /// reliable message due for (re)sending. Unreliable messages can be
+    /// pushed onto it before it is sent.
+    pub fn next_packet(&mut self, now: Instant) -> Packet {
+        self.next_packet_within(now, usize::MAX)
+    }
+
+    /// Like [`Self::next_packet`], but only takes reliable messages taking
+    /// up at most `max_bytes` between them. The rest wait for a later packet.
+    /// With less than [`Self::MAX_RELIABLE_SIZE`] bytes some message may
+    /// never fit.
+    pub fn next_packet_within(&mut self, now: Instant, max_bytes: usize) -> Packet {
+        let sequence = self.local_sequence;
+        self.local_sequence = self.local_sequence.wrapping_add(1);
+
+        let mut reliable = vec![];
+        let mut used = 0;
+        for message in &mut self.outgoing {
+            if reliable.len() == Self::MAX_RELIABLE_PER_PACKET {
+                break;
+            }
+            if message
+                .last_sent
+                .is_some_and(|sent| now.duration_since(sent) < Self::RESEND_INTERVAL)
+            {
+                continue;
+            }
+            if used + message.size > max_bytes {
+                continue;
+            }
+            used += message.size;
+            message.last_sent = Some(now);
+            reliable.push((message.id, message.command.clone()));
+        }
+
+        if self.sent_packets.len() == Self::SENT_HISTORY {
+            self.sent_packets.pop_front();
+        }
+        self.sent_packets
+            .push_back((sequence, now, reliable.iter().map(|(id, _)| *id).collect()));
+
+        Packet {
+            session: self.session,
+            sequence,
+            ack: self.remote_sequence,
+            ack_bits: self.received_bits,
+            reliable,
+            unreliable: vec![],
+        }
+    }
+
+    /// Processes a packet from the peer and returns the messages it makes
+    /// deliverable: its unreliable messages unless it is a duplicate,
+    /// followed by any reliable messages that are now next in order.
+    pub fn receive(&mut self, packet: Packet, now: Instant) -> Vec<Command> {
+        self.process_acks(packet.ack, packet.ack_bits, now);
+        let is_new = self.mark_received(packet.sequence);
+
+        let mut delivered = if is_new { packet.unreliable } else { vec![] };
+        for (id, command) in packet.reliable {
+            let ahead = id.wrapping_sub(self.next_expected_id);
+            if ahead < Self::MAX_OUT_OF_ORDER {
+                self.incoming.entry(id).or_insert(command);
+            }
+        }
+        while let Some(command) = self.incoming.remove(&self.next_expected_id) {
+            delivered.push(command);
+            self.next_expected_id = self.next_expected_id.wrapping_add(1);
+        }
+        delivered
+    }
+
+    /// Forgets the packets acknowledged by `ack` and `ack_bits` along with
+    /// the reliable messages they carried. The newest one, if it was not
+    /// acknowledged before, gives a round trip time sample.
+    fn process_acks(&mut self, ack: Option<u16>, ack_bits: u32, now: Instant) {
+        let Some(ack) = ack else {
+            return;
+        };
+        if let Some((_, sent, _)) = self
+            .sent_packets
+            .iter()
+            .find(|(sequence, _, _)| *sequence == ack)
+        {
+            let sample = now.saturating_duration_since(*sent);
+            self.rtt = Some(self.rtt.map_or(sample, |rtt| {
+                rtt.mul_f64(1.0 - Self::RTT_SMOOTHING) + sample.mul_f64(Self::RTT_SMOOTHING)
+            }));
+        }
+        let acked = |sequence: u16| {
+            let behind = ack.wrapping_sub(sequence);
+            behind == 0 || (1..=32).contains(&behind) && ack_bits & (1 << (behind - 1)) != 0
+        };
+        let mut acked_ids = vec![];
+        self.sent_packets.retain(|(sequence, _, ids)| {
+            if acked(*sequence) {
+                acked_ids.extend_from_slice(ids);
+                false
+            } else {
+                true
+            }
+        });
+        self.outgoing
+            .retain(|message| !acked_ids.contains(&message.id));
+    }
+
+    /// Records `sequence` as received and tells whether it had been before.
+    fn mark_received(&mut self, sequence: u16) -> bool {
+        let Some(remote) = self.remote_sequence else {
+            self.remote_sequence = Some(sequence);
+            return true;
+        };
+        if sequence_greater_than(sequence, remote) {
+            let shift = sequence.wrapping_sub(remote) as u32;
+            self.received_bits = if shift > 32 {
+                0
+            } else {
+                ((self.received_bits as u64) << shift | 1 << (shift - 1)) as u32
+            };
+            self.remote_sequence = Some(sequence);
+            return true;
+        }
+        let behind = remote.wrapping_sub(sequence) as u32;
+        if behind == 0 || behind > 32 {
+            return false;
+        }
+        let bit = 1 << (behind - 1);
+        let is_new = self.received_bits & bit == 0;
+        self.received_bits |= bit;
+        is_new
+    }
+}
+
+impl Display for OversizedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "reliable message of {} bytes is over the {} byte limit",
+            self.size,
+            Connection::MAX_RELIABLE_SIZE
+        )
+    }
+}
+
+impl Error for OversizedMessage {}
+
+/// Compares sequence numbers so that they keep ordering across wrapping.
+fn sequence_greater_than(a: u16, b: u16) -> bool {
+    a != b && a.wrapping_sub(b) < u16::MAX / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::ChatChannel;
+    use crate::command::CommandType;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    /// Small deterministic generator so lossy runs are reproducible.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn chance(&mut self, percent: u64) -> bool {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % 100 < percent
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.chance(0);
+            self.0 % n
+        }
+    }
+
+    /// Loopback that drops, duplicates and reorders what goes through it.
+    struct LossyLink {
+        rng: XorShift,
+        drop_percent: u64,
+        duplicate_percent: u64,
+        max_delay_frames: u64,
+        in_flight: Vec<(u64, Vec<u8>)>,
+    }
+
+    impl LossyLink {
+        fn new(seed: u64) -> Self {
+            Self {
+                rng: XorShift(seed),
+                drop_percent: 25,
+                duplicate_percent: 10,
+                max_delay_frames: 6,
+                in_flight: vec![],
+            }
+        }
+
+        fn send(&mut self, frame: u64, packet: &Packet) {
+            let bytes = packet.serialize().unwrap();
+            if self.rng.chance(self.drop_percent) {
+                return;
+            }
+            let copies = if self.rng.chance(self.duplicate_percent) {
+                2
+            } else {
+                1
+            };
+            for _ in 0..copies {
+                let arrives = frame + 1 + self.rng.below(self.max_delay_frames);
+                self.in_flight.push((arrives, bytes.clone()));
+            }
+        }
+
+        fn receive(&mut self, frame: u64) -> Vec<Packet> {
+            let (arrived, in_flight) = self
+                .in_flight
+                .drain(..)
+                .partition(|(arrives, _)| *arrives <= frame);
+            self.in_flight = in_flight;
+            arrived
+                .into_iter()
+                .map(|(_, bytes)| Packet::deserialize(&bytes).unwrap())
+                .collect()
+        }
+    }
+
+    fn ack(tick: u32) -> Command {
+        Command {
+            command_type: CommandType::SnapshotAck { tick },
+            time: 0,
+        }
+    }
+
+    fn tick_of(command: &Command) -> u32 {
+        match command.command_type {
+            CommandType::SnapshotAck { tick } => tick,
+            _ => panic!("unexpected {command:?}"),
+        }
+    }
+
+    struct Delivered {
+        reliable: Vec<u32>,
+        unreliable: Vec<u32>,
+    }
+
+    /// Sends `messages` reliably from one side to the other over a lossy link
+    /// both ways, with an unreliable message alongside every packet.
+    fn exchange(seed: u64, messages: u32, frames: u64) -> Delivered {
+        let start = Instant::now();
+        let mut sender = Connection::default();
+        let mut receiver = Connection::default();
+        let mut uplink = LossyLink::new(seed);
+        let mut downlink = LossyLink::new(seed + 1);
+        let mut delivered = Delivered {
+            reliable: vec![],
+            unreliable: vec![],
+        };
+
+        for frame in 0..frames {
+            let now = start + FRAME * frame as u32;
+            if (frame as u32) < messages {
+                sender.send_reliable(ack(frame as u32)).unwrap();
+            }
+            let mut packet = sender.next_packet(now);
+            packet.unreliable.push(ack(1_000_000 + frame as u32));
+            uplink.send(frame, &packet);
+
+            for packet in uplink.receive(frame) {
+                for command in receiver.receive(packet, now) {
+                    let tick = tick_of(&command);
+                    if tick >= 1_000_000 {
+                        delivered.unreliable.push(tick);
+                    } else {
+                        delivered.reliable.push(tick);
+                    }
+                }
+            }
+            downlink.send(frame, &receiver.next_packet(now));
+            for packet in downlink.receive(frame) {
+                sender.receive(packet, now);
+            }
+        }
+        assert!(!sender.has_unacked(), "seed {seed} left messages unacked");
+        delivered
+    }
+
+    #[test]
+    fn reliable_messages_arrive_once_and_in_order() {
+        for seed in 1..20 {
+            let delivered = exchange(seed, 200, 600);
+            assert_eq!(delivered.reliable, (0..200).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn unreliable_messages_are_never_duplicated() {
+        let delivered = exchange(7, 0, 600);
+        let mut unique = delivered.unreliable.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), delivered.unreliable.len());
+        assert!(delivered.unreliable.len() < 600);
+        assert!(delivered.unreliable.len() > 300);
+    }
+
+    #[test]
+    fn acks_cover_the_last_33_packets() {
+        let now = Instant::now();
+        let mut sender = Connection::default();
+        let mut receiver = Connection::default();
+        for sequence in 0..40 {
+            let packet = sender.next_packet(now);
+            if sequence % 3 != 0 {
+                receiver.receive(packet, now);
+            }
+        }
+        let packet = receiver.next_packet(now);
+        assert_eq!(packet.ack, Some(38));
+        for behind in 1..=32u16 {
+            let received = (38 - behind) % 3 != 0;
+            assert_eq!(packet.ack_bits & (1 << (behind - 1)) != 0, received);
+        }
+    }
+
+    #[test]
+    fn sequences_wrap_around() {
+        assert!(sequence_greater_than(0, u16::MAX));
+        assert!(sequence_greater_than(5, 65530));
+        assert!(!sequence_greater_than(65530, 5));
+
+        let now = Instant::now();
+        let mut sender = Connection {
+            local_sequence: u16::MAX - 2,
+            next_reliable_id: u16::MAX - 1,
+            ..Connection::default()
+        };
+        let mut receiver = Connection {
+            next_expected_id: u16::MAX - 1,
+            ..Connection::default()
+        };
+        let mut delivered = vec![];
+        for tick in 0..6 {
+            sender.send_reliable(ack(tick)).unwrap();
+            delivered.extend(receiver.receive(sender.next_packet(now), now));
+            sender.receive(receiver.next_packet(now), now);
+        }
+        assert_eq!(
+            delivered.iter().map(tick_of).collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4, 5]
+        );
+        assert!(!sender.has_unacked());
+    }
+
+    #[test]
+    fn reliable_messages_past_the_byte_budget_wait() {
+        let now = Instant::now();
+        let mut connection = Connection::default();
+        for tick in 0..4 {
+            connection.send_reliable(ack(tick)).unwrap();
+        }
+        let size = ack(0).serialize().unwrap().len() + 3;
+        let packet = connection.next_packet_within(now, size * 2 + 1);
+        assert_eq!(
+            packet
+                .reliable
+                .iter()
+                .map(|(_, c)| tick_of(c))
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+        let packet = connection.next_packet_within(now, usize::MAX);
+        assert_eq!(
+            packet
+                .reliable
+                .iter()
+                .map(|(_, c)| tick_of(c))
+                .collect::<Vec<_>>(),
+            [2, 3]
+        );
+    }
+
+    #[test]
+    fn oversized_reliable_messages_are_refused() {
+        let chat = |length: usize| Command {
+            command_type: CommandType::ChatSend {
+                channel: ChatChannel::All,
+                text: "x".repeat(length),
+            },
+            time: 0,
+        };
+        let mut connection = Connection::default();
+        let refused = connection.send_reliable(chat(Connection::MAX_RELIABLE_SIZE));
+        assert!(refused.is_err_and(|e| e.size > Connection::MAX_RELIABLE_SIZE));
+        assert!(!connection.has_unacked());
+
+        let largest = (0..Connection::MAX_RELIABLE_SIZE)
+            .rev()
+            .map(chat)
+            .find(|command| command.serialize().unwrap().len() + 3 <= Connection::MAX_RELIABLE_SIZE)
+            .unwrap();
+        connection.send_reliable(largest.clone()).unwrap();
+        connection.send_reliable(ack(1)).unwrap();
+        let now = Instant::now();
+        let packet = connection.next_packet_within(now, Connection::MAX_RELIABLE_SIZE);
+        assert_eq!(packet.reliable, [(0, largest)]);
+        let packet = connection.next_packet_within(now, Connection::MAX_RELIABLE_SIZE);
+        assert_eq!(packet.reliable, [(1, ack(1))]);
+    }
+
+    #[test]
+    fn headers_fit_within_the_header_size() {
+        let packet = Packet {
+            session: Some(u64::MAX),
+            sequence: u16::MAX,
+            ack: Some(u16::MAX),
+            ack_bits: u32::MAX,
+            reliable: vec![],
+            unreliable: vec![],
+        };
+        assert!(packet.serialize().unwrap().len() <= Packet::MAX_HEADER_SIZE);
+    }
+
+    #[test]
+    fn unacked_messages_wait_before_being_resent() {
+        let now = Instant::now();
+        let mut connection = Connection::default();
+        connection.send_reliable(ack(1)).unwrap();
+        assert_eq!(connection.next_packet(now).reliable.len(), 1);
+        assert!(connection.next_packet(now + FRAME).reliable.is_empty());
+        let later = now + Connection::RESEND_INTERVAL;
+        assert_eq!(connection.next_packet(later).reliable.len(), 1);
+    }
+
+    #[test]
+    fn round_trips_are_timed_from_acknowledgements() {
+        let start = Instant::now();
+        let mut sender = Connection::default();
+        let mut receiver = Connection::default();
+        assert_eq!(sender.rtt(), None);
+
+        let round_trip = Duration::from_millis(80);
+        for frame in 0..50 {
+            let now = start + FRAME * frame;
+            receiver.receive(sender.next_packet(now), now);
+            sender.receive(receiver.next_packet(now), now + round_trip);
+        }
+        assert_eq!(sender.rtt(), Some(round_trip));
+
+        // An acknowledgement repeated by a later packet is not timed again.
+        let now = start + FRAME * 60;
+        let stale = receiver.next_packet(now);
+        sender.receive(stale, now + Duration::from_secs(10));
+        assert_eq!(sender.rtt(), Some(round_trip));
+    }
+}
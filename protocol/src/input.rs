@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// One frame worth of player input. The server replays these through the same
+/// movement code as the client instead of trusting client positions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerInput {
+    /// Increments by one for every input a client sends, starting at 1.
+    pub sequence: u32,
+    pub forward: bool,
+    pub backward: bool,
+    pub left: bool,
+    pub right: bool,
+    pub jump: bool,
+    pub pitch: f32,
+    pub yaw: f32,
+    /// Seconds of simulation this input covers.
+    pub dt: f32,
+}
@@ -0,0 +1,9 @@
+pub mod bits;
+pub mod chat;
+pub mod command;
+pub mod config;
+pub mod connection;
+pub mod input;
+pub mod match_state;
+pub mod player_state;
+pub mod snapshot;
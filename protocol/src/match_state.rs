@@ -0,0 +1,86 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::player_state::Team;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameModeKind {
+    Deathmatch,
+    TeamDeathmatch,
+}
+
+/// Where a match is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchPhase {
+    /// Waiting for players, then counting down. Kills do not count.
+    Warmup,
+    Live,
+    /// The match is over and its final scores are shown. Nobody can be hurt.
+    Intermission,
+}
+
+/// One line of the scoreboard.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    pub player_id: Uuid,
+    pub name: String,
+    pub team: Option<Team>,
+    /// Enemies killed.
+    pub frags: u32,
+    pub deaths: u32,
+    /// Frags minus penalties such as suicides and team kills.
+    pub score: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGameMode(pub String);
+
+impl ScoreEntry {
+    pub fn new(player_id: Uuid, name: String, team: Option<Team>) -> Self {
+        Self {
+            player_id,
+            name,
+            team,
+            frags: 0,
+            deaths: 0,
+            score: 0,
+        }
+    }
+}
+
+impl FromStr for GameModeKind {
+    type Err = UnknownGameMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deathmatch" | "dm" => Ok(Self::Deathmatch),
+            "team_deathmatch" | "tdm" => Ok(Self::TeamDeathmatch),
+            _ => Err(UnknownGameMode(s.to_owned())),
+        }
+    }
+}
+
+impl Display for GameModeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Deathmatch => write!(f, "deathmatch"),
+            Self::TeamDeathmatch => write!(f, "team_deathmatch"),
+        }
+    }
+}
+
+impl Display for UnknownGameMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown game mode {}, expected deathmatch or team_deathmatch",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownGameMode {}
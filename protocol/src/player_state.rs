@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::command::Weapon;
+
+/// Longest player name in characters. Names are repeated in scoreboard
+/// messages, so this keeps those well inside a datagram.
+pub const MAX_NAME_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub player_id: Uuid,
+    pub position: [f32; 3],
+    pub velocity: [f32; 3],
+    pub pitch: f32,
+    pub yaw: f32,
+    pub health: u8,
+    pub is_on_ground: bool,
+    pub armor: u8,
+    /// One bit per `Weapon` the player has picked up.
+    pub weapons: u8,
+    /// Ammunition for the rocket launcher; the rifle needs none.
+    pub rockets: u8,
+}
+
+/// Side a player fights on in team modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Team {
+    Red,
+    Blue,
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        Self {
+            player_id: Uuid::new_v4(),
+            position: [0.0, 0.0, 0.0],
+            velocity: [0.0, 0.0, 0.0],
+            pitch: 0.0,
+            yaw: 0.0,
+            health: Self::MAX_HEALTH,
+            is_on_ground: false,
+            armor: 0,
+            weapons: Self::STARTING_WEAPONS,
+            rockets: 0,
+        }
+    }
+}
+
+impl PlayerState {
+    pub const MAX_HEALTH: u8 = 100;
+    pub const MAX_ARMOR: u8 = 100;
+    pub const MAX_ROCKETS: u8 = 50;
+    /// Everyone spawns with just the rifle.
+    pub const STARTING_WEAPONS: u8 = 1 << Weapon::Rifle as u8;
+
+    pub fn update(&mut self, position: [f32; 3], velocity: [f32; 3], pitch: f32, yaw: f32) {
+        self.position = position;
+        self.velocity = velocity;
+        self.pitch = pitch;
+        self.yaw = yaw;
+    }
+
+    pub fn has_weapon(&self, weapon: Weapon) -> bool {
+        self.weapons & (1 << weapon as u8) != 0
+    }
+
+    pub fn give_weapon(&mut self, weapon: Weapon) {
+        self.weapons |= 1 << weapon as u8;
+    }
+
+    /// Spends a round of `weapon`'s ammunition, if it uses any. Returns
+    /// whether there was enough to fire.
+    pub fn use_ammo(&mut self, weapon: Weapon) -> bool {
+        match weapon {
+            Weapon::Rifle => true,
+            Weapon::RocketLauncher if self.rockets > 0 => {
+                self.rockets -= 1;
+                true
+            }
+            Weapon::RocketLauncher => false,
+        }
+    }
+
+    /// Takes `damage`, half of which armor soaks up for as long as it lasts.
+    /// Returns the health lost.
+    pub fn take_damage(&mut self, damage: u8) -> u8 {
+        let absorbed = (damage / 2).min(self.armor);
+        self.armor -= absorbed;
+        let lost = (damage - absorbed).min(self.health);
+        self.health -= lost;
+        lost
+    }
+
+    /// Back to full health with nothing but what a player spawns with.
+    pub fn reset_loadout(&mut self) {
+        self.health = Self::MAX_HEALTH;
+        self.armor = 0;
+        self.weapons = Self::STARTING_WEAPONS;
+        self.rockets = 0;
+    }
+}
+
+/// `name` as it may be shown: control characters dropped, surrounding
+/// whitespace trimmed and cut to [`MAX_NAME_LENGTH`] characters.
+pub fn sanitize_name(name: &str) -> String {
+    let sanitized: String = name
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    sanitized.trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_cleaned_and_cut() {
+        assert_eq!(sanitize_name("  \u{1b}[31mdoom\nguy\t "), "[31mdoomguy");
+        assert_eq!(sanitize_name("\r\n\u{7}"), "");
+        let long = "é".repeat(MAX_NAME_LENGTH * 40);
+        assert_eq!(sanitize_name(&long).chars().count(), MAX_NAME_LENGTH);
+    }
+
+    #[test]
+    fn armor_absorbs_half_the_damage_while_it_lasts() {
+        let mut state = PlayerState {
+            armor: 10,
+            ..PlayerState::default()
+        };
+        assert_eq!(state.take_damage(16), 8);
+        assert_eq!((state.health, state.armor), (92, 2));
+        assert_eq!(state.take_damage(16), 14);
+        assert_eq!((state.health, state.armor), (78, 0));
+        assert_eq!(state.take_damage(200), 78);
+        assert_eq!(state.health, 0);
+    }
+
+    #[test]
+    fn rockets_need_the_launcher_and_ammo() {
+        let mut state = PlayerState::default();
+        assert!(state.has_weapon(Weapon::Rifle));
+        assert!(!state.has_weapon(Weapon::RocketLauncher));
+        assert!(state.use_ammo(Weapon::Rifle));
+        assert!(!state.use_ammo(Weapon::RocketLauncher));
+
+        state.give_weapon(Weapon::RocketLauncher);
+        state.rockets = 1;
+        assert!(state.has_weapon(Weapon::RocketLauncher));
+        assert!(state.use_ammo(Weapon::RocketLauncher));
+        assert!(!state.use_ammo(Weapon::RocketLauncher));
+
+        state.reset_loadout();
+        assert_eq!(state.weapons, PlayerState::STARTING_WEAPONS);
+    }
+}
@@ -0,0 +1,607 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+use std::fmt::{self, Display};
+
+use uuid::Uuid;
+
+use crate::bits::{BitReader, BitWriter, OutOfBits};
+use crate::player_state::PlayerState;
+
+/// Units per quantized position step; 1/256 of a unit is well below what is visible.
+const POSITION_SCALE: f32 = 256.0;
+const POSITION_BITS: u32 = 24;
+/// Position changes that fit in this many bits are sent as a delta instead.
+const POSITION_DELTA_BITS: u32 = 10;
+const VELOCITY_SCALE: f32 = 64.0;
+const VELOCITY_BITS: u32 = 16;
+const PITCH_BITS: u32 = 13;
+const YAW_BITS: u32 = 14;
+const HEALTH_BITS: u32 = 8;
+
+const PROJECTILE_ID_BITS: u32 = 32;
+
+const TICK_BITS: u32 = 32;
+const COUNT_BITS: u32 = 16;
+const PROJECTILE_COUNT_BITS: u32 = 8;
+const SLOT_BITS: u32 = 8;
+const KIND_BITS: u32 = 2;
+const KIND_REMOVED: u64 = 0;
+const KIND_FULL: u64 = 1;
+const KIND_DELTA: u64 = 2;
+
+/// A player state reduced to the precision it is replicated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntityState {
+    pub player_id: Uuid,
+    pub position: [i32; 3],
+    pub velocity: [i32; 3],
+    pub pitch: u16,
+    pub yaw: u16,
+    pub health: u8,
+    pub is_on_ground: bool,
+}
+
+/// A projectile reduced to the precision it is replicated with. Projectiles
+/// fly in straight lines and live briefly, so they are sent in full every
+/// tick and the receiver extrapolates them in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProjectileState {
+    pub id: u32,
+    pub position: [i32; 3],
+    pub velocity: [i32; 3],
+}
+
+/// Every replicated entity for one tick, keyed by the slot the server gave it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    pub tick: u32,
+    pub entities: BTreeMap<u8, EntityState>,
+    /// Projectiles in flight. Unlike `entities` these are never carried over
+    /// from the baseline.
+    pub projectiles: Vec<ProjectileState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    Truncated,
+    MissingBaseline(u32),
+    UnknownEntry(u64),
+}
+
+impl EntityState {
+    pub fn quantize(state: &PlayerState) -> Self {
+        Self {
+            player_id: state.player_id,
+            position: quantize_position(state.position),
+            velocity: quantize_velocity(state.velocity),
+            pitch: Self::quantize_angle(state.pitch + FRAC_PI_2, PI, PITCH_BITS),
+            yaw: Self::quantize_angle(state.yaw.rem_euclid(TAU), TAU, YAW_BITS),
+            health: state.health,
+            is_on_ground: state.is_on_ground,
+        }
+    }
+
+    /// Yaw comes back in `[0, TAU)`, so consumers must not assume it is
+    /// continuous. Armor and inventory are not replicated for other players
+    /// and come back empty.
+    pub fn dequantize(&self) -> PlayerState {
+        PlayerState {
+            player_id: self.player_id,
+            position: dequantize_position(self.position),
+            velocity: dequantize_velocity(self.velocity),
+            pitch: Self::dequantize_angle(self.pitch, PI, PITCH_BITS) - FRAC_PI_2,
+            yaw: Self::dequantize_angle(self.yaw, TAU, YAW_BITS),
+            health: self.health,
+            is_on_ground: self.is_on_ground,
+            armor: 0,
+            weapons: 0,
+            rockets: 0,
+        }
+    }
+
+    fn quantize_angle(angle: f32, range: f32, bits: u32) -> u16 {
+        let steps = (1 << bits) as f32;
+        ((angle / range * steps).round() as u32 % (1 << bits)) as u16
+    }
+
+    fn dequantize_angle(angle: u16, range: f32, bits: u32) -> f32 {
+        angle as f32 / (1 << bits) as f32 * range
+    }
+
+    fn write_full(&self, writer: &mut BitWriter) {
+        let id = self.player_id.as_u128();
+        writer.write_bits((id >> 64) as u64, 64);
+        writer.write_bits(id as u64, 64);
+        for v in self.position {
+            writer.write_signed(v as i64, POSITION_BITS);
+        }
+        for v in self.velocity {
+            writer.write_signed(v as i64, VELOCITY_BITS);
+        }
+        writer.write_bits(self.pitch as u64, PITCH_BITS);
+        writer.write_bits(self.yaw as u64, YAW_BITS);
+        writer.write_bits(self.health as u64, HEALTH_BITS);
+        writer.write_bool(self.is_on_ground);
+    }
+
+    fn read_full(reader: &mut BitReader) -> Result<Self, OutOfBits> {
+        let high = reader.read_bits(64)? as u128;
+        let low = reader.read_bits(64)? as u128;
+        let mut position = [0; 3];
+        for v in &mut position {
+            *v = reader.read_signed(POSITION_BITS)? as i32;
+        }
+        let mut velocity = [0; 3];
+        for v in &mut velocity {
+            *v = reader.read_signed(VELOCITY_BITS)? as i32;
+        }
+        Ok(Self {
+            player_id: Uuid::from_u128((high << 64) | low),
+            position,
+            velocity,
+            pitch: reader.read_bits(PITCH_BITS)? as u16,
+            yaw: reader.read_bits(YAW_BITS)? as u16,
+            health: reader.read_bits(HEALTH_BITS)? as u8,
+            is_on_ground: reader.read_bool()?,
+        })
+    }
+
+    /// Writes a changed bit per field, followed by the new value of each changed field.
+    fn write_delta(&self, baseline: &Self, writer: &mut BitWriter) {
+        for (old, new) in baseline.position.iter().zip(self.position) {
+            let delta = new as i64 - *old as i64;
+            writer.write_bool(delta != 0);
+            if delta == 0 {
+                continue;
+            }
+            let small = delta.abs() < 1 << (POSITION_DELTA_BITS - 1);
+            writer.write_bool(small);
+            if small {
+                writer.write_signed(delta, POSITION_DELTA_BITS);
+            } else {
+                writer.write_signed(new as i64, POSITION_BITS);
+            }
+        }
+        for (old, new) in baseline.velocity.iter().zip(self.velocity) {
+            writer.write_bool(*old != new);
+            if *old != new {
+                writer.write_signed(new as i64, VELOCITY_BITS);
+            }
+        }
+        Self::write_changed(writer, baseline.pitch as u64, self.pitch as u64, PITCH_BITS);
+        Self::write_changed(writer, baseline.yaw as u64, self.yaw as u64, YAW_BITS);
+        Self::write_changed(
+            writer,
+            baseline.health as u64,
+            self.health as u64,
+            HEALTH_BITS,
+        );
+        writer.write_bool(baseline.is_on_ground != self.is_on_ground);
+    }
+
+    fn read_delta(baseline: &Self, reader: &mut BitReader) -> Result<Self, OutOfBits> {
+        let mut entity = *baseline;
+        for v in &mut entity.position {
+            if !reader.read_bool()? {
+                continue;
+            }
+            if reader.read_bool()? {
+                *v += reader.read_signed(POSITION_DELTA_BITS)? as i32;
+            } else {
+                *v = reader.read_signed(POSITION_BITS)? as i32;
+            }
+        }
+        for v in &mut entity.velocity {
+            if reader.read_bool()? {
+                *v = reader.read_signed(VELOCITY_BITS)? as i32;
+            }
+        }
+        if reader.read_bool()? {
+            entity.pitch = reader.read_bits(PITCH_BITS)? as u16;
+        }
+        if reader.read_bool()? {
+            entity.yaw = reader.read_bits(YAW_BITS)? as u16;
+        }
+        if reader.read_bool()? {
+            entity.health = reader.read_bits(HEALTH_BITS)? as u8;
+        }
+        if reader.read_bool()? {
+            entity.is_on_ground = !entity.is_on_ground;
+        }
+        Ok(entity)
+    }
+
+    fn write_changed(writer: &mut BitWriter, old: u64, new: u64, bits: u32) {
+        writer.write_bool(old != new);
+        if old != new {
+            writer.write_bits(new, bits);
+        }
+    }
+}
+
+impl ProjectileState {
+    pub fn quantize(id: u32, position: [f32; 3], velocity: [f32; 3]) -> Self {
+        Self {
+            id,
+            position: quantize_position(position),
+            velocity: quantize_velocity(velocity),
+        }
+    }
+
+    pub fn position(&self) -> [f32; 3] {
+        dequantize_position(self.position)
+    }
+
+    pub fn velocity(&self) -> [f32; 3] {
+        dequantize_velocity(self.velocity)
+    }
+
+    fn write(&self, writer: &mut BitWriter) {
+        writer.write_bits(self.id as u64, PROJECTILE_ID_BITS);
+        for v in self.position {
+            writer.write_signed(v as i64, POSITION_BITS);
+        }
+        for v in self.velocity {
+            writer.write_signed(v as i64, VELOCITY_BITS);
+        }
+    }
+
+    fn read(reader: &mut BitReader) -> Result<Self, OutOfBits> {
+        let id = reader.read_bits(PROJECTILE_ID_BITS)? as u32;
+        let mut position = [0; 3];
+        for v in &mut position {
+            *v = reader.read_signed(POSITION_BITS)? as i32;
+        }
+        let mut velocity = [0; 3];
+        for v in &mut velocity {
+            *v = reader.read_signed(VELOCITY_BITS)? as i32;
+        }
+        Ok(Self {
+            id,
+            position,
+            velocity,
+        })
+    }
+}
+
+fn quantize_position(position: [f32; 3]) -> [i32; 3] {
+    let limit = (1 << (POSITION_BITS - 1)) - 1;
+    position.map(|v| ((v * POSITION_SCALE).round() as i32).clamp(-limit, limit))
+}
+
+fn dequantize_position(position: [i32; 3]) -> [f32; 3] {
+    position.map(|v| v as f32 / POSITION_SCALE)
+}
+
+fn quantize_velocity(velocity: [f32; 3]) -> [i32; 3] {
+    let limit = (1 << (VELOCITY_BITS - 1)) - 1;
+    velocity.map(|v| ((v * VELOCITY_SCALE).round() as i32).clamp(-limit, limit))
+}
+
+fn dequantize_velocity(velocity: [i32; 3]) -> [f32; 3] {
+    velocity.map(|v| v as f32 / VELOCITY_SCALE)
+}
+
+impl Snapshot {
+    /// Most projectiles a single snapshot can carry.
+    pub const MAX_PROJECTILES: usize = (1 << PROJECTILE_COUNT_BITS) - 1;
+
+    /// Bits used outside of the entries when encoding against `baseline`.
+    fn header_bits(baseline: Option<&Snapshot>) -> usize {
+        (TICK_BITS + 1 + COUNT_BITS + PROJECTILE_COUNT_BITS) as usize
+            + baseline.map_or(0, |_| TICK_BITS as usize)
+    }
+
+    /// Encodes this snapshot as changes from `baseline` (or in full without
+    /// one) in at most `max_bytes`. Entities that do not fit are left out and
+    /// the receiver keeps their baseline state; projectiles go in with
+    /// whatever room is left after the entities. Returns the encoded bytes and
+    /// the snapshot the receiver will reconstruct from them, which is what
+    /// later deltas have to be based on.
+    pub fn encode(&self, baseline: Option<&Snapshot>, max_bytes: usize) -> (Vec<u8>, Snapshot) {
+        let empty = Snapshot::default();
+        let base = baseline.unwrap_or(&empty);
+        let budget_bits = max_bytes.saturating_mul(8);
+        let mut received = Snapshot {
+            tick: self.tick,
+            entities: base.entities.clone(),
+            projectiles: vec![],
+        };
+        let mut entries = BitWriter::default();
+        let mut count: u64 = 0;
+        let fits = |used_bits: usize| Self::header_bits(baseline) + used_bits <= budget_bits;
+
+        for slot in base.entities.keys() {
+            if self.entities.contains_key(slot) {
+                continue;
+            }
+            let mut entry = BitWriter::default();
+            entry.write_bits(*slot as u64, SLOT_BITS);
+            entry.write_bits(KIND_REMOVED, KIND_BITS);
+            if fits(entries.bit_len() + entry.bit_len()) {
+                entries.append(&entry);
+                count += 1;
+                received.entities.remove(slot);
+            }
+        }
+
+        // Start from a different slot every tick so that when the budget runs
+        // out the same entities are not the ones starved each time.
+        let start = self.tick as u8;
+        let mut slots: Vec<u8> = self.entities.keys().copied().collect();
+        slots.sort_by_key(|slot| slot.wrapping_sub(start));
+        for slot in slots {
+            let entity = &self.entities[&slot];
+            let mut entry = BitWriter::default();
+            entry.write_bits(slot as u64, SLOT_BITS);
+            match base.entities.get(&slot) {
+                Some(old) if old == entity => continue,
+                Some(old) if old.player_id == entity.player_id => {
+                    entry.write_bits(KIND_DELTA, KIND_BITS);
+                    entity.write_delta(old, &mut entry);
+                }
+                _ => {
+                    entry.write_bits(KIND_FULL, KIND_BITS);
+                    entity.write_full(&mut entry);
+                }
+            }
+            if fits(entries.bit_len() + entry.bit_len()) {
+                entries.append(&entry);
+                count += 1;
+                received.entities.insert(slot, *entity);
+            }
+        }
+
+        let mut projectile_entries = BitWriter::default();
+        for projectile in self.projectiles.iter().take(Self::MAX_PROJECTILES) {
+            let mut entry = BitWriter::default();
+            projectile.write(&mut entry);
+            if !fits(entries.bit_len() + projectile_entries.bit_len() + entry.bit_len()) {
+                break;
+            }
+            projectile_entries.append(&entry);
+            received.projectiles.push(*projectile);
+        }
+
+        let mut writer = BitWriter::default();
+        writer.write_bits(self.tick as u64, TICK_BITS);
+        writer.write_bool(baseline.is_some());
+        if let Some(baseline) = baseline {
+            writer.write_bits(baseline.tick as u64, TICK_BITS);
+        }
+        writer.write_bits(count, COUNT_BITS);
+        writer.append(&entries);
+        writer.write_bits(received.projectiles.len() as u64, PROJECTILE_COUNT_BITS);
+        writer.append(&projectile_entries);
+        (writer.into_bytes(), received)
+    }
+
+    /// Reconstructs a snapshot, looking up the baseline it was encoded against by tick.
+    pub fn decode<'a>(
+        bytes: &[u8],
+        baseline: impl FnOnce(u32) -> Option<&'a Snapshot>,
+    ) -> Result<Snapshot, SnapshotError> {
+        let mut reader = BitReader::new(bytes);
+        let tick = reader.read_bits(TICK_BITS)? as u32;
+        let mut snapshot = Snapshot {
+            tick,
+            ..Snapshot::default()
+        };
+        if reader.read_bool()? {
+            let baseline_tick = reader.read_bits(TICK_BITS)? as u32;
+            let base =
+                baseline(baseline_tick).ok_or(SnapshotError::MissingBaseline(baseline_tick))?;
+            snapshot.entities = base.entities.clone();
+        }
+
+        let count = reader.read_bits(COUNT_BITS)?;
+        for _ in 0..count {
+            let slot = reader.read_bits(SLOT_BITS)? as u8;
+            match reader.read_bits(KIND_BITS)? {
+                KIND_REMOVED => {
+                    snapshot.entities.remove(&slot);
+                }
+                KIND_FULL => {
+                    snapshot
+                        .entities
+                        .insert(slot, EntityState::read_full(&mut reader)?);
+                }
+                KIND_DELTA => {
+                    let old = snapshot
+                        .entities
+                        .get(&slot)
+                        .ok_or(SnapshotError::MissingBaseline(tick))?;
+                    let entity = EntityState::read_delta(old, &mut reader)?;
+                    snapshot.entities.insert(slot, entity);
+                }
+                kind => return Err(SnapshotError::UnknownEntry(kind)),
+            }
+        }
+        let projectile_count = reader.read_bits(PROJECTILE_COUNT_BITS)?;
+        for _ in 0..projectile_count {
+            snapshot
+                .projectiles
+                .push(ProjectileState::read(&mut reader)?);
+        }
+        Ok(snapshot)
+    }
+}
+
+impl From<OutOfBits> for SnapshotError {
+    fn from(_: OutOfBits) -> Self {
+        Self::Truncated
+    }
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "snapshot ended early"),
+            Self::MissingBaseline(tick) => write!(f, "no baseline snapshot for tick {tick}"),
+            Self::UnknownEntry(kind) => write!(f, "unknown snapshot entry kind {kind}"),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(slot: u8, tick: u32) -> EntityState {
+        let t = tick as f32 * 0.05;
+        EntityState::quantize(&PlayerState {
+            player_id: Uuid::from_u128(slot as u128 + 1),
+            position: [slot as f32 + t, 0.5, t * 2.0],
+            velocity: [1.0, 0.0, 2.0],
+            pitch: 0.1,
+            yaw: t,
+            health: 100,
+            is_on_ground: true,
+            ..PlayerState::default()
+        })
+    }
+
+    fn world(tick: u32, players: u8) -> Snapshot {
+        Snapshot {
+            tick,
+            entities: (0..players)
+                .map(|slot| (slot, player(slot, tick)))
+                .collect(),
+            projectiles: vec![],
+        }
+    }
+
+    #[test]
+    fn quantization_is_close_to_the_original() {
+        let state = PlayerState {
+            position: [12.3456, -0.5, 1000.001],
+            velocity: [-3.3, 9.81, 0.0],
+            pitch: -1.2,
+            yaw: 7.0,
+            ..PlayerState::default()
+        };
+        let restored = EntityState::quantize(&state).dequantize();
+        for axis in 0..3 {
+            assert!((restored.position[axis] - state.position[axis]).abs() <= 0.5 / POSITION_SCALE);
+            assert!((restored.velocity[axis] - state.velocity[axis]).abs() <= 0.5 / VELOCITY_SCALE);
+        }
+        assert!((restored.pitch - state.pitch).abs() < 1e-3);
+        assert!((restored.yaw - state.yaw.rem_euclid(TAU)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn full_snapshot_round_trips() {
+        let snapshot = world(7, 5);
+        let (bytes, received) = snapshot.encode(None, 1200);
+        assert_eq!(received, snapshot);
+        assert_eq!(Snapshot::decode(&bytes, |_| None).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn delta_round_trips_joins_leaves_and_moves() {
+        let baseline = world(10, 6);
+        let mut current = world(11, 6);
+        current.entities.remove(&2);
+        current.entities.insert(9, player(9, 11));
+        current.entities.get_mut(&4).unwrap().player_id = Uuid::from_u128(99);
+        current.entities.insert(5, baseline.entities[&5]);
+
+        let (bytes, received) = current.encode(Some(&baseline), 1200);
+        assert_eq!(received, current);
+        let decoded = Snapshot::decode(&bytes, |tick| (tick == 10).then_some(&baseline)).unwrap();
+        assert_eq!(decoded, current);
+    }
+
+    #[test]
+    fn delta_is_smaller_than_full() {
+        let baseline = world(10, 32);
+        let current = world(11, 32);
+        let (full, _) = current.encode(None, usize::MAX);
+        let (delta, _) = current.encode(Some(&baseline), usize::MAX);
+        assert!(delta.len() * 2 < full.len());
+    }
+
+    #[test]
+    fn encoding_respects_the_byte_budget() {
+        let snapshot = world(3, 32);
+        let (bytes, received) = snapshot.encode(None, 300);
+        assert!(bytes.len() <= 300);
+        assert!(received.entities.len() < snapshot.entities.len());
+        assert_eq!(Snapshot::decode(&bytes, |_| None).unwrap(), received);
+
+        // Whatever was left out goes out on the following ticks.
+        let mut baseline = received;
+        for tick in 4..10 {
+            let mut next = snapshot.clone();
+            next.tick = tick;
+            let (bytes, received) = next.encode(Some(&baseline), 300);
+            assert!(bytes.len() <= 300);
+            baseline = received;
+        }
+        assert_eq!(baseline.entities, snapshot.entities);
+    }
+
+    #[test]
+    fn projectiles_are_sent_in_full_every_tick() {
+        let mut baseline = world(10, 2);
+        baseline.projectiles = vec![ProjectileState::quantize(
+            1,
+            [0.0, 1.0, 2.0],
+            [0.0, 0.0, 12.0],
+        )];
+        let mut current = world(11, 2);
+        current.projectiles = vec![ProjectileState::quantize(
+            2,
+            [-3.5, 0.25, 8.0],
+            [-6.0, 1.5, 0.0],
+        )];
+
+        let (bytes, received) = current.encode(Some(&baseline), 1200);
+        assert_eq!(received, current);
+        let decoded = Snapshot::decode(&bytes, |tick| (tick == 10).then_some(&baseline)).unwrap();
+        assert_eq!(decoded.projectiles, current.projectiles);
+        assert_eq!(decoded.projectiles[0].position(), [-3.5, 0.25, 8.0]);
+        assert_eq!(decoded.projectiles[0].velocity(), [-6.0, 1.5, 0.0]);
+
+        current.projectiles.clear();
+        let (bytes, _) = current.encode(Some(&baseline), 1200);
+        let decoded = Snapshot::decode(&bytes, |_| Some(&baseline)).unwrap();
+        assert!(decoded.projectiles.is_empty());
+    }
+
+    #[test]
+    fn projectiles_only_use_room_left_by_players() {
+        let mut snapshot = world(3, 24);
+        snapshot.projectiles = (0..100)
+            .map(|id| ProjectileState::quantize(id, [id as f32, 0.0, 0.0], [0.0, 0.0, 1.0]))
+            .collect();
+        let (bytes, received) = snapshot.encode(None, 1200);
+        assert!(bytes.len() <= 1200);
+        assert_eq!(received.entities, snapshot.entities);
+        assert!(!received.projectiles.is_empty());
+        assert!(received.projectiles.len() < snapshot.projectiles.len());
+        assert_eq!(Snapshot::decode(&bytes, |_| None).unwrap(), received);
+    }
+
+    #[test]
+    fn missing_baseline_is_reported() {
+        let baseline = world(1, 2);
+        let (bytes, _) = world(2, 2).encode(Some(&baseline), 1200);
+        assert_eq!(
+            Snapshot::decode(&bytes, |_| None),
+            Err(SnapshotError::MissingBaseline(1))
+        );
+    }
+
+    #[test]
+    fn truncated_snapshot_is_rejected() {
+        let (bytes, _) = world(2, 4).encode(None, 1200);
+        assert_eq!(
+            Snapshot::decode(&bytes[..bytes.len() / 2], |_| None),
+            Err(SnapshotError::Truncated)
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use std::f32::consts::{PI, TAU};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use game::navigation::NavGraph;
+use game::player_body::{PlayerBody, StepClock};
+use game::world::World;
+use nalgebra::{Point3, Vector3};
+use protocol::command::{CommandType, Weapon};
+use protocol::input::PlayerInput;
+use protocol::player_state::PlayerState;
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BotDifficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownBotDifficulty(pub String);
+
+/// How well a bot plays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BotSkill {
+    /// Largest angle, in radians, a bot's aim strays from its target.
+    pub aim_error: f32,
+    /// Ticks between first seeing an enemy and firing at it.
+    pub reaction_ticks: u64,
+    /// Radians a bot can turn per second.
+    pub turn_speed: f32,
+}
+
+/// A player simulated by the server. Every tick it looks at the world the
+/// way a client would and answers with the same commands a client sends.
+#[derive(Debug, Clone)]
+pub struct Bot {
+    skill: BotSkill,
+    /// Length of a tick, which its inputs cover in whole sub-steps.
+    dt: f32,
+    clock: StepClock,
+    sequence: u32,
+    pitch: f32,
+    yaw: f32,
+    /// Nodes still to walk through, the next one first.
+    path: Vec<usize>,
+    /// The node last walked through, which the way to the next one leaves.
+    reached: Option<usize>,
+    target: Option<Uuid>,
+    /// Tick at which the current target came into view.
+    target_seen_at: u64,
+    /// Where the bot stood on the previous tick and for how many ticks it
+    /// has been stuck there while trying to move.
+    last_position: Point3<f32>,
+    stuck_ticks: u32,
+}
+
+impl BotDifficulty {
+    pub fn skill(&self, tick_rate_millis: u64) -> BotSkill {
+        let (aim_error, reaction_millis, turn_speed) = match self {
+            Self::Easy => (0.15, 600, 3.0),
+            Self::Normal => (0.06, 350, 6.0),
+            Self::Hard => (0.02, 150, 12.0),
+        };
+        BotSkill {
+            aim_error,
+            reaction_ticks: (reaction_millis as u64).div_ceil(tick_rate_millis),
+            turn_speed,
+        }
+    }
+}
+
+impl Bot {
+    /// Ticks without moving after which a bot gives up on its path.
+    const STUCK_TICKS: u32 = 10;
+    /// Ticks between new paths while chasing a target that moves.
+    const CHASE_REPATH_TICKS: u64 = 20;
+    /// Horizontal distance at which a waypoint counts as reached.
+    const WAYPOINT_RADIUS: f32 = 0.15;
+    /// Largest angle, in radians, between aim and target that still fires.
+    const FIRE_TOLERANCE: f32 = 0.05;
+
+    pub fn new(skill: BotSkill, dt: f32) -> Self {
+        Self {
+            skill,
+            dt,
+            clock: StepClock::default(),
+            sequence: 0,
+            pitch: 0.0,
+            yaw: 0.0,
+            path: vec![],
+            reached: None,
+            target: None,
+            target_seen_at: 0,
+            last_position: Point3::origin(),
+            stuck_ticks: 0,
+        }
+    }
+
+    /// Decides what the bot controlling `state` does this tick: one input,
+    /// and a shot once it has had time to react to an enemy and aim at it.
+    pub fn think(
+        &mut self,
+        state: &PlayerState,
+        world: &World,
+        nav: &NavGraph,
+        is_enemy: impl Fn(&Uuid) -> bool,
+        tick: u64,
+        now: u128,
+    ) -> Vec<CommandType> {
+        let Some(body) = world.player(&state.player_id).filter(|_| state.health > 0) else {
+            self.path.clear();
+            self.reached = None;
+            self.target = None;
+            return vec![];
+        };
+        let eye = body.position;
+        let feet = eye - Vector3::new(0.0, PlayerBody::HITBOX_HEIGHT, 0.0);
+        let target = self.find_target(state.player_id, eye, world, &is_enemy);
+        if target.map(|(id, _)| id) != self.target {
+            self.target = target.map(|(id, _)| id);
+            self.target_seen_at = tick;
+        }
+
+        if body.position == self.last_position && !self.path.is_empty() {
+            self.stuck_ticks += 1;
+        } else {
+            self.stuck_ticks = 0;
+        }
+        self.last_position = body.position;
+        let stuck = self.stuck_ticks >= Self::STUCK_TICKS;
+        let chasing = target.is_some() && tick.is_multiple_of(Self::CHASE_REPATH_TICKS);
+        if stuck || chasing {
+            self.path.clear();
+            self.reached = None;
+            self.stuck_ticks = 0;
+        }
+        if self.path.is_empty() {
+            self.plan(nav, feet, target.map(|(_, position)| position));
+        }
+        while let Some(&next) = self.path.first() {
+            let offset = nav.node(next) - feet;
+            if Vector3::new(offset.x, 0.0, offset.z).norm() > Self::WAYPOINT_RADIUS {
+                break;
+            }
+            self.reached = Some(self.path.remove(0));
+        }
+        let waypoint = self.path.first().map(|&node| nav.node(node));
+        let heading = waypoint
+            .map(|waypoint| Vector3::new(waypoint.x - feet.x, 0.0, waypoint.z - feet.z))
+            .and_then(|heading| heading.try_normalize(f32::EPSILON));
+
+        let mut commands = vec![];
+        match target {
+            Some((_, position)) => {
+                // Aim at the middle of the target's hitbox.
+                let center = position - Vector3::new(0.0, PlayerBody::HITBOX_HEIGHT / 2.0, 0.0);
+                let aim = (center - eye).normalize();
+                let error = self.skill.aim_error;
+                let pitch = aim.y.asin() + rand::random_range(-error..=error);
+                let yaw = aim.x.atan2(aim.z) + rand::random_range(-error..=error);
+                let aligned = self.turn_towards(pitch, yaw);
+                let reacted = tick >= self.target_seen_at + self.skill.reaction_ticks;
+                if aligned && reacted {
+                    let weapon = if state.has_weapon(Weapon::RocketLauncher) && state.rockets > 0 {
+                        Weapon::RocketLauncher
+                    } else {
+                        Weapon::Rifle
+                    };
+                    commands.push(CommandType::Fire {
+                        weapon,
+                        direction: PlayerBody::aim_direction(self.pitch, self.yaw).into(),
+                        view_time: now,
+                    });
+                }
+            }
+            None => {
+                if let Some(heading) = heading {
+                    self.turn_towards(0.0, heading.x.atan2(heading.z));
+                }
+            }
+        }
+
+        self.sequence += 1;
+        let mut input = PlayerInput {
+            sequence: self.sequence,
+            pitch: self.pitch,
+            yaw: self.yaw,
+            dt: self.clock.advance(self.dt),
+            ..PlayerInput::default()
+        };
+        if let Some(heading) = heading {
+            // Move along the heading whichever way the bot faces, the way a
+            // player strafes while looking at someone.
+            let (forward, left) = PlayerBody::forward_and_left(self.yaw);
+            let (ahead, aside) = (heading.dot(&forward), heading.dot(&left));
+            input.forward = ahead > 0.38;
+            input.backward = ahead < -0.38;
+            input.left = aside > 0.38;
+            input.right = aside < -0.38;
+            let leap = match (self.reached, self.path.first()) {
+                (Some(from), Some(&to)) => nav.edge(from, to).is_some_and(|edge| edge.jump),
+                _ => waypoint.is_some_and(|waypoint| waypoint.y > feet.y + PlayerBody::STEP_HEIGHT),
+            };
+            input.jump = stuck || leap;
+        }
+        commands.insert(0, CommandType::PlayerInput(input));
+        commands
+    }
+
+    /// The closest living enemy in plain sight of `eye`, with its position.
+    fn find_target(
+        &self,
+        player_id: Uuid,
+        eye: Point3<f32>,
+        world: &World,
+        is_enemy: impl Fn(&Uuid) -> bool,
+    ) -> Option<(Uuid, Point3<f32>)> {
+        world
+            .players
+            .iter()
+            .filter(|(id, _)| **id != player_id && is_enemy(id))
+            .filter(|(_, body)| {
+                let delta = body.position - eye;
+                world
+                    .collision_manager
+                    .segment_hit(eye, delta)
+                    .is_none_or(|hit| hit >= 1.0)
+            })
+            .map(|(id, body)| (*id, body.position))
+            .min_by(|(_, a), (_, b)| (a - eye).norm().total_cmp(&(b - eye).norm()))
+    }
+
+    /// Finds a path from `feet` to `goal`, or to a random node when there
+    /// is nothing to go after.
+    fn plan(&mut self, nav: &NavGraph, feet: Point3<f32>, goal: Option<Point3<f32>>) {
+        if nav.is_empty() {
+            return;
+        }
+        let Some(from) = nav.nearest(feet) else {
+            return;
+        };
+        let to = match goal {
+            Some(goal) => nav.nearest(goal - Vector3::new(0.0, PlayerBody::HITBOX_HEIGHT, 0.0)),
+            None => Some(rand::random_range(0..nav.len())),
+        };
+        if let Some(path) = to.and_then(|to| nav.path(from, to)) {
+            self.path = path;
+        }
+    }
+
+    /// Turns the bot's view towards `pitch` and `yaw` as far as its turn
+    /// speed allows this tick. Returns whether it got there.
+    fn turn_towards(&mut self, pitch: f32, yaw: f32) -> bool {
+        let max_turn = self.skill.turn_speed * self.dt;
+        let yaw_left = (yaw - self.yaw + PI).rem_euclid(TAU) - PI;
+        let pitch_left = pitch.clamp(-PlayerBody::MAX_PITCH, PlayerBody::MAX_PITCH) - self.pitch;
+        self.yaw = (self.yaw + yaw_left.clamp(-max_turn, max_turn)).rem_euclid(TAU);
+        self.pitch += pitch_left.clamp(-max_turn, max_turn);
+        yaw_left.abs().max(pitch_left.abs()) <= max_turn + Self::FIRE_TOLERANCE
+    }
+}
+
+impl FromStr for BotDifficulty {
+    type Err = UnknownBotDifficulty;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Self::Easy),
+            "normal" => Ok(Self::Normal),
+            "hard" => Ok(Self::Hard),
+            _ => Err(UnknownBotDifficulty(s.to_owned())),
+        }
+    }
+}
+
+impl Display for BotDifficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Easy => write!(f, "easy"),
+            Self::Normal => write!(f, "normal"),
+            Self::Hard => write!(f, "hard"),
+        }
+    }
+}
+
+impl Display for UnknownBotDifficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown bot difficulty {}, expected easy, normal or hard",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownBotDifficulty {}
@@ -0,0 +1,108 @@
+use std::{collections::HashSet, fs, io, path::Path};
+
+/// Words to keep out of chat, matched whole and regardless of case.
+#[derive(Debug, Clone, Default)]
+pub struct WordFilter {
+    words: HashSet<String>,
+}
+
+/// Lets a player send `burst` chat lines at once, then one more every
+/// `interval_ticks`.
+#[derive(Debug, Clone, Copy)]
+pub struct ChatRateLimit {
+    pub burst: u64,
+    pub interval_ticks: u64,
+}
+
+impl WordFilter {
+    pub fn new<S: AsRef<str>>(words: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            words: words
+                .into_iter()
+                .map(|word| word.as_ref().trim().to_lowercase())
+                .filter(|word| !word.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Reads a list with one word per line. Blank lines and lines starting
+    /// with `#` are skipped.
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Self::new(
+            contents
+                .lines()
+                .filter(|line| !line.trim().starts_with('#')),
+        ))
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// `text` with every listed word replaced by as many asterisks.
+    pub fn censor(&self, text: &str) -> String {
+        if self.words.is_empty() {
+            return text.to_owned();
+        }
+        let mut censored = String::with_capacity(text.len());
+        // Each piece is a run of letters and digits and the character ending it.
+        for piece in text.split_inclusive(|c: char| !c.is_alphanumeric()) {
+            let word = piece.trim_end_matches(|c: char| !c.is_alphanumeric());
+            if self.words.contains(&word.to_lowercase()) {
+                censored.extend(word.chars().map(|_| '*'));
+                censored.push_str(&piece[word.len()..]);
+            } else {
+                censored.push_str(piece);
+            }
+        }
+        censored
+    }
+}
+
+impl ChatRateLimit {
+    /// Whether a line sent at `tick` is allowed. `full_at` is the tick at
+    /// which the sender could send a whole burst again, and is pushed back
+    /// by every line allowed.
+    pub fn allow(&self, full_at: &mut u64, tick: u64) -> bool {
+        let next = (*full_at).max(tick) + self.interval_ticks;
+        if next > tick + self.burst * self.interval_ticks {
+            return false;
+        }
+        *full_at = next;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_words_are_censored_whole_and_regardless_of_case() {
+        let filter = WordFilter::new(["heck", " Darn "]);
+        assert_eq!(filter.len(), 2);
+        assert_eq!(
+            filter.censor("HECK, darn it! heckler"),
+            "****, **** it! heckler"
+        );
+        assert_eq!(WordFilter::default().censor("heck"), "heck");
+    }
+
+    #[test]
+    fn bursts_are_allowed_then_one_line_per_interval() {
+        let limit = ChatRateLimit {
+            burst: 3,
+            interval_ticks: 20,
+        };
+        let mut full_at = 0;
+        for _ in 0..3 {
+            assert!(limit.allow(&mut full_at, 100));
+        }
+        assert!(!limit.allow(&mut full_at, 100));
+        assert!(!limit.allow(&mut full_at, 119));
+        assert!(limit.allow(&mut full_at, 120));
+        assert!(!limit.allow(&mut full_at, 120));
+        assert!(limit.allow(&mut full_at, 1000));
+    }
+}
@@ -0,0 +1,498 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use clap::{Arg, ArgMatches, Command, value_parser};
+use log::LevelFilter;
+use protocol::command::{MAX_DATAGRAM_SIZE, MAX_PLAYERS};
+use protocol::config::{ConfigError, LayeredConfig, set_flag};
+use protocol::match_state::GameModeKind;
+use serde::Deserialize;
+
+use crate::bot::{BotDifficulty, BotSkill};
+use crate::chat::ChatRateLimit;
+use crate::game_mode::MatchSettings;
+
+/// Everything a server instance can be tuned with, read as a
+/// [`LayeredConfig`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub address: IpAddr,
+    pub port: u16,
+    pub tick_rate_millis: u64,
+    pub max_players: u8,
+    /// Seconds of silence after which a client counts as disconnected.
+    pub timeout_secs: u64,
+    /// Largest datagram sent, in bytes. Keeping it under the path MTU avoids
+    /// IP fragmentation.
+    pub mtu: usize,
+    /// Milliseconds a killed player waits before respawning.
+    pub respawn_delay_millis: u64,
+    pub game_mode: GameModeKind,
+    /// Frags that win a match; 0 for no limit.
+    pub frag_limit: u32,
+    /// Seconds a match lasts; 0 for no limit.
+    pub time_limit_secs: u64,
+    /// Seconds of warmup once enough players are in.
+    pub warmup_secs: u64,
+    /// Seconds between the end of a match and the next warmup.
+    pub intermission_secs: u64,
+    /// Chat lines a player may send at once before being rate limited.
+    pub chat_burst: u64,
+    /// Milliseconds it takes a rate limited player to earn another line.
+    pub chat_interval_millis: u64,
+    /// Words censored in chat, one per line.
+    pub chat_filter_file: Option<PathBuf>,
+    /// Directory every finished match's stats are written to as JSON.
+    pub stats_dir: PathBuf,
+    /// Bots fill the server up to this many players; 0 for none.
+    pub bot_count: u8,
+    pub bot_difficulty: BotDifficulty,
+    pub map_file: PathBuf,
+    pub log_level: LevelFilter,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 8003,
+            tick_rate_millis: 50,
+            max_players: MAX_PLAYERS,
+            timeout_secs: 5,
+            mtu: 1200,
+            respawn_delay_millis: 3000,
+            game_mode: GameModeKind::Deathmatch,
+            frag_limit: 20,
+            time_limit_secs: 600,
+            warmup_secs: 10,
+            intermission_secs: 10,
+            chat_burst: 5,
+            chat_interval_millis: 1000,
+            chat_filter_file: None,
+            stats_dir: PathBuf::from("match_stats"),
+            bot_count: 0,
+            bot_difficulty: BotDifficulty::Normal,
+            map_file: PathBuf::from("client/src/model/maps/map_1.json"),
+            log_level: LevelFilter::Info,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Smallest MTU with room for a full server's pings, or for the largest
+    /// reliable message, next to a player's own state.
+    pub const MIN_MTU: usize = 1024;
+
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+
+    pub fn tick_rate(&self) -> Duration {
+        Duration::from_millis(self.tick_rate_millis)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    /// Whole ticks a killed player waits, never less than the configured delay.
+    pub fn respawn_delay_ticks(&self) -> u64 {
+        self.respawn_delay_millis.div_ceil(self.tick_rate_millis)
+    }
+
+    pub fn match_settings(&self) -> MatchSettings {
+        let ticks = |secs: u64| (secs * 1000).div_ceil(self.tick_rate_millis);
+        MatchSettings {
+            frag_limit: self.frag_limit,
+            time_limit_ticks: ticks(self.time_limit_secs),
+            warmup_ticks: ticks(self.warmup_secs),
+            intermission_ticks: ticks(self.intermission_secs),
+            tick_rate_millis: self.tick_rate_millis,
+        }
+    }
+
+    pub fn chat_rate_limit(&self) -> ChatRateLimit {
+        ChatRateLimit {
+            burst: self.chat_burst,
+            interval_ticks: self.chat_interval_millis.div_ceil(self.tick_rate_millis),
+        }
+    }
+
+    pub fn bot_skill(&self) -> BotSkill {
+        self.bot_difficulty.skill(self.tick_rate_millis)
+    }
+}
+
+impl LayeredConfig for ServerConfig {
+    fn cli() -> Command {
+        Command::new("server")
+            .about("Dedicated server for mood")
+            .arg(
+                Arg::new("address")
+                    .long("address")
+                    .value_name("IP")
+                    .value_parser(value_parser!(IpAddr))
+                    .help("Address to bind to, IPv4 or IPv6 [default: 0.0.0.0]"),
+            )
+            .arg(
+                Arg::new("port")
+                    .long("port")
+                    .short('p')
+                    .value_parser(value_parser!(u16))
+                    .help("UDP port to listen on [default: 8003]"),
+            )
+            .arg(
+                Arg::new("tick_rate_millis")
+                    .long("tick-rate")
+                    .value_name("MILLIS")
+                    .value_parser(value_parser!(u64))
+                    .help("Milliseconds between simulation ticks [default: 50]"),
+            )
+            .arg(
+                Arg::new("max_players")
+                    .long("max-players")
+                    .value_parser(value_parser!(u8))
+                    .help("Players allowed at once [default: 32]"),
+            )
+            .arg(
+                Arg::new("timeout_secs")
+                    .long("timeout")
+                    .value_name("SECS")
+                    .value_parser(value_parser!(u64))
+                    .help("Seconds of silence before a client is disconnected [default: 5]"),
+            )
+            .arg(
+                Arg::new("mtu")
+                    .long("mtu")
+                    .value_name("BYTES")
+                    .value_parser(value_parser!(usize))
+                    .help("Largest datagram to send [default: 1200]"),
+            )
+            .arg(
+                Arg::new("respawn_delay_millis")
+                    .long("respawn-delay")
+                    .value_name("MILLIS")
+                    .value_parser(value_parser!(u64))
+                    .help("Milliseconds before a killed player respawns [default: 3000]"),
+            )
+            .arg(
+                Arg::new("game_mode")
+                    .long("mode")
+                    .value_parser(value_parser!(GameModeKind))
+                    .help("deathmatch (dm) or team_deathmatch (tdm) [default: deathmatch]"),
+            )
+            .arg(
+                Arg::new("frag_limit")
+                    .long("frag-limit")
+                    .value_parser(value_parser!(u32))
+                    .help("Frags that win a match, 0 for none [default: 20]"),
+            )
+            .arg(
+                Arg::new("time_limit_secs")
+                    .long("time-limit")
+                    .value_name("SECS")
+                    .value_parser(value_parser!(u64))
+                    .help("Seconds a match lasts, 0 for no limit [default: 600]"),
+            )
+            .arg(
+                Arg::new("warmup_secs")
+                    .long("warmup")
+                    .value_name("SECS")
+                    .value_parser(value_parser!(u64))
+                    .help("Seconds of warmup before a match [default: 10]"),
+            )
+            .arg(
+                Arg::new("intermission_secs")
+                    .long("intermission")
+                    .value_name("SECS")
+                    .value_parser(value_parser!(u64))
+                    .help("Seconds between a match and the next warmup [default: 10]"),
+            )
+            .arg(
+                Arg::new("chat_burst")
+                    .long("chat-burst")
+                    .value_name("LINES")
+                    .value_parser(value_parser!(u64))
+                    .help("Chat lines a player may send at once [default: 5]"),
+            )
+            .arg(
+                Arg::new("chat_interval_millis")
+                    .long("chat-interval")
+                    .value_name("MILLIS")
+                    .value_parser(value_parser!(u64))
+                    .help("Milliseconds per chat line once the burst is used [default: 1000]"),
+            )
+            .arg(
+                Arg::new("chat_filter_file")
+                    .long("chat-filter")
+                    .value_name("FILE")
+                    .value_parser(value_parser!(PathBuf))
+                    .help("Word list, one per line, to censor in chat"),
+            )
+            .arg(
+                Arg::new("stats_dir")
+                    .long("stats-dir")
+                    .value_name("DIR")
+                    .value_parser(value_parser!(PathBuf))
+                    .help("Directory to write match stats to [default: match_stats]"),
+            )
+            .arg(
+                Arg::new("bot_count")
+                    .long("bots")
+                    .value_name("COUNT")
+                    .value_parser(value_parser!(u8))
+                    .help("Fill the server with bots up to this many players [default: 0]"),
+            )
+            .arg(
+                Arg::new("bot_difficulty")
+                    .long("bot-difficulty")
+                    .value_parser(value_parser!(BotDifficulty))
+                    .help("easy, normal or hard [default: normal]"),
+            )
+            .arg(
+                Arg::new("map_file")
+                    .long("map")
+                    .value_name("FILE")
+                    .value_parser(value_parser!(PathBuf))
+                    .help("Collision map to load"),
+            )
+            .arg(
+                Arg::new("log_level")
+                    .long("log-level")
+                    .value_name("LEVEL")
+                    .value_parser(value_parser!(LevelFilter))
+                    .help("off, error, warn, info, debug or trace [default: info]"),
+            )
+    }
+
+    fn apply_flags(&mut self, matches: &ArgMatches) {
+        set_flag(matches, "address", &mut self.address);
+        set_flag(matches, "port", &mut self.port);
+        set_flag(matches, "tick_rate_millis", &mut self.tick_rate_millis);
+        set_flag(matches, "max_players", &mut self.max_players);
+        set_flag(matches, "timeout_secs", &mut self.timeout_secs);
+        set_flag(matches, "mtu", &mut self.mtu);
+        set_flag(
+            matches,
+            "respawn_delay_millis",
+            &mut self.respawn_delay_millis,
+        );
+        set_flag(matches, "game_mode", &mut self.game_mode);
+        set_flag(matches, "frag_limit", &mut self.frag_limit);
+        set_flag(matches, "time_limit_secs", &mut self.time_limit_secs);
+        set_flag(matches, "warmup_secs", &mut self.warmup_secs);
+        set_flag(matches, "intermission_secs", &mut self.intermission_secs);
+        set_flag(matches, "chat_burst", &mut self.chat_burst);
+        set_flag(
+            matches,
+            "chat_interval_millis",
+            &mut self.chat_interval_millis,
+        );
+        if let Some(path) = matches.get_one::<PathBuf>("chat_filter_file") {
+            self.chat_filter_file = Some(path.clone());
+        }
+        set_flag(matches, "stats_dir", &mut self.stats_dir);
+        set_flag(matches, "bot_count", &mut self.bot_count);
+        set_flag(matches, "bot_difficulty", &mut self.bot_difficulty);
+        set_flag(matches, "map_file", &mut self.map_file);
+        set_flag(matches, "log_level", &mut self.log_level);
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.port == 0 {
+            return Err(ConfigError::invalid("port", "must be between 1 and 65535"));
+        }
+        if !(1..=1000).contains(&self.tick_rate_millis) {
+            return Err(ConfigError::invalid(
+                "tick_rate_millis",
+                format!("must be between 1 and 1000, got {}", self.tick_rate_millis),
+            ));
+        }
+        if !(1..=MAX_PLAYERS).contains(&self.max_players) {
+            return Err(ConfigError::invalid(
+                "max_players",
+                format!(
+                    "must be between 1 and {MAX_PLAYERS}, got {}",
+                    self.max_players
+                ),
+            ));
+        }
+        if self.timeout_secs == 0 {
+            return Err(ConfigError::invalid(
+                "timeout_secs",
+                "must be at least 1 second",
+            ));
+        }
+        if !(Self::MIN_MTU..=MAX_DATAGRAM_SIZE).contains(&self.mtu) {
+            return Err(ConfigError::invalid(
+                "mtu",
+                format!(
+                    "must be between {} and {MAX_DATAGRAM_SIZE}, got {}",
+                    Self::MIN_MTU,
+                    self.mtu
+                ),
+            ));
+        }
+        if self.respawn_delay_millis > 60_000 {
+            return Err(ConfigError::invalid(
+                "respawn_delay_millis",
+                format!("must be at most 60000, got {}", self.respawn_delay_millis),
+            ));
+        }
+        for (field, secs) in [
+            ("time_limit_secs", self.time_limit_secs),
+            ("warmup_secs", self.warmup_secs),
+            ("intermission_secs", self.intermission_secs),
+        ] {
+            if secs > 86_400 {
+                return Err(ConfigError::invalid(
+                    field,
+                    format!("must be at most 86400, got {secs}"),
+                ));
+            }
+        }
+        if !(1..=100).contains(&self.chat_burst) {
+            return Err(ConfigError::invalid(
+                "chat_burst",
+                format!("must be between 1 and 100, got {}", self.chat_burst),
+            ));
+        }
+        if self.chat_interval_millis > 60_000 {
+            return Err(ConfigError::invalid(
+                "chat_interval_millis",
+                format!("must be at most 60000, got {}", self.chat_interval_millis),
+            ));
+        }
+        if let Some(path) = &self.chat_filter_file
+            && !path.is_file()
+        {
+            return Err(ConfigError::invalid(
+                "chat_filter_file",
+                format!("{} is not a readable file", path.display()),
+            ));
+        }
+        if self.bot_count > self.max_players {
+            return Err(ConfigError::invalid(
+                "bot_count",
+                format!(
+                    "must be at most max_players ({}), got {}",
+                    self.max_players, self.bot_count
+                ),
+            ));
+        }
+        if !self.map_file.is_file() {
+            return Err(ConfigError::invalid(
+                "map_file",
+                format!("{} is not a readable file", self.map_file.display()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../client/src/model/maps/map_1.json"
+    );
+
+    /// Validates the defaults with `change` made, naming the field refused.
+    fn check(change: impl FnOnce(&mut ServerConfig)) -> Result<(), &'static str> {
+        let mut config = ServerConfig {
+            map_file: PathBuf::from(MAP),
+            ..ServerConfig::default()
+        };
+        change(&mut config);
+        config.validate().map_err(|e| match e {
+            ConfigError::Invalid { field, .. } => field,
+            other => panic!("unexpected {other}"),
+        })
+    }
+
+    #[test]
+    fn tick_rate_is_between_1_and_1000_millis() {
+        assert_eq!(check(|c| c.tick_rate_millis = 0), Err("tick_rate_millis"));
+        assert_eq!(check(|c| c.tick_rate_millis = 1), Ok(()));
+        assert_eq!(check(|c| c.tick_rate_millis = 1000), Ok(()));
+        assert_eq!(
+            check(|c| c.tick_rate_millis = 1001),
+            Err("tick_rate_millis")
+        );
+    }
+
+    #[test]
+    fn max_players_is_between_1_and_the_protocol_limit() {
+        assert_eq!(check(|c| c.max_players = 0), Err("max_players"));
+        assert_eq!(check(|c| c.max_players = 1), Ok(()));
+        assert_eq!(check(|c| c.max_players = MAX_PLAYERS), Ok(()));
+        assert_eq!(
+            check(|c| c.max_players = MAX_PLAYERS + 1),
+            Err("max_players")
+        );
+    }
+
+    #[test]
+    fn mtu_is_between_the_minimum_and_the_largest_datagram() {
+        assert_eq!(check(|c| c.mtu = ServerConfig::MIN_MTU - 1), Err("mtu"));
+        assert_eq!(check(|c| c.mtu = ServerConfig::MIN_MTU), Ok(()));
+        assert_eq!(check(|c| c.mtu = MAX_DATAGRAM_SIZE), Ok(()));
+        assert_eq!(check(|c| c.mtu = MAX_DATAGRAM_SIZE + 1), Err("mtu"));
+    }
+
+    #[test]
+    fn bots_fit_within_max_players() {
+        let bots = |bot_count| {
+            check(|c| {
+                c.max_players = 4;
+                c.bot_count = bot_count;
+            })
+        };
+        assert_eq!(bots(4), Ok(()));
+        assert_eq!(bots(5), Err("bot_count"));
+    }
+
+    #[test]
+    fn map_and_chat_filter_must_exist() {
+        let missing = PathBuf::from("missing.json");
+        assert_eq!(check(|c| c.map_file = missing.clone()), Err("map_file"));
+        assert_eq!(
+            check(|c| c.chat_filter_file = Some(missing)),
+            Err("chat_filter_file")
+        );
+    }
+
+    #[test]
+    fn flags_set_their_settings() {
+        let config = ServerConfig::from_args([
+            "server",
+            "--map",
+            MAP,
+            "--address",
+            "::1",
+            "--mode",
+            "tdm",
+            "--mtu",
+            "1400",
+            "--bots",
+            "3",
+            "--bot-difficulty",
+            "hard",
+            "--log-level",
+            "debug",
+        ])
+        .unwrap();
+        assert_eq!(config.socket_addr(), "[::1]:8003".parse().unwrap());
+        assert_eq!(config.game_mode, GameModeKind::TeamDeathmatch);
+        assert_eq!(config.mtu, 1400);
+        assert_eq!(config.bot_count, 3);
+        assert_eq!(config.bot_difficulty, BotDifficulty::Hard);
+        assert_eq!(config.log_level, LevelFilter::Debug);
+    }
+}
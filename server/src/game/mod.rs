@@ -1 +0,0 @@
-pub mod player_state;
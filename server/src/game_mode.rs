@@ -0,0 +1,481 @@
+use std::collections::BTreeMap;
+
+use log::info;
+use protocol::command::CommandType;
+use protocol::match_state::{GameModeKind, MatchPhase, ScoreEntry};
+use protocol::player_state::Team;
+use uuid::Uuid;
+
+/// The rules that set one mode apart from another. The match lifecycle
+/// around them is the same for every mode, so a new mode only needs to
+/// implement this.
+pub trait GameMode {
+    fn kind(&self) -> GameModeKind;
+
+    /// Team for a player joining a match with `scores`.
+    fn assign_team(&self, scores: &BTreeMap<Uuid, ScoreEntry>) -> Option<Team>;
+
+    /// Whether `a` may hurt `b`, and whether `b` makes a spawn point unsafe for `a`.
+    fn is_enemy(&self, a: &ScoreEntry, b: &ScoreEntry) -> bool;
+
+    /// Score the killer gets for killing `victim`. Suicides always cost a point.
+    fn kill_score(&self, killer: &ScoreEntry, victim: &ScoreEntry) -> i32 {
+        if self.is_enemy(killer, victim) { 1 } else { -1 }
+    }
+
+    /// Whether the match is won, with `frag_limit` being at least one.
+    fn frag_limit_reached(&self, scores: &BTreeMap<Uuid, ScoreEntry>, frag_limit: u32) -> bool;
+
+    /// Players to move to another team to even the teams out.
+    fn rebalance(&self, _scores: &BTreeMap<Uuid, ScoreEntry>) -> Vec<(Uuid, Team)> {
+        vec![]
+    }
+}
+
+/// Everyone against everyone; the first to the frag limit wins.
+pub struct Deathmatch;
+
+/// Red against blue; the first team to the frag limit wins. Team sizes are
+/// kept within one of each other.
+pub struct TeamDeathmatch;
+
+/// Timing and limits of a match. Zero limits are never reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchSettings {
+    pub frag_limit: u32,
+    pub time_limit_ticks: u64,
+    pub warmup_ticks: u64,
+    pub intermission_ticks: u64,
+    pub tick_rate_millis: u64,
+}
+
+/// Something about the match the server has to act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchEvent {
+    /// The match went live; everyone starts over.
+    Started,
+    /// A frag or time limit was reached.
+    Ended,
+    /// The player was moved to another team.
+    TeamChanged(Uuid),
+}
+
+/// The state of the current match: its phase, the scoreboard and the
+/// messages clients have not been sent yet.
+pub struct Match {
+    mode: Box<dyn GameMode>,
+    settings: MatchSettings,
+    phase: MatchPhase,
+    /// Tick and server unix millis at which the current phase is over, if it is timed.
+    phase_end: Option<(u64, u128)>,
+    scores: BTreeMap<Uuid, ScoreEntry>,
+    outbox: Vec<CommandType>,
+}
+
+impl GameMode for Deathmatch {
+    fn kind(&self) -> GameModeKind {
+        GameModeKind::Deathmatch
+    }
+
+    fn assign_team(&self, _scores: &BTreeMap<Uuid, ScoreEntry>) -> Option<Team> {
+        None
+    }
+
+    fn is_enemy(&self, a: &ScoreEntry, b: &ScoreEntry) -> bool {
+        a.player_id != b.player_id
+    }
+
+    fn frag_limit_reached(&self, scores: &BTreeMap<Uuid, ScoreEntry>, frag_limit: u32) -> bool {
+        scores
+            .values()
+            .any(|entry| entry.score >= frag_limit as i32)
+    }
+}
+
+impl GameMode for TeamDeathmatch {
+    fn kind(&self) -> GameModeKind {
+        GameModeKind::TeamDeathmatch
+    }
+
+    fn assign_team(&self, scores: &BTreeMap<Uuid, ScoreEntry>) -> Option<Team> {
+        let [red, blue] = team_sizes(scores);
+        Some(if blue < red { Team::Blue } else { Team::Red })
+    }
+
+    fn is_enemy(&self, a: &ScoreEntry, b: &ScoreEntry) -> bool {
+        a.team != b.team
+    }
+
+    fn frag_limit_reached(&self, scores: &BTreeMap<Uuid, ScoreEntry>, frag_limit: u32) -> bool {
+        [Team::Red, Team::Blue]
+            .into_iter()
+            .any(|team| team_score(scores, team) >= frag_limit as i32)
+    }
+
+    /// Moves the lowest scoring players off the bigger team.
+    fn rebalance(&self, scores: &BTreeMap<Uuid, ScoreEntry>) -> Vec<(Uuid, Team)> {
+        let [red, blue] = team_sizes(scores);
+        let (bigger, smaller, surplus) = if red > blue {
+            (Team::Red, Team::Blue, red - blue)
+        } else {
+            (Team::Blue, Team::Red, blue - red)
+        };
+        let mut candidates: Vec<&ScoreEntry> = scores
+            .values()
+            .filter(|entry| entry.team == Some(bigger))
+            .collect();
+        candidates.sort_by_key(|entry| entry.score);
+        candidates
+            .into_iter()
+            .take(surplus / 2)
+            .map(|entry| (entry.player_id, smaller))
+            .collect()
+    }
+}
+
+/// The combined score of everyone on `team`.
+pub fn team_score(scores: &BTreeMap<Uuid, ScoreEntry>, team: Team) -> i32 {
+    scores
+        .values()
+        .filter(|entry| entry.team == Some(team))
+        .map(|entry| entry.score)
+        .sum()
+}
+
+fn team_sizes(scores: &BTreeMap<Uuid, ScoreEntry>) -> [usize; 2] {
+    let count = |team| {
+        scores
+            .values()
+            .filter(|entry| entry.team == Some(team))
+            .count()
+    };
+    [count(Team::Red), count(Team::Blue)]
+}
+
+pub fn game_mode(kind: GameModeKind) -> Box<dyn GameMode> {
+    match kind {
+        GameModeKind::Deathmatch => Box::new(Deathmatch),
+        GameModeKind::TeamDeathmatch => Box::new(TeamDeathmatch),
+    }
+}
+
+impl Match {
+    /// Players needed before the warmup starts counting down.
+    pub const MIN_PLAYERS: usize = 2;
+
+    pub fn new(mode: Box<dyn GameMode>, settings: MatchSettings) -> Self {
+        Self {
+            mode,
+            settings,
+            phase: MatchPhase::Warmup,
+            phase_end: None,
+            scores: BTreeMap::new(),
+            outbox: vec![],
+        }
+    }
+
+    pub fn kind(&self) -> GameModeKind {
+        self.mode.kind()
+    }
+
+    pub fn phase(&self) -> MatchPhase {
+        self.phase
+    }
+
+    pub fn scores(&self) -> &BTreeMap<Uuid, ScoreEntry> {
+        &self.scores
+    }
+
+    pub fn team(&self, player_id: &Uuid) -> Option<Team> {
+        self.scores.get(player_id).and_then(|entry| entry.team)
+    }
+
+    /// Whether `a` may hurt `b`. Players the match does not know about are
+    /// everyone's enemy.
+    pub fn is_enemy(&self, a: &Uuid, b: &Uuid) -> bool {
+        match (self.scores.get(a), self.scores.get(b)) {
+            (Some(a), Some(b)) => self.mode.is_enemy(a, b),
+            _ => a != b,
+        }
+    }
+
+    /// Puts a new player on the scoreboard and returns its team.
+    pub fn add_player(&mut self, player_id: Uuid, name: String) -> Option<Team> {
+        let team = self.mode.assign_team(&self.scores);
+        let entry = ScoreEntry::new(player_id, name, team);
+        self.outbox.push(CommandType::PlayerScore(entry.clone()));
+        self.scores.insert(player_id, entry);
+        team
+    }
+
+    pub fn remove_player(&mut self, player_id: &Uuid) {
+        if self.scores.remove(player_id).is_some() {
+            self.outbox.push(CommandType::ScoreRemoved {
+                player_id: *player_id,
+            });
+        }
+    }
+
+    /// Scores a kill. Only kills during the live phase count.
+    pub fn record_kill(&mut self, killer: &Uuid, victim: &Uuid) {
+        if self.phase != MatchPhase::Live {
+            return;
+        }
+        let points = match (self.scores.get(killer), self.scores.get(victim)) {
+            _ if killer == victim => -1,
+            (Some(killer), Some(victim)) => self.mode.kill_score(killer, victim),
+            _ => 0,
+        };
+        let is_frag = killer != victim && self.is_enemy(killer, victim);
+        if let Some(entry) = self.scores.get_mut(victim) {
+            entry.deaths += 1;
+            self.outbox.push(CommandType::PlayerScore(entry.clone()));
+        }
+        if let Some(entry) = self.scores.get_mut(killer) {
+            entry.score += points;
+            if is_frag {
+                entry.frags += 1;
+            }
+            self.outbox.push(CommandType::PlayerScore(entry.clone()));
+        }
+    }
+
+    /// Moves the match along to `tick`, `now` being the server unix millis.
+    pub fn update(&mut self, tick: u64, now: u128) -> Vec<MatchEvent> {
+        let mut events = vec![];
+        for (player_id, team) in self.mode.rebalance(&self.scores) {
+            if let Some(entry) = self.scores.get_mut(&player_id) {
+                info!("moving {} to {team:?} to balance the teams", entry.name);
+                entry.team = Some(team);
+                self.outbox.push(CommandType::PlayerScore(entry.clone()));
+                events.push(MatchEvent::TeamChanged(player_id));
+            }
+        }
+
+        let phase_over = self.phase_end.is_some_and(|(end_tick, _)| tick >= end_tick);
+        let enough_players = self.scores.len() >= Self::MIN_PLAYERS;
+        match self.phase {
+            MatchPhase::Warmup if !enough_players && self.phase_end.is_some() => {
+                self.set_phase(MatchPhase::Warmup, None, tick, now);
+            }
+            MatchPhase::Warmup if enough_players && self.phase_end.is_none() => {
+                self.set_phase(
+                    MatchPhase::Warmup,
+                    Some(self.settings.warmup_ticks),
+                    tick,
+                    now,
+                );
+            }
+            MatchPhase::Warmup if enough_players && phase_over => {
+                for entry in self.scores.values_mut() {
+                    entry.frags = 0;
+                    entry.deaths = 0;
+                    entry.score = 0;
+                    self.outbox.push(CommandType::PlayerScore(entry.clone()));
+                }
+                let time_limit =
+                    (self.settings.time_limit_ticks > 0).then_some(self.settings.time_limit_ticks);
+                self.set_phase(MatchPhase::Live, time_limit, tick, now);
+                events.push(MatchEvent::Started);
+            }
+            MatchPhase::Live if self.scores.is_empty() => {
+                self.set_phase(MatchPhase::Warmup, None, tick, now);
+            }
+            MatchPhase::Live
+                if phase_over
+                    || (self.settings.frag_limit > 0
+                        && self
+                            .mode
+                            .frag_limit_reached(&self.scores, self.settings.frag_limit)) =>
+            {
+                self.set_phase(
+                    MatchPhase::Intermission,
+                    Some(self.settings.intermission_ticks),
+                    tick,
+                    now,
+                );
+                events.push(MatchEvent::Ended);
+            }
+            MatchPhase::Intermission if phase_over => {
+                self.set_phase(MatchPhase::Warmup, None, tick, now);
+            }
+            _ => {}
+        }
+        events
+    }
+
+    /// Everything a client needs to catch up with the match.
+    pub fn full_state(&self) -> Vec<CommandType> {
+        let mut messages = vec![self.state_message()];
+        messages.extend(self.scores.values().cloned().map(CommandType::PlayerScore));
+        messages
+    }
+
+    /// Messages for every client about changes since the last call.
+    pub fn take_messages(&mut self) -> Vec<CommandType> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    fn set_phase(&mut self, phase: MatchPhase, duration_ticks: Option<u64>, tick: u64, now: u128) {
+        self.phase = phase;
+        self.phase_end = duration_ticks.map(|ticks| {
+            let millis = ticks as u128 * self.settings.tick_rate_millis as u128;
+            (tick + ticks, now + millis)
+        });
+        info!("{} match is now {phase:?}", self.mode.kind());
+        self.outbox.push(self.state_message());
+    }
+
+    fn state_message(&self) -> CommandType {
+        CommandType::MatchState {
+            mode: self.mode.kind(),
+            phase: self.phase,
+            phase_ends_at: self.phase_end.map(|(_, at)| at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: MatchSettings = MatchSettings {
+        frag_limit: 3,
+        time_limit_ticks: 100,
+        warmup_ticks: 10,
+        intermission_ticks: 20,
+        tick_rate_millis: 50,
+    };
+
+    fn player(id: u128) -> Uuid {
+        Uuid::from_u128(id)
+    }
+
+    fn new_match(kind: GameModeKind, players: u128) -> Match {
+        let mut game_match = Match::new(game_mode(kind), SETTINGS);
+        for id in 1..=players {
+            game_match.add_player(player(id), format!("player {id}"));
+        }
+        game_match
+    }
+
+    /// Runs `ticks` updates from `start`, collecting every event.
+    fn run(game_match: &mut Match, start: u64, ticks: u64) -> Vec<MatchEvent> {
+        (start..start + ticks)
+            .flat_map(|tick| game_match.update(tick, tick as u128 * 50))
+            .collect()
+    }
+
+    #[test]
+    fn warmup_waits_for_enough_players() {
+        let mut game_match = new_match(GameModeKind::Deathmatch, 1);
+        assert!(run(&mut game_match, 0, 50).is_empty());
+        assert_eq!(game_match.phase(), MatchPhase::Warmup);
+
+        game_match.add_player(player(2), String::from("late"));
+        game_match.update(50, 2500);
+        assert!(run(&mut game_match, 51, 9).is_empty());
+        assert_eq!(run(&mut game_match, 60, 1), [MatchEvent::Started]);
+        assert_eq!(game_match.phase(), MatchPhase::Live);
+    }
+
+    #[test]
+    fn frag_limit_ends_the_match_and_a_new_one_follows() {
+        let mut game_match = new_match(GameModeKind::Deathmatch, 2);
+        game_match.record_kill(&player(1), &player(2));
+        run(&mut game_match, 0, 11);
+        assert_eq!(game_match.phase(), MatchPhase::Live);
+        assert_eq!(game_match.scores()[&player(1)].frags, 0);
+
+        for _ in 0..3 {
+            game_match.record_kill(&player(1), &player(2));
+        }
+        game_match.record_kill(&player(2), &player(2));
+        let scores = game_match.scores();
+        assert_eq!(scores[&player(1)].frags, 3);
+        assert_eq!(scores[&player(2)].deaths, 4);
+        assert_eq!(scores[&player(2)].score, -1);
+
+        run(&mut game_match, 11, 1);
+        assert_eq!(game_match.phase(), MatchPhase::Intermission);
+        run(&mut game_match, 12, 20);
+        assert_eq!(game_match.phase(), MatchPhase::Warmup);
+        assert_eq!(run(&mut game_match, 32, 12), [MatchEvent::Started]);
+        assert_eq!(game_match.scores()[&player(1)].score, 0);
+    }
+
+    #[test]
+    fn time_limit_ends_the_match() {
+        let mut game_match = new_match(GameModeKind::Deathmatch, 2);
+        run(&mut game_match, 0, 11);
+        run(&mut game_match, 11, 99);
+        assert_eq!(game_match.phase(), MatchPhase::Live);
+        run(&mut game_match, 110, 1);
+        assert_eq!(game_match.phase(), MatchPhase::Intermission);
+    }
+
+    #[test]
+    fn team_kills_cost_points_and_teams_win_together() {
+        let mut game_match = new_match(GameModeKind::TeamDeathmatch, 4);
+        let red: Vec<Uuid> = (1..=4)
+            .map(player)
+            .filter(|id| game_match.team(id) == Some(Team::Red))
+            .collect();
+        let blue: Vec<Uuid> = (1..=4)
+            .map(player)
+            .filter(|id| game_match.team(id) == Some(Team::Blue))
+            .collect();
+        assert_eq!((red.len(), blue.len()), (2, 2));
+        assert!(!game_match.is_enemy(&red[0], &red[1]));
+        assert!(game_match.is_enemy(&red[0], &blue[0]));
+        run(&mut game_match, 0, 11);
+
+        game_match.record_kill(&red[0], &red[1]);
+        assert_eq!(game_match.scores()[&red[0]].score, -1);
+        assert_eq!(game_match.scores()[&red[0]].frags, 0);
+        game_match.record_kill(&red[0], &blue[0]);
+        game_match.record_kill(&red[1], &blue[1]);
+        game_match.record_kill(&red[1], &blue[0]);
+        run(&mut game_match, 11, 1);
+        assert_eq!(game_match.phase(), MatchPhase::Live);
+        game_match.record_kill(&red[0], &blue[1]);
+        assert_eq!(team_score(game_match.scores(), Team::Red), 3);
+        run(&mut game_match, 12, 1);
+        assert_eq!(game_match.phase(), MatchPhase::Intermission);
+    }
+
+    #[test]
+    fn teams_are_rebalanced_when_players_leave() {
+        let mut game_match = new_match(GameModeKind::TeamDeathmatch, 4);
+        let blue: Vec<Uuid> = (1..=4)
+            .map(player)
+            .filter(|id| game_match.team(id) == Some(Team::Blue))
+            .collect();
+        for id in &blue {
+            game_match.remove_player(id);
+        }
+        let events = game_match.update(0, 0);
+        assert_eq!(events.len(), 1);
+        let [red, blue] = team_sizes(game_match.scores());
+        assert_eq!((red, blue), (1, 1));
+        assert!(game_match.update(1, 50).is_empty());
+    }
+
+    #[test]
+    fn changes_are_queued_for_clients() {
+        let mut game_match = new_match(GameModeKind::Deathmatch, 2);
+        assert_eq!(game_match.take_messages().len(), 2);
+        game_match.update(0, 1000);
+        let messages = game_match.take_messages();
+        assert_eq!(
+            messages,
+            [CommandType::MatchState {
+                mode: GameModeKind::Deathmatch,
+                phase: MatchPhase::Warmup,
+                phase_ends_at: Some(1500),
+            }]
+        );
+        assert_eq!(game_match.full_state().len(), 3);
+        assert!(game_match.take_messages().is_empty());
+    }
+}
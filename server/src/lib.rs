@@ -1,169 +1,1655 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     io,
-    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
-    rc::Rc,
-    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    net::{IpAddr, SocketAddr, UdpSocket},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
-use command::{Command, CommandType};
-use game::player_state::PlayerState;
+use bot::{Bot, BotSkill};
+use chat::{ChatRateLimit, WordFilter};
+use config::ServerConfig;
+use game::collision_manager::CollisionManager;
+use game::hitscan::{self, HitboxHistory};
+use game::map::{CollisionMapLoader, SpawnPoint};
+use game::navigation::{NavGraph, Reach};
+use game::pickup::Pickups;
+use game::player_body::PlayerBody;
+use game::projectile;
+use game::spawn;
+use game::world::World;
+use game_mode::{Match, MatchEvent};
 use log::{error, info, log, warn};
+use nalgebra::{Point3, Vector3};
+use player::Player;
+use protocol::chat::ChatChannel;
+use protocol::command::{
+    Command, CommandType, MAX_DATAGRAM_SIZE, PROTOCOL_VERSION, RejectReason, Weapon, unix_millis,
+};
+use protocol::connection::{Connection, Packet, SessionToken};
+use protocol::match_state::{GameModeKind, MatchPhase};
+use protocol::player_state::Team;
+use protocol::snapshot::{EntityState, ProjectileState, Snapshot};
+use stats::{CombatStats, MatchReport, PlayerReport, TeamReport};
+use tick::{Clock, SystemClock, TickScheduler, TickStats};
+use uuid::Uuid;
 
-mod command;
-mod game;
+mod bot;
+mod chat;
+pub mod config;
+mod game_mode;
+mod player;
+mod stats;
+pub mod tick;
 
 pub struct Server {
     socket: UdpSocket,
     input_commands: VecDeque<InputCommand>,
-    player_states: HashMap<SocketAddr, PlayerState>,
-    last_packet_sent: HashMap<SocketAddr, Instant>,
-    last_tick: Instant,
-    tick_rate: Duration,
+    connections: HashMap<SessionToken, Connection>,
+    /// Where each session was last heard from; replies go there.
+    addresses: HashMap<SessionToken, SocketAddr>,
+    /// Sessions handed to addresses that have not sent their token back yet.
+    handshakes: HashMap<SocketAddr, SessionToken>,
+    players: HashMap<SessionToken, Player>,
+    /// Sessions played by the server itself. They have a player but no
+    /// connection or address.
+    bots: HashMap<SessionToken, Bot>,
+    bot_count: u8,
+    bot_skill: BotSkill,
+    last_packet_sent: HashMap<SessionToken, Instant>,
+    banned_addrs: HashSet<IpAddr>,
+    map: String,
+    world: World,
+    nav_graph: NavGraph,
+    spawn_points: Vec<SpawnPoint>,
+    pickups: Pickups,
+    respawn_delay_ticks: u64,
+    hitbox_history: HitboxHistory,
+    game_match: Match,
+    chat_rate_limit: ChatRateLimit,
+    word_filter: WordFilter,
+    stats_dir: PathBuf,
+    /// Tick the current match went live at.
+    match_started_tick: u64,
+    scheduler: TickScheduler,
+    tick_stats: TickStats,
     ticks_elapsed: u64,
+    max_players: u8,
+    timeout: Duration,
+    mtu: usize,
 }
 
 struct InputCommand {
     command: Command,
-    src_addr: SocketAddr,
+    session: SessionToken,
 }
 impl Server {
-    const MAX_PLAYERS: u8 = 32;
-    pub fn new(server_addr: Ipv4Addr, port: u16, tick_rate_in_millis: u64) -> io::Result<Self> {
-        let addr = SocketAddr::new(IpAddr::V4(server_addr), port);
-        let socket = UdpSocket::bind(addr)?;
+    /// How long a disconnected player keeps its slot and state for a reconnect.
+    const RECONNECT_GRACE: Duration = Duration::from_secs(30);
+    /// How often tick duration statistics are logged.
+    const STATS_INTERVAL: Duration = Duration::from_secs(10);
+    /// How often every client is told everyone's ping.
+    const PING_INTERVAL: Duration = Duration::from_secs(1);
+    pub fn new(config: &ServerConfig) -> io::Result<Self> {
+        let socket = UdpSocket::bind(config.socket_addr())?;
         socket.set_nonblocking(true)?;
+        let map_file = config.map_file.to_string_lossy();
+        let map = config
+            .map_file
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(&map_file)
+            .to_owned();
+        let map_loader = CollisionMapLoader::from_file(&map_file)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{map_file}: {e}")))?;
+        let word_filter = match &config.chat_filter_file {
+            Some(path) => {
+                let word_filter = WordFilter::from_file(path)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+                info!("censoring {} words in chat", word_filter.len());
+                word_filter
+            }
+            None => WordFilter::default(),
+        };
+        let collision_manager = map_loader.load();
+        let nav_graph =
+            Self::load_nav_graph(&config.map_file, &collision_manager, Reach::default());
+        let spawn_points = map_loader.spawn_points();
+        for spawn_point in &spawn_points {
+            let feet = Point3::from(spawn_point.position)
+                - Vector3::new(0.0, PlayerBody::HITBOX_HEIGHT, 0.0);
+            let walkable = nav_graph
+                .nearest(feet)
+                .is_some_and(|node| (nav_graph.node(node) - feet).norm() <= NavGraph::SPACING);
+            if !walkable {
+                warn!(
+                    "spawn point at {:?} is not on walkable ground",
+                    spawn_point.position
+                );
+            }
+        }
+        if config.bot_count > 0 {
+            info!(
+                "up to {} {} bots on a graph of {} nodes",
+                config.bot_count,
+                config.bot_difficulty,
+                nav_graph.len()
+            );
+        }
 
         Ok(Self {
             socket,
             input_commands: VecDeque::new(),
-            player_states: HashMap::new(),
+            connections: HashMap::new(),
+            addresses: HashMap::new(),
+            handshakes: HashMap::new(),
+            players: HashMap::new(),
+            bots: HashMap::new(),
+            bot_count: config.bot_count,
+            bot_skill: config.bot_skill(),
             last_packet_sent: HashMap::new(),
-            last_tick: Instant::now(),
-            tick_rate: Duration::from_millis(tick_rate_in_millis),
+            banned_addrs: HashSet::new(),
+            map,
+            world: World::new(collision_manager),
+            nav_graph,
+            spawn_points,
+            pickups: map_loader.pickups(),
+            respawn_delay_ticks: config.respawn_delay_ticks(),
+            hitbox_history: HitboxHistory::default(),
+            game_match: Match::new(
+                game_mode::game_mode(config.game_mode),
+                config.match_settings(),
+            ),
+            chat_rate_limit: config.chat_rate_limit(),
+            word_filter,
+            stats_dir: config.stats_dir.clone(),
+            match_started_tick: 0,
+            scheduler: TickScheduler::new(config.tick_rate(), Instant::now()),
+            tick_stats: TickStats::new(config.tick_rate()),
             ticks_elapsed: 0,
+            max_players: config.max_players,
+            timeout: config.timeout(),
+            mtu: config.mtu,
         })
     }
 
+    /// The navigation graph saved next to `map_file`, or one built from
+    /// `collision_manager` if there is none for this `reach`.
+    fn load_nav_graph(
+        map_file: &Path,
+        collision_manager: &CollisionManager,
+        reach: Reach,
+    ) -> NavGraph {
+        let nav_file = NavGraph::file_for_map(map_file);
+        if nav_file.is_file() {
+            match NavGraph::from_file(&nav_file) {
+                Ok(graph) if graph.reach() == Some(reach) => {
+                    info!("navigation graph loaded from {}", nav_file.display());
+                    return graph;
+                }
+                Ok(_) => warn!(
+                    "{} was built with different reach parameters, building a new one",
+                    nav_file.display()
+                ),
+                Err(e) => warn!("{}: {e}, building a new one", nav_file.display()),
+            }
+        }
+        NavGraph::build(collision_manager, reach)
+    }
+
+    /// Refuses any future join from `ip` and drops it if it is currently connected.
+    pub fn ban(&mut self, ip: IpAddr) {
+        self.banned_addrs.insert(ip);
+        let banned: Vec<SessionToken> = self
+            .addresses
+            .iter()
+            .filter(|(_, src_addr)| src_addr.ip() == ip)
+            .map(|(&session, _)| session)
+            .collect();
+        for session in banned {
+            self.remove_session(session);
+        }
+    }
+
     pub fn run(&mut self) {
-        let mut buffer = [0; 1024];
+        let clock = SystemClock;
         loop {
-            self.poll_connections(&mut buffer);
-
-            let now = Instant::now();
-            if now.duration_since(self.last_tick) >= self.tick_rate {
-                self.process_game_tick();
-                self.last_tick = now;
-                self.ticks_elapsed += 1;
-                self.input_commands.clear();
-                self.cull_dead_connections();
-                self.emit_game_state();
-            }
-            std::thread::sleep(Duration::from_millis(1));
-        }
-    }
-
-    fn poll_connections(&mut self, buffer: &mut [u8]) {
-        match self.socket.recv_from(buffer) {
-            Ok((number_of_bytes, src_addr)) => {
-                if let Ok(command) = Command::deserialize(&buffer[..number_of_bytes]) {
-                    log!(
-                        command.command_type.log_level(),
-                        "{} sent {:?}",
-                        src_addr,
-                        command.command_type
-                    );
-                    self.input_commands
-                        .push_back(InputCommand { command, src_addr });
-                } else {
-                    warn!("{src_addr} sent an invalid command");
+            let next_tick = self.update(&clock);
+            clock.sleep_until(next_tick);
+        }
+    }
+
+    /// Reads every waiting datagram and runs each tick that is due by
+    /// `clock`. Returns when the next tick is due.
+    pub fn update(&mut self, clock: &impl Clock) -> Instant {
+        self.poll_connections(clock.now());
+        for _ in 0..self.scheduler.due_ticks(clock.now()) {
+            let started = clock.now();
+            self.tick(started);
+            self.tick_stats
+                .record(clock.now().saturating_duration_since(started));
+        }
+
+        let report_every =
+            (Self::STATS_INTERVAL.as_millis() / self.scheduler.tick_rate().as_millis()) as usize;
+        if self.tick_stats.len() >= report_every.max(1) {
+            if let Some(summary) = self.tick_stats.summary() {
+                info!("{summary}, {} skipped", self.scheduler.skipped_ticks());
+            }
+            self.tick_stats.reset();
+        }
+        self.scheduler.next_tick()
+    }
+
+    /// Runs one tick of the simulation as of `now`.
+    fn tick(&mut self, now: Instant) {
+        self.balance_bots();
+        self.drive_bots();
+        self.process_game_tick();
+        self.ticks_elapsed += 1;
+        self.cull_dead_connections(now);
+        self.emit_game_state(now);
+    }
+
+    pub fn ticks_elapsed(&self) -> u64 {
+        self.ticks_elapsed
+    }
+
+    /// Reads datagrams until the socket has none left, as received at `now`.
+    fn poll_connections(&mut self, now: Instant) {
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        loop {
+            let (number_of_bytes, src_addr) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    error!("{e}");
+                    return;
                 }
+            };
+            self.receive_packet(&buffer[..number_of_bytes], src_addr, now);
+        }
+    }
+
+    fn receive_packet(&mut self, datagram: &[u8], src_addr: SocketAddr, now: Instant) {
+        let Ok(packet) = Packet::deserialize(datagram) else {
+            warn!("{src_addr} sent an invalid packet");
+            return;
+        };
+        let Some(session) = self.identify(&packet, src_addr) else {
+            warn!("{src_addr} sent a packet for an unknown session or without a join");
+            return;
+        };
+        self.last_packet_sent.insert(session, now);
+        if let Some(player) = self.players.get_mut(&session) {
+            player.network.record_received(datagram.len());
+        }
+        let Some(connection) = self.connections.get_mut(&session) else {
+            return;
+        };
+        for command in connection.receive(packet, now) {
+            log!(
+                command.command_type.log_level(),
+                "{} sent {:?}",
+                src_addr,
+                command.command_type
+            );
+            self.input_commands
+                .push_back(InputCommand { command, session });
+        }
+    }
+
+    /// Finds the session `packet` from `src_addr` belongs to. Packets without
+    /// a token continue a handshake, or start one if they carry a join and
+    /// fewer than `max_players` handshakes are pending; packets with one must
+    /// name a live session and move it to `src_addr` if the client's address
+    /// changed.
+    fn identify(&mut self, packet: &Packet, src_addr: SocketAddr) -> Option<SessionToken> {
+        let Some(session) = packet.session else {
+            if let Some(&session) = self.handshakes.get(&src_addr) {
+                return Some(session);
             }
-            Err(e) if e.kind() != io::ErrorKind::WouldBlock => {
-                error!("{e}");
+            let joins = packet
+                .reliable
+                .iter()
+                .any(|(_, command)| matches!(command.command_type, CommandType::PlayerJoin { .. }));
+            if !joins {
+                return None;
             }
-            _ => {}
+            // Joins that never come back with their token hold a session
+            // until they time out, so only so many may wait at once.
+            if self.handshakes.len() >= self.max_players as usize {
+                warn!(
+                    "refusing a join from {src_addr}: {} handshakes pending",
+                    self.handshakes.len()
+                );
+                return None;
+            }
+            let session = self.new_session_token();
+            let mut connection = Connection::default();
+            connection.set_session(session);
+            self.connections.insert(session, connection);
+            self.addresses.insert(session, src_addr);
+            self.handshakes.insert(src_addr, session);
+            return Some(session);
+        };
+
+        let address = self.addresses.get_mut(&session)?;
+        if *address != src_addr {
+            info!("session {session:016x} moved from {address} to {src_addr}");
+            *address = src_addr;
         }
+        self.handshakes.retain(|_, pending| *pending != session);
+        Some(session)
     }
 
-    fn process_game_tick(&mut self) {
+    fn new_session_token(&self) -> SessionToken {
         loop {
-            let Some(input_command) = self.input_commands.pop_front() else {
-                break;
+            let session = Uuid::new_v4().as_u64_pair().0;
+            if !self.connections.contains_key(&session) && !self.players.contains_key(&session) {
+                return session;
+            }
+        }
+    }
+
+    /// Adds or removes one bot so that, with the people playing, there are
+    /// as many players as bots configured.
+    fn balance_bots(&mut self) {
+        let humans = self.players.len() - self.bots.len();
+        let wanted = (self.bot_count as usize)
+            .saturating_sub(humans)
+            .min((self.max_players as usize).saturating_sub(humans));
+        if self.bots.len() > wanted {
+            if let Some(&session) = self.bots.keys().next() {
+                self.remove_session(session);
+            }
+        } else if self.bots.len() < wanted {
+            let session = self.new_session_token();
+            let name = (1..)
+                .map(|number| format!("Bot {number}"))
+                .find(|name| self.players.values().all(|player| player.name != *name))
+                .unwrap_or_default();
+            match self.accept_join(session, PROTOCOL_VERSION, name) {
+                Ok(_) => {
+                    let dt = self.scheduler.tick_rate().as_secs_f32();
+                    self.bots.insert(session, Bot::new(self.bot_skill, dt));
+                }
+                Err(reason) => warn!("could not add a bot: {reason}"),
+            }
+        }
+    }
+
+    /// Queues what every bot decides to do this tick, exactly as if its
+    /// commands had arrived from a client.
+    fn drive_bots(&mut self) {
+        let now = unix_millis();
+        for (session, bot) in &mut self.bots {
+            let Some(player) = self.players.get(session) else {
+                continue;
             };
+            let player_id = player.state.player_id;
+            let game_match = &self.game_match;
+            let commands = bot.think(
+                &player.state,
+                &self.world,
+                &self.nav_graph,
+                |other| game_match.is_enemy(&player_id, other),
+                self.ticks_elapsed,
+                now,
+            );
+            for command_type in commands {
+                self.input_commands.push_back(InputCommand {
+                    command: Command::new(command_type),
+                    session: *session,
+                });
+            }
+        }
+    }
 
+    fn process_game_tick(&mut self) {
+        let dt = self.scheduler.tick_rate().as_secs_f32();
+        for player in self.players.values_mut() {
+            player.refill_time_budget(dt);
+        }
+        let mut inputs = vec![];
+        let mut shots = vec![];
+        let mut chat = vec![];
+        while let Some(input_command) = self.input_commands.pop_front() {
             let command = input_command.command;
-            let src_addr = input_command.src_addr;
+            let session = input_command.session;
 
             match command.command_type {
-                CommandType::PlayerJoin => {
-                    if (self.player_states.len() as u8) < Self::MAX_PLAYERS {
-                        self.player_states.entry(src_addr).or_default();
-                        self.last_packet_sent.insert(src_addr, Instant::now());
+                CommandType::PlayerJoin {
+                    protocol_version,
+                    player_name,
+                } => match self.accept_join(session, protocol_version, player_name) {
+                    Ok(player_id) => {
+                        let reply = CommandType::JoinAccepted {
+                            player_id,
+                            session_token: session,
+                            tick_rate_millis: self.scheduler.tick_rate().as_millis() as u64,
+                            map: self.map.clone(),
+                        };
+                        self.send_reliable(reply, session);
+                        self.send_catch_up(session);
                     }
-                }
+                    Err(reason) => {
+                        info!("rejected join from session {session:016x}: {reason}");
+                        self.send_reliable(CommandType::JoinRejected(reason), session);
+                    }
+                },
                 CommandType::PlayerLeave => {
-                    self.player_states.remove(&src_addr);
-                    self.last_packet_sent.remove(&src_addr);
+                    self.remove_session(session);
                 }
-                CommandType::PlayerMove {
-                    position,
-                    velocity,
-                    pitch,
-                    yaw,
-                } => {
-                    self.last_packet_sent.insert(src_addr, Instant::now());
-                    if let Some(player) = self.player_states.get_mut(&src_addr) {
-                        player.update(position, velocity, pitch, yaw);
+                CommandType::SnapshotAck { tick } => {
+                    if let Some(player) = self.players.get_mut(&session) {
+                        player.acknowledge_snapshot(tick);
+                    }
+                }
+                CommandType::PlayerInput(mut input) => {
+                    if let Some(player) = self.players.get_mut(&session)
+                        && player.accept_input(&mut input)
+                    {
+                        inputs.push((player.state.player_id, input));
                     }
                 }
+                CommandType::Fire {
+                    weapon,
+                    direction,
+                    view_time,
+                } => {
+                    shots.push((session, weapon, Vector3::from(direction), view_time));
+                }
+                CommandType::ChatSend { channel, text } => {
+                    chat.push((session, channel, text));
+                }
                 _ => {}
             }
         }
+
+        let splash_hits = self.world.step(dt, &inputs);
+        self.respawn_dead_players();
+        self.update_match();
+        for player in self.players.values_mut() {
+            if let Some(body) = self.world.player(&player.state.player_id) {
+                body.write_state(&mut player.state);
+            }
+        }
+
+        let mut events = self.update_pickups(dt);
+        for hit in splash_hits {
+            events.extend(self.damage(hit.owner, hit.target, hit.damage));
+        }
+        let now = unix_millis();
+        self.hitbox_history.record(now, &self.world);
+        for (session, weapon, direction, view_time) in shots {
+            events.extend(self.fire(session, weapon, direction, view_time, now));
+        }
+        events.extend(self.game_match.take_messages());
+        for event in events {
+            self.broadcast_reliable(event);
+        }
+        for (session, channel, text) in chat {
+            self.relay_chat(session, channel, &text);
+        }
+    }
+
+    /// Logs a line of chat from `session` and passes it on to everyone on
+    /// `channel`, with listed words censored. Lines over the sender's rate
+    /// limit and lines left empty once sanitized are dropped.
+    fn relay_chat(&mut self, session: SessionToken, channel: ChatChannel, text: &str) {
+        let Some(sender) = self.players.get_mut(&session) else {
+            return;
+        };
+        if !self
+            .chat_rate_limit
+            .allow(&mut sender.chat_full_at, self.ticks_elapsed)
+        {
+            warn!("{} is chatting too fast, dropped {text:?}", sender.name);
+            return;
+        }
+        let text = self.word_filter.censor(&protocol::chat::sanitize(text));
+        if text.is_empty() {
+            return;
+        }
+        info!("[{channel}] {}: {text}", sender.name);
+
+        let sender = sender.state.player_id;
+        let team = match channel {
+            ChatChannel::All => None,
+            ChatChannel::Team => self.game_match.team(&sender),
+        };
+        let message = CommandType::ChatMessage {
+            sender,
+            channel,
+            text,
+        };
+        for (session, player) in &self.players {
+            if team.is_some() && self.game_match.team(&player.state.player_id) != team {
+                continue;
+            }
+            if let Some(connection) = self.connections.get_mut(session) {
+                queue_reliable(connection, *session, message.clone());
+            }
+        }
     }
 
-    fn emit_game_state(&self) {
-        let collected_states: Rc<[PlayerState]> = Rc::from(
-            self.player_states
-                .values()
-                .cloned()
-                .collect::<Vec<_>>()
-                .into_boxed_slice(),
+    /// Brings back pickups whose respawn time is over and hands the rest to
+    /// living players touching them. Returns the availability changes.
+    fn update_pickups(&mut self, dt: f32) -> Vec<CommandType> {
+        let availability = |pickup: usize, available| CommandType::PickupAvailability {
+            pickup: pickup as u16,
+            available,
+        };
+        let mut events: Vec<CommandType> = self
+            .pickups
+            .update(dt)
+            .into_iter()
+            .map(|pickup| availability(pickup, true))
+            .collect();
+        for player in self.players.values_mut() {
+            if player.state.health == 0 {
+                continue;
+            }
+            let Some(body) = self.world.player(&player.state.player_id) else {
+                continue;
+            };
+            for pickup in self.pickups.collect(body, &mut player.state) {
+                events.push(availability(pickup, false));
+            }
+        }
+        events
+    }
+
+    /// Fires `weapon` for `session`. Rifle shots are resolved right away
+    /// against the hitboxes as the shooter saw them at `view_time`; rockets
+    /// are launched into the world. Returns the hit and kill events caused.
+    fn fire(
+        &mut self,
+        session: SessionToken,
+        weapon: Weapon,
+        direction: Vector3<f32>,
+        view_time: u128,
+        now: u128,
+    ) -> Vec<CommandType> {
+        let Some(shooter) = self.players.get_mut(&session) else {
+            return vec![];
+        };
+        if self.game_match.phase() == MatchPhase::Intermission
+            || !direction.iter().all(|v| v.is_finite())
+            || direction.norm() < f32::EPSILON
+            || !shooter.try_fire(weapon, now)
+        {
+            return vec![];
+        }
+        shooter.combat.shots_fired += 1;
+        let shooter_id = shooter.state.player_id;
+        let Some(origin) = self.world.player(&shooter_id).map(|body| body.position) else {
+            return vec![];
+        };
+        let direction = direction.normalize();
+        match weapon {
+            Weapon::Rifle => {
+                let hitboxes = self.hitbox_history.rewind(view_time);
+                let hit = hitscan::trace(
+                    &self.world.collision_manager,
+                    &hitboxes,
+                    shooter_id,
+                    origin,
+                    direction,
+                );
+                hit.map_or_else(Vec::new, |hit| {
+                    self.damage(shooter_id, hit.player_id, hitscan::DAMAGE)
+                })
+            }
+            Weapon::RocketLauncher => {
+                self.world
+                    .spawn_projectile(shooter_id, origin, direction * projectile::SPEED);
+                vec![]
+            }
+        }
+    }
+
+    /// Takes up to `damage` health from `target` and returns the resulting
+    /// hit and kill events. A killed player leaves the world until its
+    /// respawn delay is over. Nobody is hurt during intermission, and players
+    /// only hurt teammates' health if the mode makes them enemies.
+    fn damage(&mut self, shooter: Uuid, target: Uuid, damage: u8) -> Vec<CommandType> {
+        if self.game_match.phase() == MatchPhase::Intermission
+            || (shooter != target && !self.game_match.is_enemy(&shooter, &target))
+        {
+            return vec![];
+        }
+        let Some(player) = self
+            .players
+            .values_mut()
+            .find(|player| player.state.player_id == target && player.state.health > 0)
+        else {
+            return vec![];
+        };
+
+        let damage = player.state.take_damage(damage);
+        player.combat.damage_taken += damage as u32;
+        let mut events = vec![CommandType::Hit {
+            shooter,
+            target,
+            damage,
+        }];
+        if player.state.health == 0 {
+            info!("{shooter} killed {target}");
+            events.push(CommandType::Kill {
+                killer: shooter,
+                victim: target,
+            });
+            self.world.remove_player(&target);
+            player.respawn_tick = Some(self.ticks_elapsed + self.respawn_delay_ticks);
+            self.game_match.record_kill(&shooter, &target);
+        }
+        if shooter != target
+            && let Some(attacker) = self
+                .players
+                .values_mut()
+                .find(|player| player.state.player_id == shooter)
+        {
+            attacker.combat.hits += 1;
+            attacker.combat.damage_dealt += damage as u32;
+        }
+        events
+    }
+
+    /// Brings back every dead player whose respawn delay is over.
+    fn respawn_dead_players(&mut self) {
+        let due: Vec<Uuid> = self
+            .players
+            .values()
+            .filter(|player| {
+                player
+                    .respawn_tick
+                    .is_some_and(|tick| tick <= self.ticks_elapsed)
+            })
+            .map(|player| player.state.player_id)
+            .collect();
+        for player_id in due {
+            self.respawn(player_id);
+        }
+    }
+
+    /// Moves the match along and acts on what happened: everyone starts over
+    /// when a match goes live, and players moved to another team respawn on
+    /// their new side.
+    fn update_match(&mut self) {
+        for event in self.game_match.update(self.ticks_elapsed, unix_millis()) {
+            match event {
+                MatchEvent::Started => {
+                    self.match_started_tick = self.ticks_elapsed;
+                    for player in self.players.values_mut() {
+                        player.combat = CombatStats::default();
+                    }
+                    let everyone: Vec<Uuid> = self
+                        .players
+                        .values()
+                        .map(|player| player.state.player_id)
+                        .collect();
+                    for player_id in everyone {
+                        self.respawn(player_id);
+                    }
+                }
+                MatchEvent::Ended => {
+                    for entry in self.game_match.scores().values() {
+                        info!(
+                            "{}: {} frags, {} deaths, score {}",
+                            entry.name, entry.frags, entry.deaths, entry.score
+                        );
+                    }
+                    match self.match_report().write(&self.stats_dir) {
+                        Ok(path) => info!("match stats written to {}", path.display()),
+                        Err(e) => error!("could not write match stats: {e}"),
+                    }
+                }
+                MatchEvent::TeamChanged(player_id) => self.respawn(player_id),
+            }
+        }
+    }
+
+    /// The scoreboard of the match that just ended along with each player's
+    /// ping, combat and network stats.
+    fn match_report(&self) -> MatchReport {
+        let scores = self.game_match.scores();
+        let teams = match self.game_match.kind() {
+            GameModeKind::Deathmatch => vec![],
+            GameModeKind::TeamDeathmatch => [Team::Red, Team::Blue]
+                .into_iter()
+                .map(|team| TeamReport {
+                    team,
+                    score: game_mode::team_score(scores, team),
+                })
+                .collect(),
+        };
+        let players = self
+            .players
+            .iter()
+            .filter_map(|(session, player)| {
+                Some(PlayerReport {
+                    score: scores.get(&player.state.player_id)?.clone(),
+                    ping_millis: self.ping(*session).map(|ping| ping.as_millis() as u64),
+                    combat: player.combat,
+                    network: player.network,
+                })
+            })
+            .collect();
+        let ticks = self.ticks_elapsed - self.match_started_tick;
+        MatchReport {
+            map: self.map.clone(),
+            mode: self.game_match.kind(),
+            ended_at: unix_millis() as u64,
+            duration_secs: (self.scheduler.tick_rate() * ticks as u32).as_secs_f64(),
+            teams,
+            players,
+        }
+    }
+
+    /// Round trip time to `session`, once it has acknowledged something.
+    fn ping(&self, session: SessionToken) -> Option<Duration> {
+        self.connections.get(&session)?.rtt()
+    }
+
+    /// Puts `player_id` back into the world at full health, with only the
+    /// weapons players start with.
+    fn respawn(&mut self, player_id: Uuid) {
+        self.spawn_body(player_id);
+        if let Some(player) = self
+            .players
+            .values_mut()
+            .find(|player| player.state.player_id == player_id)
+        {
+            player.state.reset_loadout();
+            player.respawn_tick = None;
+        }
+    }
+
+    /// Puts `player_id` into the world at the safest spawn point for its
+    /// team, facing the way the point does.
+    fn spawn_body(&mut self, player_id: Uuid) -> &mut PlayerBody {
+        let game_match = &self.game_match;
+        let spawn_point = spawn::choose_spawn(
+            &self.spawn_points,
+            &self.world,
+            game_match.team(&player_id),
+            |other| game_match.is_enemy(&player_id, other),
+        )
+        .cloned()
+        .unwrap_or_default();
+        let body = self
+            .world
+            .spawn_player(player_id, Point3::from(spawn_point.position));
+        body.yaw = spawn_point.yaw;
+        body
+    }
+
+    /// Registers `session` as a player, or explains why it may not join.
+    /// A repeated join from an existing player is answered with its current id.
+    fn accept_join(
+        &mut self,
+        session: SessionToken,
+        protocol_version: u32,
+        player_name: String,
+    ) -> Result<Uuid, RejectReason> {
+        if self
+            .addresses
+            .get(&session)
+            .is_some_and(|src_addr| self.banned_addrs.contains(&src_addr.ip()))
+        {
+            return Err(RejectReason::Banned);
+        }
+        if protocol_version != PROTOCOL_VERSION {
+            return Err(RejectReason::VersionMismatch {
+                server_version: PROTOCOL_VERSION,
+            });
+        }
+        if let Some(player) = self.players.get(&session) {
+            return Ok(player.state.player_id);
+        }
+        let player_name = protocol::player_state::sanitize_name(&player_name);
+        if player_name.is_empty() {
+            return Err(RejectReason::InvalidName);
+        }
+        if self.players.len() as u8 >= self.max_players
+            && let Some(&bot) = self.bots.keys().next()
+        {
+            self.remove_session(bot);
+        }
+        if self.players.len() as u8 >= self.max_players {
+            return Err(RejectReason::ServerFull);
+        }
+
+        let slot = (0..self.max_players)
+            .find(|slot| self.players.values().all(|player| player.slot != *slot))
+            .ok_or(RejectReason::ServerFull)?;
+        let player_id = Uuid::new_v4();
+        self.game_match.add_player(player_id, player_name.clone());
+        let body = self.spawn_body(player_id);
+        let player = Player::new(player_name, player_id, slot, body);
+        info!(
+            "{} joined with session {session:016x} as {player_id}",
+            player.name
         );
-        self.player_states.iter().for_each(|(src_addr, state)| {
-            let game_state = Command {
-                command_type: CommandType::Data((state.player_id, collected_states.clone())),
-                time: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis(),
-            }
-            .serialize();
-            if let Ok(serialized_state) = game_state {
-                let Ok(_) = self.socket.send_to(&serialized_state, src_addr) else {
-                    error!("failed to send data to {src_addr}");
-                    return;
+        self.players.insert(session, player);
+        Ok(player_id)
+    }
+
+    fn remove_session(&mut self, session: SessionToken) {
+        if let Some(player) = self.players.remove(&session) {
+            let network = player.network;
+            info!(
+                "{} left after {} packets ({} bytes) in and {} ({} bytes) out, ping {:?}",
+                player.name,
+                network.packets_received,
+                network.bytes_received,
+                network.packets_sent,
+                network.bytes_sent,
+                self.ping(session)
+            );
+            self.world.remove_player(&player.state.player_id);
+            self.game_match.remove_player(&player.state.player_id);
+        }
+        self.bots.remove(&session);
+        self.last_packet_sent.remove(&session);
+        self.connections.remove(&session);
+        self.addresses.remove(&session);
+        self.handshakes.retain(|_, pending| *pending != session);
+    }
+
+    /// Whether `session` is a bot or was heard from within the timeout of `now`.
+    fn is_connected(&self, session: SessionToken, now: Instant) -> bool {
+        self.bots.contains_key(&session)
+            || self
+                .last_packet_sent
+                .get(&session)
+                .is_some_and(|time| now.saturating_duration_since(*time) <= self.timeout)
+    }
+
+    /// Queues `command_type` on the reliable channel; it goes out with the next tick.
+    fn send_reliable(&mut self, command_type: CommandType, session: SessionToken) {
+        if let Some(connection) = self.connections.get_mut(&session) {
+            queue_reliable(connection, session, command_type);
+        }
+    }
+
+    /// Tells a player who just joined what the match and the map's pickups
+    /// look like.
+    fn send_catch_up(&mut self, session: SessionToken) {
+        for message in self.game_match.full_state() {
+            self.send_reliable(message, session);
+        }
+        for pickup in 0..self.pickups.spawns().len() {
+            if !self.pickups.is_available(pickup) {
+                let message = CommandType::PickupAvailability {
+                    pickup: pickup as u16,
+                    available: false,
                 };
+                self.send_reliable(message, session);
             }
-        });
+        }
+    }
+
+    fn broadcast_reliable(&mut self, command_type: CommandType) {
+        for session in self.players.keys() {
+            if let Some(connection) = self.connections.get_mut(session) {
+                queue_reliable(connection, *session, command_type.clone());
+            }
+        }
+    }
+
+    /// Sends `packet` to `src_addr` and returns how many bytes went out.
+    fn send_packet(&self, packet: &Packet, src_addr: &SocketAddr) -> Option<usize> {
+        match packet.serialize() {
+            Ok(serialized) => match self.socket.send_to(&serialized, src_addr) {
+                Ok(sent) => Some(sent),
+                Err(_) => {
+                    error!("failed to send data to {src_addr}");
+                    None
+                }
+            },
+            Err(e) => {
+                error!("failed to serialize packet for {src_addr}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Sends every connected client a packet with its pending reliable
+    /// messages. Players also get their own state in full and every other
+    /// connected player as a delta against the last snapshot they
+    /// acknowledged, plus everyone's ping now and then.
+    fn emit_game_state(&mut self, now: Instant) {
+        let outgoing = self.game_state_packets(now);
+        for (session, src_addr, packet) in &outgoing {
+            if let Some(sent) = self.send_packet(packet, src_addr)
+                && let Some(player) = self.players.get_mut(session)
+            {
+                player.network.record_sent(sent);
+            }
+        }
+    }
+
+    /// Builds the packets [`Self::emit_game_state`] sends, each within the
+    /// MTU. Reliable messages get the room first, then pings, then the
+    /// snapshot; reliable messages and pings that do not fit wait for a
+    /// later tick.
+    fn game_state_packets(&mut self, now: Instant) -> Vec<(SessionToken, SocketAddr, Packet)> {
+        let world = Snapshot {
+            tick: self.ticks_elapsed as u32,
+            entities: self
+                .players
+                .iter()
+                .filter(|(session, _)| self.is_connected(**session, now))
+                .map(|(_, player)| (player.slot, EntityState::quantize(&player.state)))
+                .collect(),
+            projectiles: self
+                .world
+                .projectiles
+                .iter()
+                .map(|projectile| {
+                    ProjectileState::quantize(
+                        projectile.id,
+                        projectile.position.into(),
+                        projectile.velocity.into(),
+                    )
+                })
+                .collect(),
+        };
+
+        let ping_every =
+            (Self::PING_INTERVAL.as_millis() / self.scheduler.tick_rate().as_millis()).max(1);
+        if self.ticks_elapsed.is_multiple_of(ping_every as u64) {
+            for player in self.players.values_mut() {
+                player.pings_due = true;
+            }
+        }
+        let pings = self
+            .players
+            .values()
+            .any(|player| player.pings_due)
+            .then(|| {
+                Command::new(CommandType::PlayerPings(
+                    self.players
+                        .iter()
+                        .filter_map(|(session, player)| {
+                            let ping = self.ping(*session)?.as_millis().min(u16::MAX as u128);
+                            Some((player.state.player_id, ping as u16))
+                        })
+                        .collect(),
+                ))
+            });
+        let pings_size = pings
+            .as_ref()
+            .and_then(|pings| pings.serialize().ok())
+            .map_or(0, |bytes| bytes.len());
+
+        let connected: HashSet<SessionToken> = self
+            .connections
+            .keys()
+            .copied()
+            .filter(|session| self.is_connected(*session, now))
+            .collect();
+        let mut outgoing = Vec::with_capacity(connected.len());
+        for (session, connection) in self.connections.iter_mut() {
+            if !connected.contains(session) {
+                continue;
+            }
+            let Some(src_addr) = self.addresses.get(session) else {
+                continue;
+            };
+            let Some(player) = self.players.get_mut(session) else {
+                if connection.has_unacked() {
+                    let budget = self.mtu.saturating_sub(Packet::MAX_HEADER_SIZE);
+                    outgoing.push((
+                        *session,
+                        *src_addr,
+                        connection.next_packet_within(now, budget),
+                    ));
+                }
+                continue;
+            };
+            let mut snapshot = world.clone();
+            snapshot.entities.remove(&player.slot);
+            let data = |snapshot| {
+                Command::new(CommandType::Data {
+                    player_id: player.state.player_id,
+                    last_input_sequence: player.last_input_sequence,
+                    local_player: player.state,
+                    snapshot,
+                })
+            };
+            let empty_data = data(vec![]);
+            let data_size = empty_data.serialize().map_or(0, |bytes| bytes.len());
+            // The snapshot's length prefix grows by up to two bytes once filled.
+            let reliable_budget = self
+                .mtu
+                .saturating_sub(Packet::MAX_HEADER_SIZE + data_size + 2);
+            let mut packet = connection.next_packet_within(now, reliable_budget);
+            packet.unreliable.push(empty_data);
+            if let Some(pings) = &pings
+                && player.pings_due
+            {
+                let size = packet.serialize().map_or(usize::MAX, |bytes| bytes.len());
+                if size + pings_size + 2 <= self.mtu {
+                    packet.unreliable.push(pings.clone());
+                    player.pings_due = false;
+                }
+            }
+            let overhead = packet.serialize().map_or(0, |bytes| bytes.len());
+            let budget = self.mtu.saturating_sub(overhead + 2);
+            let (bytes, received) = snapshot.encode(player.snapshot_baseline(), budget);
+            packet.unreliable[0] = data(bytes);
+            outgoing.push((*session, *src_addr, packet));
+            player.record_snapshot(received);
+        }
+        outgoing
     }
 
-    pub fn cull_dead_connections(&mut self) {
-        let addresses_to_remove: Vec<SocketAddr> = self
+    /// Forgets clients that went silent. Players keep their slot and state
+    /// for the reconnect grace period; everyone else is dropped right away.
+    pub fn cull_dead_connections(&mut self, now: Instant) {
+        let sessions_to_remove: Vec<SessionToken> = self
             .last_packet_sent
             .iter()
-            .filter(|(_, time)| time.elapsed() > Duration::from_secs(5))
-            .map(|(&src_addr, _)| src_addr)
+            .filter(|(session, time)| {
+                let limit = if self.players.contains_key(session) {
+                    self.timeout + Self::RECONNECT_GRACE
+                } else {
+                    self.timeout
+                };
+                now.saturating_duration_since(**time) > limit
+            })
+            .map(|(&session, _)| session)
             .collect();
 
-        for addr in &addresses_to_remove {
-            info!("Culling connection from {addr}");
-            self.last_packet_sent.remove(addr);
-            self.player_states.remove(addr);
+        for session in &sessions_to_remove {
+            info!("Culling session {session:016x}");
+            self.remove_session(*session);
+        }
+
+        if !sessions_to_remove.is_empty() {
+            info!("Current connections: {:?}", self.addresses.values());
+        }
+    }
+}
+
+/// Queues `command_type` on `connection`'s reliable channel, or logs why it
+/// cannot be sent.
+fn queue_reliable(connection: &mut Connection, session: SessionToken, command_type: CommandType) {
+    if let Err(e) = connection.send_reliable(Command::new(command_type)) {
+        error!("dropped a message to session {session:016x}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, net::Ipv4Addr};
+
+    use game::pickup::{self, PickupKind};
+    use protocol::command::MAX_PLAYERS;
+    use protocol::input::PlayerInput;
+    use protocol::match_state::ScoreEntry;
+    use protocol::player_state::{MAX_NAME_LENGTH, PlayerState};
+
+    use super::*;
+
+    /// Clock that only moves when told to, so no test ever sleeps.
+    struct ManualClock(Cell<Instant>);
+
+    impl ManualClock {
+        fn advance(&self, duration: Duration) {
+            self.0.set(self.0.get() + duration);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            self.0.get()
+        }
+
+        fn sleep_until(&self, deadline: Instant) {
+            self.0.set(self.0.get().max(deadline));
         }
+    }
+
+    fn test_config() -> ServerConfig {
+        ServerConfig {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 0,
+            map_file: concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../client/src/model/maps/map_1.json"
+            )
+            .into(),
+            ..ServerConfig::default()
+        }
+    }
+
+    fn test_server() -> (Server, ManualClock) {
+        let server = Server::new(&test_config()).unwrap();
+        let clock = ManualClock(Cell::new(Instant::now()));
+        (server, clock)
+    }
+
+    #[test]
+    fn ticks_follow_the_injected_clock() {
+        let (mut server, clock) = test_server();
+        let next_tick = server.update(&clock);
+        assert_eq!(server.ticks_elapsed(), 0);
+
+        clock.sleep_until(next_tick);
+        server.update(&clock);
+        assert_eq!(server.ticks_elapsed(), 1);
 
-        if !addresses_to_remove.is_empty() {
-            info!("Current connections: {:?}", self.player_states.keys());
+        clock.advance(Duration::from_millis(130));
+        server.update(&clock);
+        assert_eq!(server.ticks_elapsed(), 3);
+        assert_eq!(server.tick_stats.len(), 3);
+    }
+
+    #[test]
+    fn killed_players_respawn_after_the_delay() {
+        let (mut server, _) = test_server();
+        let victim = server
+            .accept_join(1, PROTOCOL_VERSION, String::from("victim"))
+            .unwrap();
+        let killer = server
+            .accept_join(2, PROTOCOL_VERSION, String::from("killer"))
+            .unwrap();
+        assert_ne!(
+            server.world.player(&victim).unwrap().position,
+            server.world.player(&killer).unwrap().position
+        );
+
+        let events = server.damage(killer, victim, PlayerState::MAX_HEALTH);
+        assert!(matches!(
+            events[..],
+            [CommandType::Hit { .. }, CommandType::Kill { .. }]
+        ));
+        assert!(server.world.player(&victim).is_none());
+        assert!(server.damage(killer, victim, 10).is_empty());
+
+        for _ in 0..server.respawn_delay_ticks {
+            server.tick(Instant::now());
         }
+        assert!(server.world.player(&victim).is_none());
+        assert_eq!(server.players[&1].state.health, 0);
+        server.tick(Instant::now());
+        assert!(server.world.player(&victim).is_some());
+        assert_eq!(server.players[&1].state.health, PlayerState::MAX_HEALTH);
+    }
+
+    #[test]
+    fn players_take_the_pickups_they_touch() {
+        let (mut server, _) = test_server();
+        let player_id = server
+            .accept_join(1, PROTOCOL_VERSION, String::from("collector"))
+            .unwrap();
+        let launcher = server
+            .pickups
+            .spawns()
+            .iter()
+            .position(|spawn| spawn.kind == PickupKind::RocketLauncher)
+            .unwrap();
+        let [x, _, z] = server.pickups.spawns()[launcher].position;
+        server
+            .world
+            .spawn_player(player_id, Point3::new(x, PlayerBody::HITBOX_HEIGHT, z));
+
+        assert_eq!(
+            server.update_pickups(0.05),
+            [CommandType::PickupAvailability {
+                pickup: launcher as u16,
+                available: false,
+            }]
+        );
+        let state = &server.players[&1].state;
+        assert!(state.has_weapon(Weapon::RocketLauncher));
+        assert_eq!(state.rockets, pickup::ROCKETS_AMOUNT);
+        assert!(server.update_pickups(0.05).is_empty());
+    }
+
+    #[test]
+    fn teammates_cannot_hurt_each_other() {
+        let (mut server, _) = test_server();
+        server.game_match = Match::new(
+            game_mode::game_mode(GameModeKind::TeamDeathmatch),
+            ServerConfig::default().match_settings(),
+        );
+        let players: Vec<Uuid> = (1..=3)
+            .map(|session| {
+                server
+                    .accept_join(session, PROTOCOL_VERSION, format!("player {session}"))
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(server.game_match.team(&players[0]), Some(Team::Red));
+        assert_eq!(server.game_match.team(&players[2]), Some(Team::Red));
+
+        assert!(server.damage(players[0], players[2], 10).is_empty());
+        assert_eq!(server.damage(players[0], players[1], 10).len(), 1);
+        assert_eq!(server.damage(players[0], players[0], 10).len(), 1);
+    }
+
+    #[test]
+    fn match_reports_count_damage_on_both_sides() {
+        let (mut server, _) = test_server();
+        let target = server
+            .accept_join(1, PROTOCOL_VERSION, String::from("target"))
+            .unwrap();
+        let shooter = server
+            .accept_join(2, PROTOCOL_VERSION, String::from("shooter"))
+            .unwrap();
+        server.damage(shooter, target, 10);
+        server.damage(shooter, target, 15);
+        server.damage(target, target, 5);
+
+        let report = server.match_report();
+        assert_eq!(report.mode, GameModeKind::Deathmatch);
+        assert!(report.teams.is_empty());
+        let combat = |name: &str| {
+            report
+                .players
+                .iter()
+                .find(|player| player.score.name == name)
+                .unwrap()
+                .combat
+        };
+        assert_eq!(combat("shooter").hits, 2);
+        assert_eq!(combat("shooter").damage_dealt, 25);
+        assert_eq!(combat("target").hits, 0);
+        assert_eq!(combat("target").damage_taken, 30);
+    }
+
+    #[test]
+    fn chat_is_rate_limited_and_stays_on_its_channel() {
+        let (mut server, clock) = test_server();
+        server.game_match = Match::new(
+            game_mode::game_mode(GameModeKind::TeamDeathmatch),
+            ServerConfig::default().match_settings(),
+        );
+        for session in 1..=3 {
+            server
+                .accept_join(session, PROTOCOL_VERSION, format!("player {session}"))
+                .unwrap();
+            server.connections.insert(session, Connection::default());
+        }
+        let received = |server: &mut Server, session| {
+            server
+                .connections
+                .get_mut(&session)
+                .unwrap()
+                .next_packet(clock.now())
+                .reliable
+                .into_iter()
+                .map(|(_, command)| command.command_type)
+                .collect::<Vec<_>>()
+        };
+
+        server.relay_chat(1, ChatChannel::Team, "  push mid\n");
+        let expected = CommandType::ChatMessage {
+            sender: server.players[&1].state.player_id,
+            channel: ChatChannel::Team,
+            text: String::from("push mid"),
+        };
+        assert_eq!(received(&mut server, 1), std::slice::from_ref(&expected));
+        assert!(received(&mut server, 2).is_empty());
+        assert_eq!(received(&mut server, 3), [expected]);
+
+        let burst = server.chat_rate_limit.burst as usize;
+        for _ in 0..burst {
+            server.relay_chat(1, ChatChannel::All, "spam");
+        }
+        assert_eq!(received(&mut server, 2).len(), burst - 1);
+    }
+
+    #[test]
+    fn bots_roam_the_map_without_leaving_it() {
+        let config = ServerConfig {
+            bot_count: 4,
+            bot_difficulty: bot::BotDifficulty::Hard,
+            warmup_secs: 1,
+            ..test_config()
+        };
+        let mut server = Server::new(&config).unwrap();
+        let mut spawned_at = HashMap::new();
+        let mut moved = HashSet::new();
+        for _ in 0..400 {
+            server.tick(Instant::now());
+            for (session, player) in &server.players {
+                assert!(server.bots.contains_key(session));
+                let Some(body) = server.world.player(&player.state.player_id) else {
+                    continue;
+                };
+                let [x, y, z] = [body.position.x, body.position.y, body.position.z];
+                assert!(
+                    (-0.5..=7.5).contains(&x),
+                    "{} left the map at {x}",
+                    player.name
+                );
+                assert!(
+                    (-0.5..=7.5).contains(&z),
+                    "{} left the map at {z}",
+                    player.name
+                );
+                assert!(
+                    y >= PlayerBody::HITBOX_HEIGHT - 0.01,
+                    "{} fell to {y}",
+                    player.name
+                );
+                if *spawned_at.entry(*session).or_insert(body.position) != body.position {
+                    moved.insert(*session);
+                }
+            }
+        }
+        assert_eq!(server.bots.len(), 4);
+        assert_eq!(moved.len(), 4);
+        let shots: u32 = server
+            .players
+            .values()
+            .map(|player| player.combat.shots_fired)
+            .sum();
+        assert!(shots > 0);
+
+        server
+            .accept_join(1, PROTOCOL_VERSION, String::from("human"))
+            .unwrap();
+        server.tick(Instant::now());
+        assert_eq!(server.players.len(), 4);
+        assert_eq!(server.bots.len(), 3);
+    }
+
+    #[test]
+    fn every_waiting_datagram_is_read_at_once() {
+        let (mut server, clock) = test_server();
+        let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        client.connect(server.socket.local_addr().unwrap()).unwrap();
+
+        let mut connection = Connection::default();
+        connection
+            .send_reliable(Command::new(CommandType::PlayerJoin {
+                protocol_version: PROTOCOL_VERSION,
+                player_name: String::from("burst"),
+            }))
+            .unwrap();
+        for tick in 0..100 {
+            let mut packet = connection.next_packet(clock.now());
+            packet
+                .unreliable
+                .push(Command::new(CommandType::SnapshotAck { tick }));
+            client.send(&packet.serialize().unwrap()).unwrap();
+        }
+
+        server.update(&clock);
+        assert_eq!(server.ticks_elapsed(), 0);
+        assert_eq!(server.input_commands.len(), 101);
+
+        clock.advance(server.scheduler.tick_rate());
+        server.update(&clock);
+        assert_eq!(server.players.len(), 1);
+        assert!(server.input_commands.is_empty());
+    }
+
+    #[test]
+    fn full_servers_send_every_datagram_within_the_mtu() {
+        let config = ServerConfig {
+            mtu: ServerConfig::MIN_MTU,
+            ..test_config()
+        };
+        let mut server = Server::new(&config).unwrap();
+        let now = Instant::now();
+        let mut clients = HashMap::new();
+        for session in 1..=MAX_PLAYERS as SessionToken {
+            let player_id = server
+                .accept_join(session, PROTOCOL_VERSION, format!("{:x<32}", session))
+                .unwrap();
+            server.connections.insert(session, Connection::default());
+            let address = SocketAddr::from((Ipv4Addr::LOCALHOST, 10_000 + session as u16));
+            server.addresses.insert(session, address);
+            server.last_packet_sent.insert(session, now);
+            // One round trip, so everyone's ping is in the pings.
+            let mut client = Connection::default();
+            let connection = server.connections.get_mut(&session).unwrap();
+            client.receive(connection.next_packet(now), now);
+            connection.receive(client.next_packet(now), now + Duration::from_millis(30));
+            clients.insert(session, client);
+            let largest = (0..Connection::MAX_RELIABLE_SIZE)
+                .rev()
+                .map(|length| {
+                    Command::new(CommandType::ChatMessage {
+                        sender: player_id,
+                        channel: ChatChannel::All,
+                        text: "x".repeat(length),
+                    })
+                })
+                .find(|command| {
+                    command.serialize().unwrap().len() + 3 <= Connection::MAX_RELIABLE_SIZE
+                })
+                .unwrap();
+            connection.send_reliable(largest).unwrap();
+            for line in 0..16 {
+                server.send_reliable(
+                    CommandType::ChatMessage {
+                        sender: player_id,
+                        channel: ChatChannel::All,
+                        text: format!("{line:0>100}"),
+                    },
+                    session,
+                );
+            }
+        }
+        let scores: Vec<CommandType> = server
+            .players
+            .values()
+            .map(|player| {
+                CommandType::PlayerScore(ScoreEntry {
+                    player_id: player.state.player_id,
+                    name: player.name.clone(),
+                    team: None,
+                    frags: u32::MAX,
+                    deaths: u32::MAX,
+                    score: i32::MIN,
+                })
+            })
+            .collect();
+        for score in scores {
+            server.broadcast_reliable(score);
+        }
+
+        let mut pinged = HashSet::new();
+        for tick in 0..40 {
+            let now = now + Connection::RESEND_INTERVAL * tick;
+            let packets = server.game_state_packets(now);
+            assert_eq!(packets.len(), MAX_PLAYERS as usize);
+            for (session, _, packet) in packets {
+                let size = packet.serialize().unwrap().len();
+                assert!(size <= server.mtu, "{size} byte datagram to {session}");
+                let client = clients.get_mut(&session).unwrap();
+                let delivered = client.receive(packet, now);
+                if delivered
+                    .iter()
+                    .any(|command| matches!(command.command_type, CommandType::PlayerPings(_)))
+                {
+                    pinged.insert(session);
+                }
+                let reply = client.next_packet(now);
+                server
+                    .connections
+                    .get_mut(&session)
+                    .unwrap()
+                    .receive(reply, now);
+            }
+        }
+        assert!(
+            server
+                .connections
+                .values()
+                .all(|connection| !connection.has_unacked())
+        );
+        assert_eq!(pinged.len(), MAX_PLAYERS as usize);
+    }
+
+    /// A client socket that has sent the server a join as `name`.
+    /// The packet is built by hand, as a misbehaving client could, so `name`
+    /// may be longer than a [`Connection`] would let through.
+    fn join_over_udp(server: &Server, name: &str) -> UdpSocket {
+        let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        client.connect(server.socket.local_addr().unwrap()).unwrap();
+        let join = Command::new(CommandType::PlayerJoin {
+            protocol_version: PROTOCOL_VERSION,
+            player_name: String::from(name),
+        });
+        let packet = Packet {
+            session: None,
+            sequence: 0,
+            ack: None,
+            ack_bits: 0,
+            reliable: vec![(0, join)],
+            unreliable: vec![],
+        };
+        client.send(&packet.serialize().unwrap()).unwrap();
+        client
+    }
+
+    #[test]
+    fn only_joins_open_a_session() {
+        let (mut server, clock) = test_server();
+        let stranger = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        stranger
+            .connect(server.socket.local_addr().unwrap())
+            .unwrap();
+        let mut connection = Connection::default();
+        for tick in 0..10 {
+            let mut packet = connection.next_packet(clock.now());
+            packet
+                .unreliable
+                .push(Command::new(CommandType::SnapshotAck { tick }));
+            stranger.send(&packet.serialize().unwrap()).unwrap();
+        }
+        server.update(&clock);
+        assert!(server.connections.is_empty());
+        assert!(server.handshakes.is_empty());
+
+        join_over_udp(&server, "joiner");
+        server.update(&clock);
+        assert_eq!(server.connections.len(), 1);
+        assert_eq!(server.handshakes.len(), 1);
+    }
+
+    #[test]
+    fn silent_clients_time_out_on_the_injected_clock() {
+        let (mut server, clock) = test_server();
+        join_over_udp(&server, "quiet");
+        server.update(&clock);
+        clock.advance(server.scheduler.tick_rate());
+        server.update(&clock);
+        assert_eq!(server.players.len(), 1);
+        let session = *server.players.keys().next().unwrap();
+
+        clock.advance(server.timeout - server.scheduler.tick_rate());
+        server.update(&clock);
+        assert!(server.is_connected(session, clock.now()));
+        clock.advance(server.scheduler.tick_rate());
+        server.update(&clock);
+        assert!(!server.is_connected(session, clock.now()));
+        assert_eq!(server.players.len(), 1);
+
+        clock.advance(Server::RECONNECT_GRACE);
+        server.update(&clock);
+        assert!(server.players.is_empty());
+        assert!(server.connections.is_empty());
+    }
+
+    #[test]
+    fn pending_handshakes_are_capped() {
+        let config = ServerConfig {
+            max_players: 2,
+            ..test_config()
+        };
+        let mut server = Server::new(&config).unwrap();
+        let clock = ManualClock(Cell::new(Instant::now()));
+        let joiners: Vec<UdpSocket> = (0..3)
+            .map(|joiner| join_over_udp(&server, &format!("joiner {joiner}")))
+            .collect();
+        server.update(&clock);
+        assert_eq!(server.handshakes.len(), 2);
+        assert_eq!(server.connections.len(), 2);
+
+        let refused = server
+            .handshakes
+            .keys()
+            .all(|address| *address != joiners[2].local_addr().unwrap());
+        assert!(refused);
+    }
+
+    #[test]
+    fn joins_with_unprintable_names_are_cleaned_or_refused() {
+        let (mut server, clock) = test_server();
+        let oversized = format!("\u{1b}[2J{}\n", "x".repeat(1400));
+        join_over_udp(&server, &oversized);
+        let blank = join_over_udp(&server, " \r\n\t ");
+
+        server.update(&clock);
+        clock.advance(server.scheduler.tick_rate());
+        server.update(&clock);
+
+        let names: Vec<&str> = server
+            .players
+            .values()
+            .map(|player| player.name.as_str())
+            .collect();
+        let expected = format!("[2J{}", "x".repeat(MAX_NAME_LENGTH - 3));
+        assert_eq!(names, [expected.as_str()]);
+
+        blank
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        let received = blank.recv(&mut buffer).unwrap();
+        let reply = Packet::deserialize(&buffer[..received]).unwrap();
+        assert!(reply.reliable.iter().any(|(_, command)| {
+            command.command_type == CommandType::JoinRejected(RejectReason::InvalidName)
+        }));
+    }
+
+    #[test]
+    fn flooding_inputs_does_not_move_players_faster() {
+        let (mut server, _) = test_server();
+        let player_id = server
+            .accept_join(1, PROTOCOL_VERSION, String::from("speeder"))
+            .unwrap();
+        let start = Point3::new(3.5, PlayerBody::HITBOX_HEIGHT, 3.5);
+        server.world.spawn_player(player_id, start);
+
+        let ticks = 10;
+        let mut sequence = 0;
+        for _ in 0..ticks {
+            for _ in 0..50 {
+                sequence += 1;
+                let input = PlayerInput {
+                    sequence,
+                    forward: true,
+                    dt: PlayerBody::MAX_STEP_DT,
+                    ..PlayerInput::default()
+                };
+                server.input_commands.push_back(InputCommand {
+                    command: Command::new(CommandType::PlayerInput(input)),
+                    session: 1,
+                });
+            }
+            server.tick(Instant::now());
+        }
+
+        let end = server.world.player(&player_id).unwrap().position;
+        let travelled = Vector3::new(end.x - start.x, 0.0, end.z - start.z).norm();
+        let elapsed = server.scheduler.tick_rate().as_secs_f32() * ticks as f32;
+        assert!(travelled > 0.5, "only moved {travelled}");
+        assert!(
+            travelled <= PlayerBody::MOVE_SPEED * elapsed + 0.01,
+            "moved {travelled} in {elapsed} seconds"
+        );
     }
 }
@@ -1,14 +1,30 @@
-use std::{io, net::Ipv4Addr};
+use std::{env, process::ExitCode};
 
-use log::info;
-use server::Server;
+use log::{error, info};
+use protocol::config::LayeredConfig;
+use server::{Server, config::ServerConfig};
 
-fn main() -> io::Result<()> {
-    env_logger::init();
-    let ip = Ipv4Addr::new(0, 0, 0, 0);
-    let port = 8003;
-    let mut server = Server::new(ip, port, 50)?;
-    info!("starting server at {ip}:{port}");
+fn main() -> ExitCode {
+    let config = match ServerConfig::from_args(env::args_os()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    env_logger::Builder::new()
+        .filter_level(config.log_level)
+        .parse_default_env()
+        .init();
+
+    let mut server = match Server::new(&config) {
+        Ok(server) => server,
+        Err(e) => {
+            error!("could not start server on {}: {e}", config.socket_addr());
+            return ExitCode::FAILURE;
+        }
+    };
+    info!("starting server at {}", config.socket_addr());
     server.run();
-    Ok(())
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+
+use game::{hitscan, player_body::PlayerBody, projectile};
+use protocol::{
+    command::Weapon, input::PlayerInput, player_state::PlayerState, snapshot::Snapshot,
+};
+use uuid::Uuid;
+
+use crate::stats::{CombatStats, NetworkStats};
+
+/// A connected player as the server sees it. `state` is what gets replicated,
+/// derived from the player's body in the server's `World`.
+pub struct Player {
+    pub name: String,
+    pub state: PlayerState,
+    pub last_input_sequence: u32,
+    /// Seconds of movement the player's inputs may still simulate. Server
+    /// time fills it, so flooding inputs cannot move anyone faster.
+    time_budget: f32,
+    /// Identifies this player inside snapshots; the lowest slot free on join.
+    pub slot: u8,
+    /// Server time from which the player may shoot again, set by the
+    /// cooldown of the weapon fired last.
+    next_shot_at: u128,
+    /// Tick at which a dead player comes back.
+    pub respawn_tick: Option<u64>,
+    /// Tick at which the player may send a full burst of chat again.
+    pub chat_full_at: u64,
+    pub combat: CombatStats,
+    pub network: NetworkStats,
+    /// Whether everyone's ping is owed to this player, waiting for a packet
+    /// with room for it.
+    pub pings_due: bool,
+    /// What this player will have reconstructed from each recent snapshot.
+    snapshot_history: VecDeque<Snapshot>,
+    acked_snapshot_tick: Option<u32>,
+}
+
+impl Player {
+    /// Snapshots older than this can no longer be used as a delta baseline.
+    const SNAPSHOT_HISTORY: usize = 32;
+    /// Most movement time saved up, which covers inputs arriving in bursts
+    /// after network jitter.
+    const MAX_TIME_BUDGET: f32 = 0.25;
+
+    pub fn new(name: String, player_id: Uuid, slot: u8, body: &PlayerBody) -> Self {
+        let mut state = PlayerState {
+            player_id,
+            ..PlayerState::default()
+        };
+        body.write_state(&mut state);
+        Self {
+            name,
+            state,
+            last_input_sequence: 0,
+            time_budget: 0.0,
+            slot,
+            next_shot_at: 0,
+            respawn_tick: None,
+            chat_full_at: 0,
+            combat: CombatStats::default(),
+            network: NetworkStats::default(),
+            pings_due: false,
+            snapshot_history: VecDeque::with_capacity(Self::SNAPSHOT_HISTORY),
+            acked_snapshot_tick: None,
+        }
+    }
+
+    /// Adds `dt` seconds of server time to what the player may simulate.
+    pub fn refill_time_budget(&mut self, dt: f32) {
+        self.time_budget = (self.time_budget + dt).min(Self::MAX_TIME_BUDGET);
+    }
+
+    /// Whether `input` should be simulated: it must be newer than every
+    /// input accepted before and hold only finite values. Its `dt` is cut
+    /// down to the whole sub-steps left in the time budget, which it uses up.
+    pub fn accept_input(&mut self, input: &mut PlayerInput) -> bool {
+        if input.sequence <= self.last_input_sequence {
+            return false;
+        }
+        if !input.dt.is_finite() || !input.pitch.is_finite() || !input.yaw.is_finite() {
+            return false;
+        }
+        self.last_input_sequence = input.sequence;
+        // Nudged up so a budget of exactly n sub-steps is not rounded to n - 1.
+        let affordable = (self.time_budget / PlayerBody::SUBSTEP_DT + 1e-3).floor() as u32;
+        let substeps = PlayerBody::substeps(input.dt).min(affordable);
+        input.dt = substeps as f32 * PlayerBody::SUBSTEP_DT;
+        self.time_budget = (self.time_budget - input.dt).max(0.0);
+        true
+    }
+
+    /// Whether the player may fire `weapon` at `now`, recording the shot and
+    /// spending its ammunition if so.
+    pub fn try_fire(&mut self, weapon: Weapon, now: u128) -> bool {
+        if self.state.health == 0
+            || now < self.next_shot_at
+            || !self.state.has_weapon(weapon)
+            || !self.state.use_ammo(weapon)
+        {
+            return false;
+        }
+        let cooldown = match weapon {
+            Weapon::Rifle => hitscan::COOLDOWN_MILLIS,
+            Weapon::RocketLauncher => projectile::COOLDOWN_MILLIS,
+        };
+        self.next_shot_at = now + cooldown;
+        true
+    }
+
+    pub fn acknowledge_snapshot(&mut self, tick: u32) {
+        if self.acked_snapshot_tick.is_none_or(|acked| tick > acked) {
+            self.acked_snapshot_tick = Some(tick);
+        }
+    }
+
+    /// The newest snapshot the client confirmed, if it is still remembered.
+    pub fn snapshot_baseline(&self) -> Option<&Snapshot> {
+        let acked = self.acked_snapshot_tick?;
+        self.snapshot_history
+            .iter()
+            .find(|snapshot| snapshot.tick == acked)
+    }
+
+    pub fn record_snapshot(&mut self, snapshot: Snapshot) {
+        if self.snapshot_history.len() == Self::SNAPSHOT_HISTORY {
+            self.snapshot_history.pop_front();
+        }
+        self.snapshot_history.push_back(snapshot);
+    }
+}
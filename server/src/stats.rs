@@ -0,0 +1,128 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use protocol::match_state::{GameModeKind, ScoreEntry};
+use protocol::player_state::Team;
+use serde::Serialize;
+
+/// What a player did in the current match beyond what the scoreboard shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CombatStats {
+    pub shots_fired: u32,
+    pub hits: u32,
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+}
+
+/// Traffic exchanged with one client since it connected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct NetworkStats {
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+/// The final standings of a match, as written to disk once it ends.
+#[derive(Debug, Serialize)]
+pub struct MatchReport {
+    pub map: String,
+    pub mode: GameModeKind,
+    /// Server unix millis at which the match ended.
+    pub ended_at: u64,
+    pub duration_secs: f64,
+    /// Each team's score, in team modes.
+    pub teams: Vec<TeamReport>,
+    pub players: Vec<PlayerReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TeamReport {
+    pub team: Team,
+    pub score: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerReport {
+    #[serde(flatten)]
+    pub score: ScoreEntry,
+    pub ping_millis: Option<u64>,
+    pub combat: CombatStats,
+    pub network: NetworkStats,
+}
+
+impl NetworkStats {
+    pub fn record_sent(&mut self, bytes: usize) {
+        self.packets_sent += 1;
+        self.bytes_sent += bytes as u64;
+    }
+
+    pub fn record_received(&mut self, bytes: usize) {
+        self.packets_received += 1;
+        self.bytes_received += bytes as u64;
+    }
+}
+
+impl MatchReport {
+    /// Writes the report as JSON into `dir`, named after when the match
+    /// ended, and returns the file's path. `dir` is created if needed.
+    pub fn write(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("match_{}.json", self.ended_at));
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(&path, json)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn reports_are_written_as_json() {
+        let report = MatchReport {
+            map: String::from("map_1"),
+            mode: GameModeKind::TeamDeathmatch,
+            ended_at: 1_700_000_000_000,
+            duration_secs: 600.0,
+            teams: vec![TeamReport {
+                team: Team::Red,
+                score: 3,
+            }],
+            players: vec![PlayerReport {
+                score: ScoreEntry {
+                    frags: 3,
+                    score: 3,
+                    ..ScoreEntry::new(Uuid::nil(), String::from("doomguy"), Some(Team::Red))
+                },
+                ping_millis: Some(42),
+                combat: CombatStats {
+                    shots_fired: 10,
+                    hits: 4,
+                    ..CombatStats::default()
+                },
+                network: NetworkStats::default(),
+            }],
+        };
+        let dir = std::env::temp_dir().join(format!("mood-stats-{}", Uuid::new_v4()));
+        let path = report.write(&dir).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(path.file_name().unwrap(), "match_1700000000000.json");
+        assert_eq!(json["mode"], "team_deathmatch");
+        assert_eq!(json["teams"][0]["team"], "red");
+        let player = &json["players"][0];
+        assert_eq!(player["name"], "doomguy");
+        assert_eq!(player["frags"], 3);
+        assert_eq!(player["ping_millis"], 42);
+        assert_eq!(player["combat"]["hits"], 4);
+        assert_eq!(player["network"]["bytes_sent"], 0);
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+/// Where the server loop gets its time from, so tests can drive it by hand.
+pub trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep_until(&self, deadline: Instant);
+}
+
+/// The real clock, sleeping the thread.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+/// Decides when ticks run. Time is accumulated against a fixed schedule, so
+/// a late tick does not push every later one back.
+#[derive(Debug)]
+pub struct TickScheduler {
+    tick_rate: Duration,
+    next_tick: Instant,
+    skipped_ticks: u64,
+}
+
+/// Durations of recent ticks, summarized now and then in the log.
+#[derive(Debug)]
+pub struct TickStats {
+    tick_rate: Duration,
+    durations: Vec<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickSummary {
+    pub ticks: usize,
+    pub mean: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    /// Ticks that took longer than the tick rate.
+    pub overruns: usize,
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if !remaining.is_zero() {
+            std::thread::sleep(remaining);
+        }
+    }
+}
+
+impl TickScheduler {
+    /// Most ticks run back to back to catch up; time beyond that is skipped.
+    const MAX_CATCH_UP: u32 = 5;
+
+    /// The first tick is due one `tick_rate` after `start`.
+    pub fn new(tick_rate: Duration, start: Instant) -> Self {
+        Self {
+            tick_rate,
+            next_tick: start + tick_rate,
+            skipped_ticks: 0,
+        }
+    }
+
+    pub fn tick_rate(&self) -> Duration {
+        self.tick_rate
+    }
+
+    pub fn next_tick(&self) -> Instant {
+        self.next_tick
+    }
+
+    /// Ticks dropped so far because the loop fell too far behind.
+    pub fn skipped_ticks(&self) -> u64 {
+        self.skipped_ticks
+    }
+
+    /// How many ticks to run at `now`, moving the schedule past them.
+    pub fn due_ticks(&mut self, now: Instant) -> u32 {
+        let mut due = 0;
+        while now >= self.next_tick && due < Self::MAX_CATCH_UP {
+            self.next_tick += self.tick_rate;
+            due += 1;
+        }
+        if now >= self.next_tick {
+            let behind = (now - self.next_tick).as_nanos() / self.tick_rate.as_nanos() + 1;
+            self.next_tick += self.tick_rate * behind as u32;
+            self.skipped_ticks += behind as u64;
+        }
+        due
+    }
+}
+
+impl TickStats {
+    pub fn new(tick_rate: Duration) -> Self {
+        Self {
+            tick_rate,
+            durations: vec![],
+        }
+    }
+
+    pub fn record(&mut self, duration: Duration) {
+        self.durations.push(duration);
+    }
+
+    pub fn len(&self) -> usize {
+        self.durations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.durations.is_empty()
+    }
+
+    pub fn summary(&self) -> Option<TickSummary> {
+        if self.durations.is_empty() {
+            return None;
+        }
+        let mut sorted = self.durations.clone();
+        sorted.sort();
+        let ticks = sorted.len();
+        let p99_index = (ticks * 99).div_ceil(100) - 1;
+        Some(TickSummary {
+            ticks,
+            mean: sorted.iter().sum::<Duration>() / ticks as u32,
+            p99: sorted[p99_index],
+            max: sorted[ticks - 1],
+            overruns: sorted.iter().filter(|d| **d > self.tick_rate).count(),
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.durations.clear();
+    }
+}
+
+impl Display for TickSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ticks: mean {:.2?}, p99 {:.2?}, max {:.2?}, {} overruns",
+            self.ticks, self.mean, self.p99, self.max, self.overruns
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn ticks_follow_the_schedule_without_drift() {
+        let start = Instant::now();
+        let mut scheduler = TickScheduler::new(TICK, start);
+        assert_eq!(scheduler.due_ticks(start + Duration::from_millis(49)), 0);
+        // Running 7 ms late must not delay the ticks after it.
+        assert_eq!(scheduler.due_ticks(start + Duration::from_millis(57)), 1);
+        assert_eq!(scheduler.next_tick(), start + 2 * TICK);
+        assert_eq!(scheduler.due_ticks(start + Duration::from_millis(100)), 1);
+        assert_eq!(scheduler.due_ticks(start + Duration::from_millis(260)), 3);
+        assert_eq!(scheduler.next_tick(), start + 6 * TICK);
+        assert_eq!(scheduler.skipped_ticks(), 0);
+    }
+
+    #[test]
+    fn long_stalls_are_skipped_instead_of_replayed() {
+        let start = Instant::now();
+        let mut scheduler = TickScheduler::new(TICK, start);
+        assert_eq!(
+            scheduler.due_ticks(start + Duration::from_millis(1020)),
+            TickScheduler::MAX_CATCH_UP
+        );
+        assert_eq!(scheduler.skipped_ticks(), 15);
+        assert_eq!(scheduler.next_tick(), start + 21 * TICK);
+    }
+
+    #[test]
+    fn stats_report_mean_p99_and_overruns() {
+        let mut stats = TickStats::new(TICK);
+        assert_eq!(stats.summary(), None);
+        for millis in 1..=100 {
+            stats.record(Duration::from_millis(millis));
+        }
+        let summary = stats.summary().unwrap();
+        assert_eq!(summary.ticks, 100);
+        assert_eq!(summary.mean, Duration::from_micros(50_500));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+        assert_eq!(summary.max, Duration::from_millis(100));
+        assert_eq!(summary.overruns, 50);
+        stats.reset();
+        assert!(stats.is_empty());
+    }
+}